@@ -0,0 +1,192 @@
+use crate::api::PdsClient;
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tracing::{info, instrument};
+
+/// One account to create from a batch file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountRecord {
+    pub handle: String,
+    pub email: String,
+    pub password: String,
+    #[serde(default)]
+    pub invite_code: Option<String>,
+}
+
+/// Outcome of creating a single account: its DID, or why it failed.
+#[derive(Debug)]
+pub struct RowResult {
+    pub row: usize,
+    pub handle: String,
+    pub outcome: std::result::Result<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub results: Vec<RowResult>,
+}
+
+impl BatchSummary {
+    pub fn created(&self) -> usize {
+        self.results.iter().filter(|r| r.outcome.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|r| r.outcome.is_err()).count()
+    }
+}
+
+/// Reads account records from a `.json` array or a CSV file with a
+/// `handle,email,password,inviteCode` header.
+pub fn load_records(path: impl AsRef<Path>) -> Result<Vec<AccountRecord>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)?;
+
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if is_json {
+        return serde_json::from_str(&content)
+            .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)));
+    }
+
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes())
+        .deserialize()
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Creates each account in turn, continuing past failures. `on_result` is
+/// called as each row finishes.
+#[instrument(skip_all, fields(count = records.len()))]
+pub async fn create_accounts(
+    client: &PdsClient,
+    records: Vec<AccountRecord>,
+    mut on_result: impl FnMut(&RowResult),
+) -> BatchSummary {
+    let mut summary = BatchSummary::default();
+
+    for (index, record) in records.into_iter().enumerate() {
+        let handle = record.handle.clone();
+        let outcome = client
+            .create_account(
+                record.handle,
+                record.email,
+                record.password,
+                record.invite_code,
+            )
+            .await
+            .map(|account| account.did)
+            .map_err(|e| e.to_string());
+
+        let result = RowResult {
+            row: index + 1,
+            handle,
+            outcome,
+        };
+        on_result(&result);
+        summary.results.push(result);
+    }
+
+    info!(
+        "Batch finished: {} created, {} failed",
+        summary.created(),
+        summary.failed()
+    );
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_load_csv_records() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("accounts.csv");
+        std::fs::write(
+            &file,
+            "handle,email,password,inviteCode\n\
+             alice.test.com,alice@test.com,pw1,invite-1\n\
+             bob.test.com,bob@test.com,pw2,\n",
+        )?;
+
+        let records = load_records(&file)?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].invite_code.as_deref(), Some("invite-1"));
+        assert_eq!(records[1].handle, "bob.test.com");
+        assert_eq!(records[1].invite_code, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_continues_past_failures() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .and(body_partial_json(serde_json::json!({
+                "handle": "alice.test.com",
+                "inviteCode": "invite-1"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:alice",
+                "handle": "alice.test.com"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .and(body_partial_json(
+                serde_json::json!({ "handle": "taken.test.com" }),
+            ))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "HandleNotAvailable",
+                "message": "Handle already taken"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("accounts.json");
+        std::fs::write(
+            &file,
+            serde_json::to_string(&serde_json::json!([
+                {
+                    "handle": "taken.test.com",
+                    "email": "taken@test.com",
+                    "password": "pw1"
+                },
+                {
+                    "handle": "alice.test.com",
+                    "email": "alice@test.com",
+                    "password": "pw2",
+                    "inviteCode": "invite-1"
+                }
+            ]))?,
+        )?;
+
+        let client = PdsClient::with_base_url(&mock_server.uri());
+        let mut reported = 0;
+        let summary = create_accounts(&client, load_records(&file)?, |_| reported += 1).await;
+
+        assert_eq!(reported, 2);
+        assert_eq!(summary.created(), 1);
+        assert_eq!(summary.failed(), 1);
+        assert!(matches!(
+            &summary.results[0].outcome,
+            Err(msg) if msg.contains("Handle already taken")
+        ));
+        assert_eq!(summary.results[1].outcome.as_deref(), Ok("did:plc:alice"));
+        Ok(())
+    }
+}
@@ -1,19 +1,185 @@
+use crate::config::NetworkConfig;
 use crate::error::{Error, Result};
+use crate::http::{read_json, request_id, send_traced, send_traced_unchecked};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument};
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct CreateAccountRequest {
     handle: String,
     email: String,
     password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invite_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateAccountResponse {
     pub did: String,
     pub handle: String,
+    #[serde(default)]
+    pub access_jwt: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateAppPasswordRequest<'a> {
+    name: &'a str,
+}
+
+/// Handle reported for accounts whose handle can't be resolved.
+const INVALID_HANDLE: &str = "handle.invalid";
+
+/// An account hosted on the PDS.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepoInfo {
+    pub did: String,
+    pub handle: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListReposResponse {
+    #[serde(default)]
+    cursor: Option<String>,
+    repos: Vec<ListedRepo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListedRepo {
+    did: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DeleteAccountRequest<'a> {
+    did: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateInviteCodeRequest {
+    use_count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateInviteCodeResponse {
+    code: String,
+}
+
+#[derive(Debug, Serialize)]
+struct UpdateAccountPasswordRequest<'a> {
+    did: &'a str,
+    password: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppPassword {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescribeRepoResponse {
+    pub handle: String,
+    pub did: String,
+    pub did_doc: serde_json::Value,
+    #[serde(default)]
+    pub handle_is_correct: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidDocument {
+    pub id: String,
+    #[serde(default)]
+    pub also_known_as: Vec<String>,
+    #[serde(default)]
+    pub verification_method: Vec<VerificationMethod>,
+    #[serde(default)]
+    pub service: Vec<DidService>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationMethod {
+    pub id: String,
+    #[serde(default)]
+    pub public_key_multibase: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidService {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub service_type: String,
+    pub service_endpoint: String,
+}
+
+/// Server metadata from `com.atproto.server.describeServer`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescribeServerResponse {
+    #[serde(default)]
+    pub available_user_domains: Vec<String>,
+}
+
+impl DescribeServerResponse {
+    /// Checks that `handle` ends in one of the server's user domains, which
+    /// are listed with a leading dot (e.g. `.example.com`).
+    pub fn check_handle(&self, handle: &str) -> Result<()> {
+        let allowed = self.available_user_domains.iter().any(|domain| {
+            let suffix = format!(".{}", domain.trim_start_matches('.'));
+            handle.len() > suffix.len() && handle.to_lowercase().ends_with(&suffix.to_lowercase())
+        });
+        if allowed {
+            return Ok(());
+        }
+        Err(Error::Config(format!(
+            "Handle {} is not under any domain this PDS serves (available: {})",
+            handle,
+            if self.available_user_domains.is_empty() {
+                "none".to_string()
+            } else {
+                self.available_user_domains.join(", ")
+            }
+        )))
+    }
+}
+
+/// Current operation data for a `did:plc` as served by the PLC directory.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlcData {
+    #[serde(default)]
+    pub rotation_keys: Vec<String>,
+}
+
+impl DidDocument {
+    /// Whether the document lists `handle` as an `at://` alias.
+    pub fn claims_handle(&self, handle: &str) -> bool {
+        let alias = format!("at://{}", handle.trim_start_matches('@'));
+        self.also_known_as
+            .iter()
+            .any(|aka| aka.eq_ignore_ascii_case(&alias))
+    }
+
+    pub fn pds_endpoint(&self) -> Option<&str> {
+        self.service
+            .iter()
+            .find(|s| {
+                s.id.ends_with("#atproto_pds") || s.service_type == "AtprotoPersonalDataServer"
+            })
+            .map(|s| s.service_endpoint.as_str())
+    }
+}
+
+impl DescribeRepoResponse {
+    pub fn document(&self) -> Result<DidDocument> {
+        serde_json::from_value(self.did_doc.clone())
+            .map_err(|e| Error::Api(format!("Failed to parse DID document: {}", e)))
+    }
 }
 
 pub struct PdsClient {
@@ -22,22 +188,36 @@ pub struct PdsClient {
 }
 
 impl PdsClient {
-    pub fn new(domain: &str) -> Self {
+    /// A client for the PDS at `network`'s domain, honoring its TLS and
+    /// port settings.
+    pub fn new(network: &NetworkConfig) -> Self {
         Self {
             client: Client::builder()
                 .danger_accept_invalid_certs(true) // For self-signed certs
                 .build()
                 .expect("Failed to create HTTP client"),
-            base_url: format!("https://pds.{}", domain),
+            base_url: network.pds_url(),
         }
     }
 
-    #[instrument(skip(self))]
+    #[cfg(test)]
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            client: Client::builder()
+                .danger_accept_invalid_certs(true) // For self-signed certs
+                .build()
+                .expect("Failed to create HTTP client"),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    #[instrument(skip(self, password), fields(request_id))]
     pub async fn create_account(
         &self,
         handle: String,
         email: String,
         password: String,
+        invite_code: Option<String>,
     ) -> Result<CreateAccountResponse> {
         let url = format!("{}/xrpc/com.atproto.server.createAccount", self.base_url);
         debug!("Creating account at: {}", url);
@@ -46,43 +226,215 @@ impl PdsClient {
             handle,
             email,
             password,
+            invite_code,
         };
 
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&request)?)
-            .send()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to create account: {}", e)))?;
+            .body(serde_json::to_string(&request)?);
+        let response = send_traced(request, "create account").await?;
+        let account: CreateAccountResponse = read_json(response).await?;
 
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(Error::Api(format!(
-                "Failed to create account: {}",
-                error_text
-            )));
+        debug!("Account created successfully with DID: {}", account.did);
+        Ok(account)
+    }
+
+    /// Mints an app password for the account owning `access_jwt`.
+    #[instrument(skip(self, access_jwt), fields(request_id))]
+    pub async fn create_app_password(&self, access_jwt: &str, name: &str) -> Result<AppPassword> {
+        let url = format!(
+            "{}/xrpc/com.atproto.server.createAppPassword",
+            self.base_url
+        );
+        debug!("Creating app password at: {}", url);
+
+        let request = self
+            .client
+            .post(&url)
+            .bearer_auth(access_jwt)
+            .json(&CreateAppPasswordRequest { name });
+        let response = send_traced(request, "create app password").await?;
+        read_json(response).await
+    }
+
+    /// Checks whether the PDS accepts `password` for the `admin` user.
+    #[instrument(skip(self, password), fields(request_id))]
+    pub async fn verify_admin_password(&self, password: &str) -> Result<bool> {
+        let url = format!("{}/xrpc/com.atproto.admin.getInviteCodes", self.base_url);
+        debug!("Verifying admin password at: {}", url);
+
+        let request = self
+            .client
+            .get(&url)
+            .query(&[("limit", "1")])
+            .basic_auth("admin", Some(password));
+        let response = send_traced_unchecked(request, "verify admin password").await?;
+
+        match response.status().as_u16() {
+            200..=299 => Ok(true),
+            401 | 403 => Ok(false),
+            status => Err(Error::Api(format!(
+                "Failed to verify admin password [request {}]: HTTP {}",
+                request_id(&response),
+                status
+            ))),
         }
+    }
 
-        let account = response
-            .json::<CreateAccountResponse>()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to parse response: {}", e)))?;
+    /// Deletes the account `did`, authorizing as the PDS admin with
+    /// `password`. This is the admin route: `com.atproto.server.deleteAccount`
+    /// also needs a token emailed to the account owner.
+    #[instrument(skip(self, password), fields(request_id))]
+    pub async fn delete_account(&self, did: &str, password: &str) -> Result<()> {
+        let url = format!("{}/xrpc/com.atproto.admin.deleteAccount", self.base_url);
+        debug!("Deleting account at: {}", url);
 
-        debug!("Account created successfully with DID: {}", account.did);
-        Ok(account)
+        let request = self
+            .client
+            .post(&url)
+            .basic_auth("admin", Some(password))
+            .json(&DeleteAccountRequest { did });
+        send_traced(request, "delete account").await?;
+
+        debug!("Deleted account {}", did);
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(request_id))]
+    pub async fn describe_server(&self) -> Result<DescribeServerResponse> {
+        let url = format!("{}/xrpc/com.atproto.server.describeServer", self.base_url);
+        debug!("Describing server at: {}", url);
+
+        let response = send_traced(self.client.get(&url), "describe server").await?;
+        read_json(response).await
+    }
+
+    /// Fails early if the PDS would reject `handle` for its domain.
+    pub async fn check_handle_domain(&self, handle: &str) -> Result<()> {
+        self.describe_server().await?.check_handle(handle)
+    }
+
+    #[instrument(skip(self), fields(request_id))]
+    pub async fn describe_repo(&self, did_or_handle: &str) -> Result<DescribeRepoResponse> {
+        let url = format!("{}/xrpc/com.atproto.repo.describeRepo", self.base_url);
+        debug!("Describing repo {} at: {}", did_or_handle, url);
+
+        let request = self.client.get(&url).query(&[("repo", did_or_handle)]);
+        let response = send_traced(request, "describe repo").await?;
+        read_json(response).await
+    }
+
+    /// Creates an invite code good for `use_count` accounts, authorizing as
+    /// the PDS admin with `admin_password`.
+    #[instrument(skip(self, admin_password), fields(request_id))]
+    pub async fn create_invite_code(&self, use_count: u32, admin_password: &str) -> Result<String> {
+        let url = format!("{}/xrpc/com.atproto.server.createInviteCode", self.base_url);
+        debug!("Creating invite code at: {}", url);
+
+        let request = self
+            .client
+            .post(&url)
+            .basic_auth("admin", Some(admin_password))
+            .json(&CreateInviteCodeRequest { use_count });
+        let response = send_traced(request, "create invite code").await?;
+        let invite: CreateInviteCodeResponse = read_json(response).await?;
+        Ok(invite.code)
+    }
+
+    /// Sets a new password for the account with `handle` (or DID),
+    /// authorizing as the PDS admin with `admin_password`.
+    #[instrument(skip(self, new_password, admin_password), fields(request_id))]
+    pub async fn reset_account_password(
+        &self,
+        handle: &str,
+        new_password: &str,
+        admin_password: &str,
+    ) -> Result<()> {
+        let did = self.describe_repo(handle).await?.did;
+
+        let url = format!(
+            "{}/xrpc/com.atproto.admin.updateAccountPassword",
+            self.base_url
+        );
+        debug!("Resetting password for {} at: {}", did, url);
+
+        let request = self
+            .client
+            .post(&url)
+            .basic_auth("admin", Some(admin_password))
+            .json(&UpdateAccountPasswordRequest {
+                did: &did,
+                password: new_password,
+            });
+        send_traced(request, "reset password").await?;
+        Ok(())
+    }
+
+    /// Every account on the PDS, following `listRepos` cursors to the end.
+    /// `listRepos` only returns DIDs, so each handle comes from
+    /// `describeRepo`; accounts that can't be described (e.g. deactivated
+    /// ones) get `handle.invalid`.
+    #[instrument(skip(self))]
+    pub async fn list_accounts(&self) -> Result<Vec<RepoInfo>> {
+        let mut dids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.list_repos_page(cursor.as_deref()).await?;
+            let done = page.repos.is_empty();
+            dids.extend(page.repos.into_iter().map(|repo| repo.did));
+            match page.cursor {
+                Some(next) if !done => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        let mut accounts = Vec::with_capacity(dids.len());
+        for did in dids {
+            let handle = match self.describe_repo(&did).await {
+                Ok(repo) => repo.handle,
+                Err(e) => {
+                    debug!("Could not describe {}: {}", did, e);
+                    INVALID_HANDLE.to_string()
+                }
+            };
+            accounts.push(RepoInfo { did, handle });
+        }
+        Ok(accounts)
+    }
+
+    #[instrument(skip(self), fields(request_id))]
+    async fn list_repos_page(&self, cursor: Option<&str>) -> Result<ListReposResponse> {
+        let url = format!("{}/xrpc/com.atproto.sync.listRepos", self.base_url);
+        debug!("Listing repos at: {}", url);
+
+        let mut request = self.client.get(&url).query(&[("limit", "500")]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+
+        let response = send_traced(request, "list repos").await?;
+        read_json(response).await
+    }
+
+    /// Fetches `{plc_url}/{did}/data`, which carries the rotation keys.
+    #[instrument(skip(self), fields(request_id))]
+    pub async fn fetch_plc_data(&self, plc_url: &str, did: &str) -> Result<PlcData> {
+        let url = format!("{}/{}/data", plc_url.trim_end_matches('/'), did);
+        debug!("Fetching PLC data at: {}", url);
+
+        let response = send_traced(self.client.get(&url), "fetch PLC data").await?;
+        read_json(response).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::REQUEST_ID_HEADER;
     use serde_json::json;
-    use wiremock::matchers::{header, method, path};
+    use wiremock::matchers::{header, header_exists, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -123,6 +475,7 @@ mod tests {
                 "test.example.com".to_string(),
                 "test@example.com".to_string(),
                 "password123".to_string(),
+                None,
             )
             .await
             .unwrap();
@@ -131,6 +484,42 @@ mod tests {
         assert_eq!(result.handle, "test.example.com");
     }
 
+    #[tokio::test]
+    async fn test_create_account_invite_code() {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "did": "did:plc:test123",
+                "handle": "test.example.com"
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        for invite_code in [Some("test-com-abcde".to_string()), None] {
+            client
+                .create_account(
+                    "test.example.com".to_string(),
+                    "test@example.com".to_string(),
+                    "password123".to_string(),
+                    invite_code,
+                )
+                .await
+                .unwrap();
+        }
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let bodies: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect();
+        assert_eq!(bodies[0]["inviteCode"], "test-com-abcde");
+        assert!(bodies[1].get("inviteCode").is_none());
+    }
+
     #[tokio::test]
     async fn test_create_account_error() {
         let mock_server = MockServer::start().await;
@@ -160,9 +549,307 @@ mod tests {
                 "invalid@handle".to_string(),
                 "test@example.com".to_string(),
                 "password123".to_string(),
+                None,
             )
             .await;
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_request_id_header_and_error() {
+        let mock_server = MockServer::start().await;
+
+        let test_client = PdsClient {
+            client: Client::new(),
+            base_url: mock_server.uri(),
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .and(header_exists(REQUEST_ID_HEADER))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Invalid handle"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let err = test_client
+            .create_account(
+                "invalid@handle".to_string(),
+                "test@example.com".to_string(),
+                "password123".to_string(),
+                None,
+            )
+            .await
+            .unwrap_err();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let request_id = requests[0].headers[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(err.to_string().contains(request_id));
+    }
+
+    #[tokio::test]
+    async fn test_delete_account() {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.admin.deleteAccount"))
+            .and(header("Authorization", "Basic YWRtaW46c2VjcmV0"))
+            .and(wiremock::matchers::body_json(
+                json!({ "did": "did:plc:test123" }),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.admin.deleteAccount"))
+            .and(wiremock::matchers::body_json(
+                json!({ "did": "did:plc:missing" }),
+            ))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "InvalidRequest",
+                "message": "Account not found"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        client
+            .delete_account("did:plc:test123", "secret")
+            .await
+            .unwrap();
+
+        let err = client
+            .delete_account("did:plc:missing", "secret")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Api(msg) if msg.contains("Account not found")));
+    }
+
+    #[tokio::test]
+    async fn test_create_invite_code() {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createInviteCode"))
+            .and(header("Authorization", "Basic YWRtaW46c2VjcmV0"))
+            .and(wiremock::matchers::body_json(json!({ "useCount": 5 })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "code": "test-com-abcde" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let code = client.create_invite_code(5, "secret").await.unwrap();
+        assert_eq!(code, "test-com-abcde");
+    }
+
+    #[tokio::test]
+    async fn test_reset_account_password() {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.describeRepo"))
+            .and(query_param("repo", "alice.test.com"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "handle": "alice.test.com",
+                "did": "did:plc:alice",
+                "didDoc": {}
+            })))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.admin.updateAccountPassword"))
+            .and(header("Authorization", "Basic YWRtaW46c2VjcmV0"))
+            .and(wiremock::matchers::body_json(json!({
+                "did": "did:plc:alice",
+                "password": "new-password"
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        client
+            .reset_account_password("alice.test.com", "new-password", "secret")
+            .await
+            .unwrap();
+
+        let err = client
+            .reset_account_password("alice.test.com", "new-password", "wrong")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Api(_)));
+    }
+
+    #[tokio::test]
+    async fn test_list_accounts_follows_cursor() {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.listRepos"))
+            .and(query_param("cursor", "page2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "repos": [{ "did": "did:plc:bob", "head": "x", "rev": "y" }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.listRepos"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "cursor": "page2",
+                "repos": [{ "did": "did:plc:alice", "head": "x", "rev": "y" }]
+            })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.describeRepo"))
+            .and(query_param("repo", "did:plc:alice"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "handle": "alice.test.com",
+                "did": "did:plc:alice",
+                "didDoc": {}
+            })))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.describeRepo"))
+            .and(query_param("repo", "did:plc:bob"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": "RepoDeactivated"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let accounts = client.list_accounts().await.unwrap();
+        assert_eq!(
+            accounts,
+            vec![
+                RepoInfo {
+                    did: "did:plc:alice".into(),
+                    handle: "alice.test.com".into(),
+                },
+                RepoInfo {
+                    did: "did:plc:bob".into(),
+                    handle: INVALID_HANDLE.into(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_accounts_error() {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.sync.listRepos"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("AuthRequired"))
+            .mount(&mock_server)
+            .await;
+
+        let err = client.list_accounts().await.unwrap_err();
+        assert!(
+            matches!(err, Error::Api(msg) if msg.contains("401") && msg.contains("AuthRequired"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_describe_repo() {
+        let mock_server = MockServer::start().await;
+
+        let test_client = PdsClient {
+            client: Client::new(),
+            base_url: mock_server.uri(),
+        };
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.repo.describeRepo"))
+            .and(query_param("repo", "alice.example.com"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "handle": "alice.example.com",
+                "did": "did:plc:alice123",
+                "handleIsCorrect": true,
+                "collections": [],
+                "didDoc": {
+                    "@context": ["https://www.w3.org/ns/did/v1"],
+                    "id": "did:plc:alice123",
+                    "alsoKnownAs": ["at://alice.example.com"],
+                    "verificationMethod": [{
+                        "id": "did:plc:alice123#atproto",
+                        "type": "Multikey",
+                        "controller": "did:plc:alice123",
+                        "publicKeyMultibase": "zQ3shXjHeiBuRCKmM36cuYnm7YEMzhGnCmCyW92sRJ9pribSF"
+                    }],
+                    "service": [{
+                        "id": "#atproto_pds",
+                        "type": "AtprotoPersonalDataServer",
+                        "serviceEndpoint": "https://pds.example.com"
+                    }]
+                }
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let repo = test_client
+            .describe_repo("alice.example.com")
+            .await
+            .unwrap();
+        assert_eq!(repo.did, "did:plc:alice123");
+        assert!(repo.handle_is_correct);
+
+        let doc = repo.document().unwrap();
+        assert_eq!(doc.pds_endpoint(), Some("https://pds.example.com"));
+        assert_eq!(doc.also_known_as, vec!["at://alice.example.com"]);
+        assert!(doc.claims_handle("@alice.example.com"));
+        assert!(!doc.claims_handle("bob.example.com"));
+        assert_eq!(doc.verification_method.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_plc_data() {
+        let mock_server = MockServer::start().await;
+        let test_client = PdsClient {
+            client: Client::new(),
+            base_url: mock_server.uri(),
+        };
+
+        Mock::given(method("GET"))
+            .and(path("/did:plc:alice123/data"))
+            .and(header_exists(REQUEST_ID_HEADER))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "rotationKeys": ["did:key:zQ3shrotation"]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let data = test_client
+            .fetch_plc_data(&mock_server.uri(), "did:plc:alice123")
+            .await
+            .unwrap();
+        assert_eq!(data.rotation_keys, vec!["did:key:zQ3shrotation"]);
+
+        // Failures carry the request id like the PDS calls do
+        let err = test_client
+            .fetch_plc_data(&mock_server.uri(), "did:plc:missing")
+            .await
+            .unwrap_err();
+        let requests = mock_server.received_requests().await.unwrap();
+        let request_id = requests[1].headers[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(err.to_string().contains(request_id));
+    }
 }
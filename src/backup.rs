@@ -0,0 +1,906 @@
+use crate::compose::{ComposeConfig, Volume};
+use crate::docker::{default_project_name, DockerService};
+use crate::error::{Error, Result};
+use crate::secrets::{create_private, write_private};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tracing::{debug, info, instrument};
+
+/// Project files captured alongside the volumes, relative to the project.
+pub const BACKUP_FILES: &[&str] = &["config", "docker-compose.yml", "config.toml"];
+
+/// Image used to read volumes from a throwaway container.
+const VOLUME_IMAGE: &str = "alpine";
+
+/// Appended to the archive path to name the manifest of a split backup.
+const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// What a backup archive captured.
+#[derive(Debug)]
+pub struct Backup {
+    pub output: PathBuf,
+    pub files: Vec<String>,
+    pub volumes: Vec<String>,
+    /// Set when the archive was split into chunks.
+    pub manifest: Option<ChunkManifest>,
+}
+
+/// Describes an archive split into numbered chunks, so each chunk can be
+/// copied (and re-copied) on its own and checked before reassembly.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub archive: String,
+    pub size: u64,
+    pub sha256: String,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub file: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Backup {
+    /// Archives the project in the current directory to `output`.
+    pub async fn create(output: &Path, force: bool, split_size: Option<u64>) -> Result<Self> {
+        Self::create_in(Path::new("."), output, force, split_size).await
+    }
+
+    /// Archives the project files and named volumes of `dir` as a tar.gz.
+    /// Each volume is stored as `volumes/<name>.tar.gz` under its name in the
+    /// compose file, not the project-prefixed Docker name, so it can be
+    /// restored into a project in another directory. With `split_size`,
+    /// the archive is replaced by chunks of at most that many bytes and a
+    /// manifest next to them.
+    #[instrument]
+    pub async fn create_in(
+        dir: &Path,
+        output: &Path,
+        force: bool,
+        split_size: Option<u64>,
+    ) -> Result<Self> {
+        if split_size == Some(0) {
+            return Err(Error::Config("Split size must be greater than zero".into()));
+        }
+        let manifest_path = manifest_path(output);
+        for existing in [output, manifest_path.as_path()] {
+            if existing.exists() && !force {
+                return Err(Error::Config(format!(
+                    "{} already exists; pass --force to overwrite it",
+                    existing.display()
+                )));
+            }
+        }
+
+        let files: Vec<String> = BACKUP_FILES
+            .iter()
+            .filter(|file| dir.join(file).exists())
+            .map(|file| file.to_string())
+            .collect();
+        let project = default_project_name(&std::fs::canonicalize(dir)?);
+        let project_volumes = project_volumes(&dir.join("docker-compose.yml"), &project)?;
+        let volumes = project_volumes
+            .iter()
+            .map(|volume| volume.name.clone())
+            .collect();
+
+        let staging = staging_dir("backup");
+        std::fs::create_dir_all(staging.join("volumes"))?;
+
+        let result = archive(dir, output, &files, &project_volumes, &staging).await;
+        let _ = std::fs::remove_dir_all(&staging);
+        result?;
+
+        let manifest = match split_size {
+            Some(chunk_size) => {
+                let manifest = split_archive(output, chunk_size)?;
+                write_private(&manifest_path, &serde_json::to_string_pretty(&manifest)?)?;
+                std::fs::remove_file(output)?;
+                info!(
+                    "Backup split into {} chunks, described by {}",
+                    manifest.chunks.len(),
+                    manifest_path.display()
+                );
+                Some(manifest)
+            }
+            None => {
+                info!("Backup written to {}", output.display());
+                None
+            }
+        };
+
+        Ok(Self {
+            output: output.to_path_buf(),
+            files,
+            volumes,
+            manifest,
+        })
+    }
+}
+
+impl Backup {
+    /// Restores the backup at `archive` into the current directory.
+    pub async fn restore(archive: &Path, force: bool) -> Result<Self> {
+        Self::restore_in(Path::new("."), archive, force).await
+    }
+
+    /// Restores config files into `dir` and volume contents into their Docker
+    /// volumes. `archive` may be a tar.gz or a split backup's manifest. The
+    /// layout is checked before anything is written, and running services
+    /// are only stopped when `force` is set.
+    #[instrument]
+    pub async fn restore_in(dir: &Path, archive: &Path, force: bool) -> Result<Self> {
+        let staging = staging_dir("restore");
+        std::fs::create_dir_all(&staging)?;
+        let result = restore_from(dir, archive, force, &staging).await;
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+}
+
+async fn restore_from(dir: &Path, archive: &Path, force: bool, staging: &Path) -> Result<Backup> {
+    let manifest = if archive.to_string_lossy().ends_with(MANIFEST_SUFFIX) {
+        Some(archive.to_path_buf())
+    } else if !archive.exists() && manifest_path(archive).exists() {
+        Some(manifest_path(archive))
+    } else {
+        None
+    };
+    let tarball = match &manifest {
+        Some(manifest) => {
+            let tarball = staging.join("backup.tar.gz");
+            reassemble(manifest, &tarball)?;
+            tarball
+        }
+        None => archive.to_path_buf(),
+    };
+
+    let (files, volumes) = archive_layout(&list_archive(&tarball).await?)?;
+
+    let compose_path = dir.join("docker-compose.yml");
+    if compose_path.exists() {
+        let docker = DockerService::new(compose_path.to_string_lossy());
+        let running = running_services(&docker).await;
+        if !running.is_empty() {
+            if !force {
+                return Err(Error::Docker(format!(
+                    "Services are running ({}); stop them or pass --force",
+                    running.join(", ")
+                )));
+            }
+            info!("Stopping {} before restoring", running.join(", "));
+            docker.stop_services(false).await?;
+        }
+    }
+
+    // Volumes go to this project's Docker names, which depend on `dir`
+    let targets = if volumes.is_empty() {
+        Vec::new()
+    } else {
+        if !files.iter().any(|file| file == "docker-compose.yml") {
+            return Err(Error::Config(
+                "Backup archive has volumes but no docker-compose.yml declaring them".into(),
+            ));
+        }
+        extract(&tarball, staging, &["docker-compose.yml".to_string()]).await?;
+        let project = default_project_name(&std::fs::canonicalize(dir)?);
+        let declared = project_volumes(&staging.join("docker-compose.yml"), &project)?;
+        volumes
+            .iter()
+            .map(|archived| restore_target(archived, &declared).cloned())
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    extract(&tarball, dir, &files).await?;
+    if !targets.is_empty() {
+        extract(&tarball, staging, &["volumes".to_string()]).await?;
+        for (archived, target) in volumes.iter().zip(&targets) {
+            load_volume(archived, &target.docker_name, &staging.join("volumes")).await?;
+        }
+    }
+
+    info!("Restored backup from {}", archive.display());
+    Ok(Backup {
+        output: archive.to_path_buf(),
+        files,
+        volumes,
+        manifest: None,
+    })
+}
+
+/// Names of services with a running container, treating an unreachable
+/// Docker as nothing running.
+async fn running_services(docker: &DockerService) -> Vec<String> {
+    match docker.get_service_status().await {
+        Ok(statuses) => {
+            let mut running: Vec<String> = statuses
+                .into_iter()
+                .filter(|(_, status)| status.running)
+                .map(|(name, _)| name)
+                .collect();
+            running.sort();
+            running
+        }
+        Err(e) => {
+            debug!("Could not read service status: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+async fn list_archive(archive: &Path) -> Result<Vec<String>> {
+    let output = Command::new("tar")
+        .arg("-tzf")
+        .arg(archive)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::Config(format!(
+            "{} is not a readable backup archive: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+/// Checks that every entry is a backed-up project file or a volume dump,
+/// returning the top-level files and the volume names.
+fn archive_layout(entries: &[String]) -> Result<(Vec<String>, Vec<String>)> {
+    let mut files = Vec::new();
+    let mut volumes = Vec::new();
+
+    for entry in entries {
+        let entry = entry.trim_start_matches("./").trim_end_matches('/');
+        if entry.is_empty() {
+            continue;
+        }
+        if entry.starts_with('/') || entry.split('/').any(|part| part == "..") {
+            return Err(Error::Config(format!(
+                "Backup archive contains an unsafe path: {}",
+                entry
+            )));
+        }
+
+        let top = entry.split('/').next().unwrap_or_default();
+        if let Some(file) = BACKUP_FILES.iter().find(|file| **file == top) {
+            if !files.iter().any(|f| f == file) {
+                files.push(file.to_string());
+            }
+        } else if top == "volumes" {
+            if entry == "volumes" {
+                continue;
+            }
+            match entry
+                .strip_prefix("volumes/")
+                .and_then(|name| name.strip_suffix(".tar.gz"))
+            {
+                Some(volume) if !volume.is_empty() && !volume.contains('/') => {
+                    volumes.push(volume.to_string())
+                }
+                _ => {
+                    return Err(Error::Config(format!(
+                        "Unexpected volume entry in backup archive: {}",
+                        entry
+                    )))
+                }
+            }
+        } else {
+            return Err(Error::Config(format!(
+                "Unexpected entry in backup archive: {}",
+                entry
+            )));
+        }
+    }
+
+    if !files.iter().any(|file| file != "config") {
+        return Err(Error::Config(
+            "Backup archive has neither config.toml nor docker-compose.yml".into(),
+        ));
+    }
+    files.sort();
+    volumes.sort();
+    Ok((files, volumes))
+}
+
+async fn extract(archive: &Path, dest: &Path, members: &[String]) -> Result<()> {
+    let output = Command::new("tar")
+        .arg("-xzf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dest)
+        .args(members)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::Io(std::io::Error::other(format!(
+            "Failed to extract {}: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(())
+}
+
+/// Replaces the contents of the Docker volume `docker_name` with
+/// `src/<archived>.tar.gz`.
+async fn load_volume(archived: &str, docker_name: &str, src: &Path) -> Result<()> {
+    let output = Command::new("docker")
+        .args(volume_load_args(archived, docker_name, src))
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(Error::Docker(format!(
+            "Failed to restore volume {}: {}",
+            docker_name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn volume_load_args(archived: &str, docker_name: &str, src: &Path) -> Vec<String> {
+    vec![
+        "run".to_string(),
+        "--rm".to_string(),
+        "-v".to_string(),
+        format!("{}:/data", docker_name),
+        "-v".to_string(),
+        format!("{}:/backup:ro", src.display()),
+        VOLUME_IMAGE.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        format!(
+            "find /data -mindepth 1 -delete && tar -xzf /backup/{}.tar.gz -C /data",
+            archived
+        ),
+    ]
+}
+
+fn staging_dir(purpose: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "atc-{}-{}-{}",
+        purpose,
+        std::process::id(),
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+    ))
+}
+
+/// Where the manifest for a split `archive` lives.
+pub fn manifest_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(MANIFEST_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Splits `archive` into `<archive>.partNNN` files of at most `chunk_size`
+/// bytes, hashing each chunk and the whole archive as it goes.
+fn split_archive(archive: &Path, chunk_size: u64) -> Result<ChunkManifest> {
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| Error::Config(format!("Invalid archive path: {}", archive.display())))?;
+
+    let mut reader = HashingReader::new(BufReader::new(File::open(archive)?));
+    let mut chunks = Vec::new();
+    loop {
+        let file = format!("{}.part{:03}", name, chunks.len());
+        let path = archive.with_file_name(&file);
+        let mut writer = HashingWriter::new(create_private(&path)?);
+        let size = std::io::copy(&mut (&mut reader).take(chunk_size), &mut writer)?;
+
+        if size == 0 && !chunks.is_empty() {
+            std::fs::remove_file(&path)?;
+            break;
+        }
+        chunks.push(Chunk {
+            file,
+            size,
+            sha256: writer.finish()?,
+        });
+        if size < chunk_size {
+            break;
+        }
+    }
+
+    Ok(ChunkManifest {
+        archive: name,
+        size: chunks.iter().map(|chunk| chunk.size).sum(),
+        sha256: reader.finish(),
+        chunks,
+    })
+}
+
+/// Rebuilds a split archive at `output` from the manifest at `manifest`,
+/// checking every chunk and the result against it.
+pub fn reassemble(manifest: &Path, output: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(manifest)?;
+    let manifest_data: ChunkManifest = serde_json::from_str(&content)
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", manifest.display(), e)))?;
+
+    // Chunks must sit next to the manifest
+    if let Some(chunk) = manifest_data.chunks.iter().find(|chunk| {
+        chunk.file.is_empty()
+            || chunk.file.contains("..")
+            || chunk.file.contains(['/', '\\'])
+            || Path::new(&chunk.file).is_absolute()
+    }) {
+        return Err(Error::Config(format!(
+            "{} names an invalid chunk file: {}",
+            manifest.display(),
+            chunk.file
+        )));
+    }
+
+    let mut writer = HashingWriter::new(create_private(output)?);
+    for chunk in &manifest_data.chunks {
+        let path = manifest.with_file_name(&chunk.file);
+        let mut reader = HashingReader::new(BufReader::new(File::open(&path).map_err(|e| {
+            Error::Config(format!("Missing backup chunk {}: {}", path.display(), e))
+        })?));
+        let size = std::io::copy(&mut reader, &mut writer)?;
+
+        let sha256 = reader.finish();
+        if size != chunk.size || sha256 != chunk.sha256 {
+            return Err(Error::Config(format!(
+                "Backup chunk {} does not match its manifest",
+                path.display()
+            )));
+        }
+    }
+
+    if writer.finish()? != manifest_data.sha256 {
+        return Err(Error::Config(format!(
+            "Reassembled {} does not match its manifest",
+            output.display()
+        )));
+    }
+    Ok(())
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(self) -> String {
+        hex(&self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(mut self) -> std::io::Result<String> {
+        self.inner.flush()?;
+        Ok(hex(&self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+async fn archive(
+    dir: &Path,
+    output: &Path,
+    files: &[String],
+    volumes: &[ProjectVolume],
+    staging: &Path,
+) -> Result<()> {
+    for volume in volumes {
+        dump_volume(volume, &staging.join("volumes")).await?;
+    }
+
+    // The archive holds config/secrets.toml, so it is created owner-only
+    // first; tar truncates the existing file and keeps its mode
+    create_private(output)?;
+    let mut cmd = Command::new("tar");
+    cmd.arg("-czf").arg(output).arg("-C").arg(dir).args(files);
+    if !volumes.is_empty() {
+        cmd.arg("-C").arg(staging).arg("volumes");
+    }
+
+    debug!("Running tar command: {:?}", cmd);
+    let result = cmd.output().await?;
+    if !result.status.success() {
+        return Err(Error::Io(std::io::Error::other(format!(
+            "Failed to write {}: {}",
+            output.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        ))));
+    }
+    Ok(())
+}
+
+/// Tars the contents of a Docker volume into `dest/<name>.tar.gz`.
+async fn dump_volume(volume: &ProjectVolume, dest: &Path) -> Result<()> {
+    let output = Command::new("docker")
+        .args(volume_dump_args(volume, dest))
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(Error::Docker(format!(
+            "Failed to back up volume {}: {}",
+            volume.docker_name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn volume_dump_args(volume: &ProjectVolume, dest: &Path) -> Vec<String> {
+    vec![
+        "run".to_string(),
+        "--rm".to_string(),
+        "-v".to_string(),
+        format!("{}:/data:ro", volume.docker_name),
+        "-v".to_string(),
+        format!("{}:/backup", dest.display()),
+        VOLUME_IMAGE.to_string(),
+        "tar".to_string(),
+        "-czf".to_string(),
+        format!("/backup/{}.tar.gz", volume.name),
+        "-C".to_string(),
+        "/data".to_string(),
+        ".".to_string(),
+    ]
+}
+
+/// A named volume from a compose file and the Docker volume backing it.
+#[derive(Debug, Clone, PartialEq)]
+struct ProjectVolume {
+    /// The key under `volumes:` in the compose file
+    name: String,
+    docker_name: String,
+}
+
+/// The named volumes declared in the compose file at `compose_path`, as
+/// Docker names them for `project`.
+fn project_volumes(compose_path: &Path, project: &str) -> Result<Vec<ProjectVolume>> {
+    if !compose_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let compose = ComposeConfig::load(compose_path)?;
+    let mut volumes: Vec<ProjectVolume> = compose
+        .volumes
+        .iter()
+        .flatten()
+        .map(|(name, volume)| ProjectVolume {
+            name: name.clone(),
+            docker_name: docker_volume_name(project, name, volume),
+        })
+        .collect();
+    volumes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(volumes)
+}
+
+/// The declared volume an archived dump restores into. Older backups named
+/// dumps after the source project's Docker volumes (`<project>_<name>`), so
+/// those are matched by their suffix.
+fn restore_target<'a>(archived: &str, declared: &'a [ProjectVolume]) -> Result<&'a ProjectVolume> {
+    declared
+        .iter()
+        .find(|volume| volume.name == archived)
+        .or_else(|| {
+            declared.iter().find(|volume| {
+                archived
+                    .strip_suffix(volume.name.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('_'))
+            })
+        })
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "Backup volume {} is not declared in its docker-compose.yml",
+                archived
+            ))
+        })
+}
+
+/// Compose prefixes volumes with the project name unless they are external.
+fn docker_volume_name(project: &str, name: &str, volume: &Volume) -> String {
+    if volume.external == Some(true) {
+        name.to_string()
+    } else {
+        format!("{}_{}", project, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backup_captures_files_and_refuses_overwrite() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("config"))?;
+        std::fs::write(dir.path().join("config/Caddyfile"), "pds.test.com {}")?;
+        std::fs::write(dir.path().join("config.toml"), "[network]")?;
+
+        let output = dir.path().join("backup.tar.gz");
+        let backup = Backup::create_in(dir.path(), &output, false, None).await?;
+        assert_eq!(backup.files, vec!["config", "config.toml"]);
+        assert!(backup.volumes.is_empty());
+
+        let listing = std::process::Command::new("tar")
+            .arg("-tzf")
+            .arg(&output)
+            .output()?;
+        let listing = String::from_utf8_lossy(&listing.stdout);
+        assert!(listing.contains("config/Caddyfile"));
+        assert!(listing.contains("config.toml"));
+
+        assert!(matches!(
+            Backup::create_in(dir.path(), &output, false, None).await,
+            Err(Error::Config(msg)) if msg.contains("--force")
+        ));
+        assert!(Backup::create_in(dir.path(), &output, true, None)
+            .await
+            .is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_split_backup_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("config"))?;
+        // Random bytes so the gzip output is larger than a few chunks
+        let data: Vec<u8> = (0..16 * 1024).map(|_| rand::random::<u8>()).collect();
+        std::fs::write(dir.path().join("config/blob"), &data)?;
+        std::fs::write(dir.path().join("config.toml"), "[network]")?;
+
+        let output = dir.path().join("backup.tar.gz");
+        let backup = Backup::create_in(dir.path(), &output, false, Some(4096)).await?;
+        let manifest = backup.manifest.expect("split backup has a manifest");
+        assert!(manifest.chunks.len() > 1);
+        assert!(manifest.chunks.iter().all(|chunk| chunk.size <= 4096));
+        assert!(!output.exists());
+
+        let restored = dir.path().join("restored.tar.gz");
+        reassemble(&manifest_path(&output), &restored)?;
+        assert_eq!(std::fs::metadata(&restored)?.len(), manifest.size);
+
+        let extract = dir.path().join("extract");
+        std::fs::create_dir_all(&extract)?;
+        std::fs::write(extract.join("config.toml"), "")?;
+        Backup::restore_in(&extract, &output, false).await?;
+        assert_eq!(std::fs::read(extract.join("config/blob"))?, data);
+
+        let mut escaping = serde_json::from_str::<ChunkManifest>(&std::fs::read_to_string(
+            manifest_path(&output),
+        )?)?;
+        for file in ["../../etc/passwd", "/etc/passwd", ".."] {
+            escaping.chunks[0].file = file.to_string();
+            let path = dir.path().join("escaping.manifest.json");
+            std::fs::write(&path, serde_json::to_string(&escaping)?)?;
+            assert!(matches!(
+                reassemble(&path, &restored),
+                Err(Error::Config(msg)) if msg.contains("invalid chunk file")
+            ));
+        }
+
+        let first = dir.path().join(&manifest.chunks[0].file);
+        std::fs::write(&first, b"corrupted")?;
+        assert!(matches!(
+            reassemble(&manifest_path(&output), &restored),
+            Err(Error::Config(msg)) if msg.contains("does not match")
+        ));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_backup_files_are_private() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("config"))?;
+        std::fs::write(dir.path().join("config/secrets.toml"), "[secrets]")?;
+        let mode = |path: &Path| -> Result<u32> {
+            Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+        };
+
+        let output = dir.path().join("backup.tar.gz");
+        Backup::create_in(dir.path(), &output, false, None).await?;
+        assert_eq!(mode(&output)?, 0o600);
+
+        // Overwriting a world-readable archive tightens it too
+        std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o644))?;
+        Backup::create_in(dir.path(), &output, true, None).await?;
+        assert_eq!(mode(&output)?, 0o600);
+
+        let split = dir.path().join("split.tar.gz");
+        let backup = Backup::create_in(dir.path(), &split, false, Some(64)).await?;
+        assert_eq!(mode(&manifest_path(&split))?, 0o600);
+        for chunk in backup.manifest.expect("split backup has a manifest").chunks {
+            assert_eq!(mode(&dir.path().join(&chunk.file))?, 0o600);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_roundtrip_and_malformed_archive() -> Result<()> {
+        let source = tempfile::tempdir()?;
+        std::fs::create_dir_all(source.path().join("config"))?;
+        std::fs::write(source.path().join("config/Caddyfile"), "pds.test.com {}")?;
+        std::fs::write(source.path().join("config.toml"), "[network]")?;
+        let archive = source.path().join("backup.tar.gz");
+        Backup::create_in(source.path(), &archive, false, None).await?;
+
+        let target = tempfile::tempdir()?;
+        std::fs::write(target.path().join("config.toml"), "stale")?;
+        let restored = Backup::restore_in(target.path(), &archive, false).await?;
+        assert_eq!(restored.files, vec!["config", "config.toml"]);
+        assert_eq!(
+            std::fs::read_to_string(target.path().join("config.toml"))?,
+            "[network]"
+        );
+        assert!(target.path().join("config/Caddyfile").exists());
+
+        std::fs::write(source.path().join("notes.txt"), "hello")?;
+        let malformed = source.path().join("malformed.tar.gz");
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(&malformed)
+            .arg("-C")
+            .arg(source.path())
+            .arg("config.toml")
+            .arg("notes.txt")
+            .status()?;
+        assert!(status.success());
+
+        let untouched = tempfile::tempdir()?;
+        assert!(matches!(
+            Backup::restore_in(untouched.path(), &malformed, false).await,
+            Err(Error::Config(msg)) if msg.contains("notes.txt")
+        ));
+        assert!(!untouched.path().join("config.toml").exists());
+
+        let garbage = source.path().join("garbage.tar.gz");
+        std::fs::write(&garbage, "not an archive")?;
+        assert!(matches!(
+            Backup::restore_in(untouched.path(), &garbage, false).await,
+            Err(Error::Config(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_layout() {
+        let entries: Vec<String> = [
+            "config/",
+            "config/Caddyfile",
+            "config.toml",
+            "volumes/",
+            "volumes/bsky_pds_data.tar.gz",
+        ]
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect();
+        let (files, volumes) = archive_layout(&entries).unwrap();
+        assert_eq!(files, vec!["config", "config.toml"]);
+        assert_eq!(volumes, vec!["bsky_pds_data"]);
+
+        for bad in ["../etc/passwd", "/etc/passwd", "volumes/a/b.tar.gz"] {
+            let mut entries = entries.clone();
+            entries.push(bad.to_string());
+            assert!(matches!(archive_layout(&entries), Err(Error::Config(_))));
+        }
+        assert!(matches!(
+            archive_layout(&["config/Caddyfile".to_string()]),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_volume_names() {
+        let volume = Volume {
+            external: None,
+            driver: None,
+        };
+        let external = Volume {
+            external: Some(true),
+            driver: None,
+        };
+        assert_eq!(
+            docker_volume_name("bsky", "pds_data", &volume),
+            "bsky_pds_data"
+        );
+        assert_eq!(
+            docker_volume_name("bsky", "pds_data", &external),
+            "pds_data"
+        );
+
+        let pds_data = ProjectVolume {
+            name: "pds_data".into(),
+            docker_name: "bsky_pds_data".into(),
+        };
+        let args = volume_dump_args(&pds_data, Path::new("/tmp/out"));
+        assert!(args.contains(&"bsky_pds_data:/data:ro".to_string()));
+        assert!(args.contains(&"/tmp/out:/backup".to_string()));
+        assert!(args.contains(&"/backup/pds_data.tar.gz".to_string()));
+    }
+
+    #[test]
+    fn test_restore_target_maps_to_this_project() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let compose_path = dir.path().join("docker-compose.yml");
+        let mut compose = ComposeConfig::new();
+        compose.add_pds("test.com");
+        compose.save(&compose_path)?;
+
+        let declared = project_volumes(&compose_path, "newdir")?;
+        let target = restore_target("pds_data", &declared)?;
+        assert_eq!(target.docker_name, "newdir_pds_data");
+
+        // Dumps named by an older release after the source project
+        let target = restore_target("olddir_pds_data", &declared)?;
+        assert_eq!(target.docker_name, "newdir_pds_data");
+
+        assert!(matches!(
+            restore_target("olddir_unknown", &declared),
+            Err(Error::Config(msg)) if msg.contains("olddir_unknown")
+        ));
+
+        let args = volume_load_args("olddir_pds_data", &target.docker_name, Path::new("/in"));
+        assert!(args.contains(&"newdir_pds_data:/data".to_string()));
+        assert!(args
+            .iter()
+            .any(|arg| arg.contains("/backup/olddir_pds_data.tar.gz")));
+        Ok(())
+    }
+}
@@ -1,101 +1,227 @@
-use crate::error::Result;
+use crate::certs::DnsProvider;
+use crate::error::{Error, Result};
 use std::path::Path;
 use tracing::instrument;
 
+/// One site block: the hosts it serves and how requests are handled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaddyRoute {
+    pub hosts: Vec<String>,
+    pub handlers: Vec<CaddyHandler>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaddyHandler {
+    pub matcher: CaddyMatcher,
+    pub action: CaddyAction,
+}
+
+/// Which requests a handler applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaddyMatcher {
+    /// Every request, as a bare directive in the site block
+    All,
+    /// Requests under a path, wrapped in `handle <path>`
+    Path(String),
+    /// A named `@name path <pattern>` matcher, wrapped in `handle @name`
+    Named { name: String, path: String },
+    /// Whatever earlier handlers didn't take, wrapped in `handle *`
+    Fallback,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaddyAction {
+    Respond(String),
+    ReverseProxy(String),
+}
+
+impl CaddyRoute {
+    fn proxy(hosts: Vec<String>, upstream: &str) -> Self {
+        Self {
+            hosts,
+            handlers: vec![CaddyHandler::new(
+                CaddyMatcher::All,
+                CaddyAction::ReverseProxy(upstream.to_string()),
+            )],
+        }
+    }
+
+    /// Renders the site block, with a `tls` block holding `tls_directive`
+    /// if given.
+    fn render(&self, tls_directive: Option<&str>) -> String {
+        const INDENT: &str = "                ";
+
+        let mut block = format!("{} {{\n", self.hosts.join(", "));
+        if let Some(directive) = tls_directive {
+            block.push_str(&format!(
+                "{indent}tls {{\n{indent}    {}\n{indent}}}\n",
+                directive,
+                indent = INDENT
+            ));
+        }
+        for handler in &self.handlers {
+            let directive = handler.action.directive();
+            let wrapper = match &handler.matcher {
+                CaddyMatcher::All => {
+                    block.push_str(&format!("{}{}\n", INDENT, directive));
+                    continue;
+                }
+                CaddyMatcher::Path(path) => path.clone(),
+                CaddyMatcher::Named { name, path } => {
+                    block.push_str(&format!("{}@{} path {}\n", INDENT, name, path));
+                    format!("@{}", name)
+                }
+                CaddyMatcher::Fallback => "*".to_string(),
+            };
+            block.push_str(&format!(
+                "{indent}handle {} {{\n{indent}    {}\n{indent}}}\n",
+                wrapper,
+                directive,
+                indent = INDENT
+            ));
+        }
+        block.push_str("            }\n\n            ");
+        block
+    }
+}
+
+impl CaddyHandler {
+    fn new(matcher: CaddyMatcher, action: CaddyAction) -> Self {
+        Self { matcher, action }
+    }
+}
+
+impl CaddyAction {
+    fn directive(&self) -> String {
+        match self {
+            CaddyAction::Respond(body) => format!("respond \"{}\"", body),
+            CaddyAction::ReverseProxy(upstream) => format!("reverse_proxy {}", upstream),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CaddyConfig {
     domain: String,
+    dual_stack_bind: Option<String>,
+    test_endpoint: bool,
+    dns_challenge: Option<DnsProvider>,
 }
 
 impl CaddyConfig {
     pub fn new(domain: &str) -> Self {
         Self {
             domain: domain.to_string(),
+            dual_stack_bind: None,
+            test_endpoint: true,
+            dns_challenge: None,
         }
     }
 
-    #[instrument(skip(self))]
-    pub fn generate(&self) -> String {
-        let mut config = String::new();
-
-        // Debug test endpoint
-        config.push_str(&format!(
-            "test-wss.{} {{
-                respond \"OK\"
-                handle /ws {{
-                    respond \"OK\"
-                }}
-            }}
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
 
-            ",
-            self.domain
-        ));
-
-        // PDS configuration
-        config.push_str(&format!(
-            "*.pds.{domain}, pds.{domain} {{
-                @api path /xrpc/*
-                handle @api {{
-                    reverse_proxy pds:3000
-                }}
-                handle * {{
-                    reverse_proxy social-app:3000
-                }}
-            }}
+    /// Listen on `bind_v4` and `[::]` instead of Caddy's default bind.
+    pub fn with_dual_stack(mut self, bind_v4: &str) -> Self {
+        self.dual_stack_bind = Some(bind_v4.to_string());
+        self
+    }
 
-            ",
-            domain = self.domain
-        ));
+    /// Include the `test-wss` block that `check` probes.
+    pub fn with_test_endpoint(mut self, enabled: bool) -> Self {
+        self.test_endpoint = enabled;
+        self
+    }
 
-        // BGS configuration
-        config.push_str(&format!(
-            "*.bgs.{domain}, bgs.{domain} {{
-                reverse_proxy bgs:2470
-            }}
+    /// Obtain certificates through a DNS-01 challenge with `provider`,
+    /// which also covers the wildcard hosts.
+    pub fn with_dns_challenge(mut self, provider: DnsProvider) -> Self {
+        self.dns_challenge = Some(provider);
+        self
+    }
 
-            ",
-            domain = self.domain
-        ));
+    /// Every wildcard host the site blocks serve.
+    pub fn wildcard_hosts(&self) -> Vec<String> {
+        self.routes()
+            .into_iter()
+            .flat_map(|route| route.hosts)
+            .filter(|host| host.starts_with("*."))
+            .collect()
+    }
 
-        // Appview configuration
-        config.push_str(&format!(
-            "*.appview.{domain}, appview.{domain} {{
-                reverse_proxy appview:3000
-            }}
+    /// The site blocks `generate` renders, in order.
+    pub fn routes(&self) -> Vec<CaddyRoute> {
+        let domain = &self.domain;
+        let wildcard = |service: &str| {
+            vec![
+                format!("*.{}.{}", service, domain),
+                format!("{}.{}", service, domain),
+            ]
+        };
 
-            ",
-            domain = self.domain
-        ));
+        let mut routes = Vec::new();
+        if self.test_endpoint {
+            // Debug test endpoint
+            routes.push(CaddyRoute {
+                hosts: vec![format!("test-wss.{}", domain)],
+                handlers: vec![
+                    CaddyHandler::new(CaddyMatcher::All, CaddyAction::Respond("OK".into())),
+                    CaddyHandler::new(
+                        CaddyMatcher::Path("/ws".into()),
+                        CaddyAction::Respond("OK".into()),
+                    ),
+                ],
+            });
+        }
 
-        // PLC configuration
-        config.push_str(&format!(
-            "*.plc.{domain}, plc.{domain} {{
-                reverse_proxy plc:2582
-            }}
+        routes.extend([
+            // XRPC goes to the PDS, everything else to the web app
+            CaddyRoute {
+                hosts: wildcard("pds"),
+                handlers: vec![
+                    CaddyHandler::new(
+                        CaddyMatcher::Named {
+                            name: "api".into(),
+                            path: "/xrpc/*".into(),
+                        },
+                        CaddyAction::ReverseProxy("pds:3000".into()),
+                    ),
+                    CaddyHandler::new(
+                        CaddyMatcher::Fallback,
+                        CaddyAction::ReverseProxy("social-app:3000".into()),
+                    ),
+                ],
+            },
+            CaddyRoute::proxy(wildcard("bgs"), "bgs:2470"),
+            CaddyRoute::proxy(wildcard("appview"), "appview:3000"),
+            CaddyRoute::proxy(wildcard("plc"), "plc:2582"),
+            CaddyRoute::proxy(vec![format!("social-app.{}", domain)], "social-app:3000"),
+            CaddyRoute::proxy(vec![format!("ozone.{}", domain)], "ozone:3000"),
+        ]);
+        routes
+    }
 
-            ",
-            domain = self.domain
-        ));
+    #[instrument(skip(self))]
+    pub fn generate(&self) -> String {
+        let mut config = String::new();
 
-        // Social app configuration
-        config.push_str(&format!(
-            "social-app.{domain} {{
-                reverse_proxy social-app:3000
+        // Global options must come before any site block
+        if let Some(bind_v4) = &self.dual_stack_bind {
+            config.push_str(&format!(
+                "{{
+                default_bind {} [::]
             }}
 
             ",
-            domain = self.domain
-        ));
-
-        // Ozone configuration
-        config.push_str(&format!(
-            "ozone.{domain} {{
-                reverse_proxy ozone:3000
-            }}
+                bind_v4
+            ));
+        }
 
-            ",
-            domain = self.domain
-        ));
+        let tls = self.dns_challenge.map(DnsProvider::caddy_directive);
+        for route in self.routes() {
+            config.push_str(&route.render(tls));
+        }
 
         config
     }
@@ -106,11 +232,55 @@ impl CaddyConfig {
     }
 }
 
+/// Checks that a Caddyfile has at least one site block and that its braces
+/// balance. Caddy itself isn't needed, so this runs before anything starts.
+pub fn validate_caddyfile(content: &str) -> Result<()> {
+    let mut depth = 0usize;
+    let mut blocks = 0;
+    for (number, line) in content.lines().enumerate() {
+        for c in line.chars() {
+            match c {
+                '{' => {
+                    if depth == 0 {
+                        blocks += 1;
+                    }
+                    depth += 1;
+                }
+                '}' => {
+                    depth = depth.checked_sub(1).ok_or_else(|| {
+                        Error::Config(format!("Caddyfile line {}: unmatched '}}'", number + 1))
+                    })?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err(Error::Config("Caddyfile has an unclosed '{'".into()));
+    }
+    if blocks == 0 {
+        return Err(Error::Config("Caddyfile defines no sites".into()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use assert_fs::prelude::*;
 
+    #[test]
+    fn test_validate_caddyfile() {
+        assert!(validate_caddyfile(&CaddyConfig::new("example.com").generate()).is_ok());
+        let dual_stack = CaddyConfig::new("example.com").with_dual_stack("0.0.0.0");
+        assert!(validate_caddyfile(&dual_stack.generate()).is_ok());
+
+        for broken in ["", "example.com {\n", "example.com }\n{\n"] {
+            assert!(matches!(validate_caddyfile(broken), Err(Error::Config(_))));
+        }
+    }
+
     #[test]
     fn test_generate_config() {
         let config = CaddyConfig::new("example.com");
@@ -140,6 +310,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_dual_stack_bind() {
+        let content = CaddyConfig::new("example.com").generate();
+        assert!(!content.contains("default_bind"));
+
+        let content = CaddyConfig::new("example.com")
+            .with_dual_stack("0.0.0.0")
+            .generate();
+        assert!(content.starts_with("{"));
+        assert!(content.contains("default_bind 0.0.0.0 [::]"));
+    }
+
+    #[test]
+    fn test_without_test_endpoint() {
+        let content = CaddyConfig::new("example.com")
+            .with_test_endpoint(false)
+            .generate();
+        assert!(!content.contains("test-wss"));
+        assert!(content.starts_with("*.pds.example.com, pds.example.com {"));
+    }
+
+    #[test]
+    fn test_routes() {
+        let routes = CaddyConfig::new("example.com").routes();
+
+        let pds = routes
+            .iter()
+            .find(|route| route.hosts.contains(&"pds.example.com".to_string()))
+            .unwrap();
+        assert_eq!(
+            pds.hosts,
+            vec![
+                "*.pds.example.com".to_string(),
+                "pds.example.com".to_string()
+            ]
+        );
+        assert_eq!(
+            pds.handlers,
+            vec![
+                CaddyHandler::new(
+                    CaddyMatcher::Named {
+                        name: "api".into(),
+                        path: "/xrpc/*".into()
+                    },
+                    CaddyAction::ReverseProxy("pds:3000".into()),
+                ),
+                CaddyHandler::new(
+                    CaddyMatcher::Fallback,
+                    CaddyAction::ReverseProxy("social-app:3000".into()),
+                ),
+            ]
+        );
+
+        let plc = routes
+            .iter()
+            .find(|route| route.hosts.contains(&"plc.example.com".to_string()))
+            .unwrap();
+        assert_eq!(
+            plc.handlers,
+            vec![CaddyHandler::new(
+                CaddyMatcher::All,
+                CaddyAction::ReverseProxy("plc:2582".into())
+            )]
+        );
+    }
+
     #[test]
     fn test_proxy_rules() {
         let config = CaddyConfig::new("example.com");
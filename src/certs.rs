@@ -1,8 +1,72 @@
+use crate::caddy::CaddyConfig;
 use crate::error::{Error, Result};
-use std::{path::Path, process::Stdio};
+use crate::secrets::Secrets;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path, process::Stdio};
 use tokio::process::Command;
 use tracing::{debug, instrument};
 
+/// DNS providers Caddy can solve DNS-01 challenges with. Each needs a Caddy
+/// image built with the matching `caddy-dns` module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsProvider {
+    Cloudflare,
+    Route53,
+}
+
+impl DnsProvider {
+    /// Environment variables Caddy reads the provider's credentials from.
+    pub fn env_keys(self) -> &'static [&'static str] {
+        match self {
+            Self::Cloudflare => &["CLOUDFLARE_API_TOKEN"],
+            Self::Route53 => &["AWS_ACCESS_KEY_ID", "AWS_SECRET_ACCESS_KEY"],
+        }
+    }
+
+    /// The `dns` line of a site's `tls` block.
+    pub fn caddy_directive(self) -> &'static str {
+        match self {
+            Self::Cloudflare => "dns cloudflare {env.CLOUDFLARE_API_TOKEN}",
+            // The module reads the AWS variables itself
+            Self::Route53 => "dns route53",
+        }
+    }
+
+    /// Pairs `credentials` with `env_keys`: an API token for Cloudflare,
+    /// `ACCESS_KEY_ID:SECRET_ACCESS_KEY` for Route 53.
+    pub fn credential_env(self, credentials: &str) -> Result<Vec<(String, String)>> {
+        let values: Vec<&str> = match self {
+            Self::Cloudflare => vec![credentials],
+            Self::Route53 => credentials.splitn(2, ':').collect(),
+        };
+        if values.len() != self.env_keys().len() || values.iter().any(|v| v.trim().is_empty()) {
+            return Err(Error::Cert(match self {
+                Self::Cloudflare => "Cloudflare needs an API token".to_string(),
+                Self::Route53 => {
+                    "Route 53 credentials must look like ACCESS_KEY_ID:SECRET_ACCESS_KEY"
+                        .to_string()
+                }
+            }));
+        }
+        Ok(self
+            .env_keys()
+            .iter()
+            .zip(values)
+            .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+            .collect())
+    }
+}
+
+impl fmt::Display for DnsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cloudflare => f.write_str("cloudflare"),
+            Self::Route53 => f.write_str("route53"),
+        }
+    }
+}
+
 pub struct CertManager;
 
 impl CertManager {
@@ -117,6 +181,43 @@ impl CertManager {
         Ok(())
     }
 
+    /// Switches certificate issuance to DNS-01 through `provider`: stores
+    /// `credentials` in the secrets file and rewrites the Caddyfile from
+    /// `caddy` with a `tls { dns ... }` block per site. Fails unless `caddy`
+    /// serves wildcard hosts under a public domain, which is what DNS-01
+    /// is needed for.
+    #[instrument(skip(caddy, credentials))]
+    pub fn configure_dns_challenge(
+        caddy: CaddyConfig,
+        provider: DnsProvider,
+        credentials: &str,
+        secrets_path: impl AsRef<Path> + fmt::Debug,
+        caddyfile_path: impl AsRef<Path> + fmt::Debug,
+    ) -> Result<()> {
+        let domain = caddy.domain();
+        let public = domain.contains('.')
+            && domain != "localhost"
+            && !domain.ends_with(".localhost")
+            && domain.parse::<std::net::IpAddr>().is_err();
+        if !public || caddy.wildcard_hosts().is_empty() {
+            return Err(Error::Cert(format!(
+                "DNS-01 is for wildcard certificates under a public domain, but the configured \
+                 domain is {}",
+                domain
+            )));
+        }
+
+        let env = provider.credential_env(credentials)?;
+        let secrets_path = secrets_path.as_ref();
+        let mut secrets = Secrets::load(secrets_path)?;
+        secrets.dns_credentials = env.into_iter().collect();
+        secrets.save(secrets_path)?;
+
+        caddy.with_dns_challenge(provider).save(caddyfile_path)?;
+        debug!("Enabled DNS-01 challenges with {}", provider);
+        Ok(())
+    }
+
     #[instrument]
     pub async fn check_cert_exists(cert_dir: impl AsRef<Path> + std::fmt::Debug) -> Result<bool> {
         let cert_dir = cert_dir.as_ref();
@@ -142,6 +243,53 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_configure_dns_challenge() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let secrets_path = temp_dir.path().join("secrets.toml");
+        let caddyfile_path = temp_dir.path().join("Caddyfile");
+        Secrets::generate().save(&secrets_path)?;
+
+        CertManager::configure_dns_challenge(
+            CaddyConfig::new("example.com"),
+            DnsProvider::Cloudflare,
+            "cf-token",
+            &secrets_path,
+            &caddyfile_path,
+        )?;
+
+        let secrets = Secrets::load(&secrets_path)?;
+        assert_eq!(secrets.dns_credentials["CLOUDFLARE_API_TOKEN"], "cf-token");
+        let caddyfile = std::fs::read_to_string(&caddyfile_path)?;
+        assert!(caddyfile.contains("dns cloudflare {env.CLOUDFLARE_API_TOKEN}"));
+        crate::caddy::validate_caddyfile(&caddyfile)?;
+
+        let local = CertManager::configure_dns_challenge(
+            CaddyConfig::new("localhost"),
+            DnsProvider::Cloudflare,
+            "cf-token",
+            &secrets_path,
+            &caddyfile_path,
+        );
+        assert!(matches!(local, Err(Error::Cert(msg)) if msg.contains("localhost")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dns_provider_credentials() {
+        assert_eq!(
+            DnsProvider::Route53
+                .credential_env("AKID:s3cr3t:x")
+                .unwrap(),
+            vec![
+                ("AWS_ACCESS_KEY_ID".to_string(), "AKID".to_string()),
+                ("AWS_SECRET_ACCESS_KEY".to_string(), "s3cr3t:x".to_string()),
+            ]
+        );
+        assert!(DnsProvider::Route53.credential_env("AKID").is_err());
+        assert!(DnsProvider::Cloudflare.credential_env(" ").is_err());
+    }
+
     #[tokio::test]
     async fn test_check_cert_exists() -> Result<()> {
         let temp_dir = tempdir()?;
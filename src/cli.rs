@@ -1,4 +1,6 @@
-use clap::{Args, Parser, Subcommand};
+use crate::certs::DnsProvider;
+use crate::nsid::Nsid;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 /// Bluesky self-hosting manager
@@ -9,6 +11,11 @@ pub struct Cli {
     #[arg(short, long, default_value = "config.toml")]
     pub config: PathBuf,
 
+    /// Project directory to operate in instead of the current one;
+    /// relative paths (including --config) resolve against it
+    #[arg(long, global = true)]
+    pub dir: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -24,18 +31,81 @@ pub enum Commands {
     /// Stop Bluesky services
     Stop(StopArgs),
 
+    /// Restart running services
+    Restart(RestartArgs),
+
+    /// Pull newer images and recreate their containers
+    Update(UpdateArgs),
+
+    /// Archive the configuration and Docker volumes to a tar.gz
+    Backup(BackupArgs),
+
+    /// Restore the configuration and Docker volumes from a backup
+    Restore(RestoreArgs),
+
+    /// Print the Docker Compose config with variables substituted
+    RenderCompose,
+
+    /// Show how services depend on each other
+    Graph(GraphArgs),
+
+    /// Print the resolved environment a service will receive
+    Env(EnvArgs),
+
+    /// Interactively edit the configuration
+    Configure,
+
+    /// Edit the configuration file in $EDITOR, keeping it only if it validates
+    EditConfig,
+
     /// Create a new account
     CreateAccount(CreateAccountArgs),
 
+    /// Create accounts from a CSV or JSON file
+    CreateAccountsBatch(CreateAccountsBatchArgs),
+
+    /// Create an invite code for account creation
+    CreateInviteCode(CreateInviteCodeArgs),
+
+    /// List the accounts hosted on the PDS
+    ListAccounts(ListAccountsArgs),
+
+    /// Permanently delete an account from the PDS
+    DeleteAccount(DeleteAccountArgs),
+
+    /// Set a new password for an account
+    ResetPassword(ResetPasswordArgs),
+
+    /// Check that a handle is under one of the PDS's user domains
+    CheckHandle(CheckHandleArgs),
+
+    /// Generate a new PDS admin password and apply it
+    RotateAdminPassword,
+
+    /// Regenerate all secrets and restart the services that use them
+    RotateSecrets(RotateSecretsArgs),
+
+    /// Resolve and print the DID document for a handle or DID
+    DidDoc(DidDocArgs),
+
     /// Deploy feed generator service
     DeployFeed(DeployFeedArgs),
 
     /// Show service status
     Status(StatusArgs),
 
+    /// Show CPU and memory usage per service
+    Stats(StatsArgs),
+
+    /// Show service logs
+    Logs(LogsArgs),
+
     /// Check environment readiness
     Check(CheckArgs),
 
+    /// Diagnose common problems with a running deployment
+    Doctor(DoctorArgs),
+
     /// Manage certificates
     Certs(CertArgs),
 
@@ -48,11 +118,23 @@ pub enum Commands {
     /// Check service health
     Health(HealthArgs),
 
+    /// Wait until services report healthy
+    WaitReady(WaitReadyArgs),
+
+    /// Watch service health and alert when a service stays unhealthy
+    Monitor(MonitorArgs),
+
+    /// Serve service health as Prometheus metrics, with a /healthz liveness route
+    Serve(ServeArgs),
+
     /// Deploy and configure Jetstream
     DeployJetstream(DeployJetstreamArgs),
 
     /// Subscribe to Jetstream
     Subscribe(SubscribeArgs),
+
+    /// Update atc to the latest release
+    SelfUpdate(SelfUpdateArgs),
 }
 
 #[derive(Args, Debug)]
@@ -64,6 +146,14 @@ pub struct InitArgs {
     /// Email for Let's Encrypt certificates
     #[arg(long)]
     pub cert_email: String,
+
+    /// Pin the PDS image to this tag (e.g., v0.4.0) instead of latest
+    #[arg(long)]
+    pub pds_version: Option<String>,
+
+    /// Show how the compose file and Caddyfile would change without writing anything
+    #[arg(long)]
+    pub diff: bool,
 }
 
 #[derive(Args, Debug)]
@@ -75,6 +165,26 @@ pub struct StartArgs {
     /// Skip dependency checks
     #[arg(long)]
     pub no_deps: bool,
+
+    /// Pull images concurrently before starting
+    #[arg(long)]
+    pub parallel_pull: bool,
+
+    /// Remove containers for services no longer in the compose file
+    #[arg(long)]
+    pub remove_orphans: bool,
+
+    /// Build services that declare a build section before starting
+    #[arg(long)]
+    pub build: bool,
+
+    /// Build without the image cache (implies --build)
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Apply a configured Postgres major version change to the db service
+    #[arg(long)]
+    pub force: bool,
 }
 
 #[derive(Args, Debug)]
@@ -82,6 +192,88 @@ pub struct StopArgs {
     /// Remove containers and volumes
     #[arg(long)]
     pub clean: bool,
+
+    /// Remove containers for services no longer in the compose file
+    #[arg(long)]
+    pub remove_orphans: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RestartArgs {
+    /// Specific services to restart (all if not specified)
+    #[arg(long)]
+    pub services: Option<Vec<String>>,
+}
+
+#[derive(Args, Debug)]
+pub struct GraphArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = GraphFormat::Tree)]
+    pub format: GraphFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Indented tree, each service followed by its dependencies
+    Tree,
+    /// Graphviz DOT
+    Dot,
+}
+
+#[derive(Args, Debug)]
+pub struct RotateSecretsArgs {
+    /// Confirm the rotation; existing sessions and tokens stop working
+    #[arg(long)]
+    pub yes: bool,
+
+    /// Also replace the PLC rotation key (can break DID rotation)
+    #[arg(long)]
+    pub rotate_plc_key: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct UpdateArgs {
+    /// Specific services to update (all if not specified)
+    #[arg(long)]
+    pub services: Option<Vec<String>>,
+
+    /// Remove dangling images once the update succeeds
+    #[arg(long)]
+    pub prune_after: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BackupArgs {
+    /// Archive to write (e.g., atc-backup.tar.gz)
+    pub output: PathBuf,
+
+    /// Overwrite the archive if it already exists
+    #[arg(long)]
+    pub force: bool,
+
+    /// Split the archive into chunks of at most this size (e.g., 2GiB)
+    #[arg(long)]
+    pub split_size: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreArgs {
+    /// Backup archive, or the manifest of a split backup
+    pub archive: PathBuf,
+
+    /// Stop running services instead of refusing to restore
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct EnvArgs {
+    /// Service name from the compose file
+    pub service: String,
+
+    /// Show secret values instead of masking them
+    #[arg(long)]
+    pub reveal: bool,
 }
 
 #[derive(Args, Debug)]
@@ -92,6 +284,80 @@ pub struct CreateAccountArgs {
     pub email: String,
     /// Account password
     pub password: String,
+
+    /// Print the created account as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Invite code, for a PDS that requires one
+    #[arg(long)]
+    pub invite_code: Option<String>,
+
+    /// Also mint an app password with this name
+    #[arg(long)]
+    pub app_password: Option<String>,
+
+    /// Don't check the handle against the PDS's available user domains
+    #[arg(long)]
+    pub skip_domain_check: bool,
+
+    /// Wait for the PDS to report healthy before creating the account
+    #[arg(long)]
+    pub wait_healthy: bool,
+
+    /// Seconds to wait with --wait-healthy before giving up
+    #[arg(long, default_value_t = 120)]
+    pub wait_timeout: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct ListAccountsArgs {
+    /// Print the accounts as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DeleteAccountArgs {
+    /// DID of the account to delete
+    pub did: String,
+
+    /// Delete without asking for confirmation
+    #[arg(long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ResetPasswordArgs {
+    /// Handle or DID of the account
+    pub handle: String,
+    /// New account password
+    pub password: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CreateInviteCodeArgs {
+    /// How many accounts the code can create
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub uses: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckHandleArgs {
+    /// Handle to check (e.g., user.domain.com)
+    pub handle: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CreateAccountsBatchArgs {
+    /// CSV (with a handle,email,password,inviteCode header) or JSON file
+    pub path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct DidDocArgs {
+    /// Handle or DID to resolve
+    pub handle: String,
 }
 
 #[derive(Args, Debug)]
@@ -99,6 +365,19 @@ pub struct DeployFeedArgs {
     /// Publisher DID
     #[arg(long)]
     pub publisher_did: String,
+
+    /// Recreate the container even if compose considers it up to date
+    #[arg(long)]
+    pub recreate: bool,
+
+    /// Show how the compose file would change without writing or starting anything
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Seconds to wait for the feed generator to report healthy before
+    /// publishing the feed
+    #[arg(long, default_value_t = 120)]
+    pub wait_timeout: u64,
 }
 
 #[derive(Args, Debug)]
@@ -106,6 +385,82 @@ pub struct StatusArgs {
     /// Show detailed information
     #[arg(long)]
     pub verbose: bool,
+
+    /// Fail instead of warning if the PDS certificate changed
+    #[arg(long)]
+    pub strict_host_key: bool,
+
+    /// Re-pin the PDS certificate if it changed, e.g. after a renewal
+    #[arg(long)]
+    pub accept_new_cert: bool,
+
+    /// Output format
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Exit non-zero if any service in the compose file is not running
+    #[arg(long)]
+    pub fail_on_down: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Only run this phase (repeatable); runs every phase by default
+    #[arg(long, value_enum, value_name = "PHASE")]
+    pub only: Vec<DoctorPhase>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoctorPhase {
+    Dns,
+    Docker,
+    Certs,
+    Compose,
+    Health,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Print usage as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct LogsArgs {
+    /// Only show logs for this service (default: all services)
+    pub service: Option<String>,
+
+    /// Keep streaming new output
+    #[arg(short, long)]
+    pub follow: bool,
+
+    /// Number of lines to show from the end of each service's logs
+    /// (default 200 unless following or filtering by time, at most 10000)
+    #[arg(short = 'n', long)]
+    pub tail: Option<usize>,
+
+    /// Only show logs after this RFC3339 timestamp or relative duration (e.g., 30m)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only show logs before this RFC3339 timestamp or relative duration
+    #[arg(long)]
+    pub until: Option<String>,
+
+    /// Omit the service name prefix from each line
+    #[arg(long)]
+    pub no_prefix: bool,
+
+    /// Don't color service prefixes
+    #[arg(long)]
+    pub no_color: bool,
 }
 
 #[derive(Args, Debug)]
@@ -114,9 +469,29 @@ pub struct CheckArgs {
     #[arg(long)]
     pub no_dns: bool,
 
+    /// Wait up to this many seconds for DNS records to propagate
+    #[arg(long, value_name = "SECONDS")]
+    pub wait_dns: Option<u64>,
+
+    /// Seconds to wait for each DNS query (overrides dns.timeout_secs)
+    #[arg(long, value_name = "SECONDS")]
+    pub dns_timeout: Option<u64>,
+
+    /// Retries for a timed-out DNS query (overrides dns.retries)
+    #[arg(long)]
+    pub dns_retries: Option<u32>,
+
     /// Skip Docker checks
     #[arg(long)]
     pub no_docker: bool,
+
+    /// Also probe the health of running services
+    #[arg(long)]
+    pub health: bool,
+
+    /// Treat config warnings (e.g. privileged ports) as errors
+    #[arg(long)]
+    pub strict: bool,
 }
 
 #[derive(Args, Debug)]
@@ -124,28 +499,60 @@ pub struct CertArgs {
     /// Generate and install self-signed certificates
     #[arg(long)]
     pub self_signed: bool,
+
+    /// Write certificates here instead of the configured storage.cert_dir
+    #[arg(long, visible_alias = "output-dir")]
+    pub cert_dir: Option<PathBuf>,
+
+    /// Obtain Let's Encrypt certificates through a DNS-01 challenge with
+    /// this provider (needed for the wildcard hosts). images.caddy must
+    /// point at a Caddy build with the provider's caddy-dns module
+    #[arg(long, value_enum)]
+    pub dns_provider: Option<DnsProvider>,
+
+    /// Credentials for --dns-provider: an API token for Cloudflare,
+    /// ACCESS_KEY_ID:SECRET_ACCESS_KEY for Route 53. Prefer
+    /// --api-token-file or ATC_DNS_API_TOKEN, which stay out of shell history
+    #[arg(long, requires = "dns_provider", conflicts_with = "api_token_file")]
+    pub api_token: Option<String>,
+
+    /// Read the --dns-provider credentials from this file
+    #[arg(long, requires = "dns_provider")]
+    pub api_token_file: Option<PathBuf>,
+
+    /// Enable --dns-provider even though images.caddy is not set
+    #[arg(long, requires = "dns_provider")]
+    pub force: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct DeployOzoneArgs {
-    /// Server DID
+    /// Server DID (read from the [ozone] config section if omitted)
+    #[arg(long)]
+    pub server_did: Option<String>,
+
+    /// Admin DIDs, comma-separated (read from the [ozone] config section if omitted)
     #[arg(long)]
-    pub server_did: String,
+    pub admin_dids: Option<String>,
 
-    /// Admin DIDs (comma-separated)
+    /// Recreate the container even if compose considers it up to date
     #[arg(long)]
-    pub admin_dids: String,
+    pub recreate: bool,
+
+    /// Show how the compose file and Caddyfile would change without writing anything
+    #[arg(long)]
+    pub diff: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct ConfigureOzoneArgs {
-    /// Admin handle
-    #[arg(long)]
-    pub handle: String,
+    /// Admin handle; repeat or comma-separate to configure several admins
+    #[arg(long = "handle", value_delimiter = ',', required = true)]
+    pub handles: Vec<String>,
 
-    /// PLC sign token
-    #[arg(long)]
-    pub plc_sign_token: String,
+    /// PLC sign token for each admin, in the same order as --handle
+    #[arg(long = "plc-sign-token", value_delimiter = ',', required = true)]
+    pub plc_sign_tokens: Vec<String>,
 
     /// Ozone URL
     #[arg(long)]
@@ -161,6 +568,76 @@ pub struct HealthArgs {
     /// Include detailed health metrics
     #[arg(long)]
     pub verbose: bool,
+
+    /// Fail instead of warning if the PDS certificate changed
+    #[arg(long)]
+    pub strict_host_key: bool,
+
+    /// Re-pin the PDS certificate if it changed, e.g. after a renewal
+    #[arg(long)]
+    pub accept_new_cert: bool,
+
+    /// Output format
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Seconds to wait for each health request
+    #[arg(long, default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Times to retry a service that isn't healthy before reporting it
+    #[arg(long, default_value_t = 0)]
+    pub retries: u32,
+
+    /// Keep probing until Ctrl-C, posting state changes to
+    /// notifications.webhook_url
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Seconds between probes with --watch
+    #[arg(long, default_value_t = 10, requires = "watch")]
+    pub interval: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct WaitReadyArgs {
+    /// Specific services to wait for (all if not specified)
+    #[arg(long)]
+    pub services: Option<Vec<String>>,
+
+    /// Give up after this many seconds
+    #[arg(long, default_value_t = 120)]
+    pub timeout: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct MonitorArgs {
+    /// Specific services to watch (all if not specified)
+    #[arg(long)]
+    pub services: Option<Vec<String>>,
+
+    /// Seconds between health probes
+    #[arg(long, default_value_t = 10)]
+    pub interval: u64,
+
+    /// Alert once a service has been unhealthy for this many seconds
+    #[arg(long, default_value_t = 60)]
+    pub threshold: u64,
+
+    /// URL to POST a JSON alert to before exiting
+    #[arg(long)]
+    pub webhook: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:9100")]
+    pub listen: std::net::SocketAddr,
+
+    /// Specific services to report (all if not specified)
+    #[arg(long)]
+    pub services: Option<Vec<String>>,
 }
 
 #[derive(Args, Debug)]
@@ -168,11 +645,78 @@ pub struct DeployJetstreamArgs {
     /// Custom reconnect delay in milliseconds
     #[arg(long)]
     pub reconnect_delay: Option<u32>,
+
+    /// Recreate the container even if compose considers it up to date
+    #[arg(long)]
+    pub recreate: bool,
+
+    /// Validate and show the compose changes without writing or starting anything
+    #[arg(long)]
+    pub check_only: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct SubscribeArgs {
-    /// Collections to subscribe to (comma-separated)
+    /// Collections to subscribe to (comma-separated NSIDs; defaults to the
+    /// standard app.bsky collections)
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(Nsid))]
+    pub collections: Vec<Nsid>,
+
+    /// Only receive events from these repos (comma-separated DIDs)
+    #[arg(long, value_delimiter = ',')]
+    pub dids: Vec<String>,
+
+    /// Remember this subscription in config/jetstream.toml
     #[arg(long)]
-    pub collections: Vec<String>,
+    pub save: bool,
+
+    /// Re-run the subscription saved with --save
+    #[arg(long, conflicts_with_all = ["collections", "dids"])]
+    pub resume: bool,
+
+    /// Replay from this cursor (a `time_us` value). Overrides the cursor
+    /// left in .jetstream-cursor by the previous subscription
+    #[arg(long)]
+    pub cursor: Option<u64>,
+
+    /// Write events to this NDJSON file instead of stdout
+    #[arg(long, conflicts_with = "split_dir")]
+    pub output: Option<PathBuf>,
+
+    /// Write events to <dir>/<collection>.ndjson instead of stdout
+    #[arg(long)]
+    pub split_dir: Option<PathBuf>,
+
+    /// Disconnect after writing this many events
+    #[arg(long)]
+    pub max_events: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+pub struct SelfUpdateArgs {
+    /// Only report whether a newer release is available
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribe_collections_are_validated() {
+        let cli = Cli::try_parse_from([
+            "atc",
+            "subscribe",
+            "--collections",
+            "app.bsky.feed.post,app.bsky.feed.like",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Subscribe(args) => assert_eq!(args.collections.len(), 2),
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        assert!(Cli::try_parse_from(["atc", "subscribe", "--collections", "not-an-nsid"]).is_err());
+    }
 }
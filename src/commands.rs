@@ -1,55 +1,108 @@
 use crate::{
+    accounts::{create_accounts, load_records},
     api::PdsClient,
-    caddy::CaddyConfig,
+    backup::{manifest_path, Backup},
+    caddy::{validate_caddyfile, CaddyConfig},
     certs::CertManager,
-    cli::Commands,
-    compose::ComposeConfig,
-    config::Config,
-    dns::DnsChecker,
-    docker::DockerService,
+    cli::{Commands, CreateAccountArgs, DoctorPhase, GraphFormat, OutputFormat},
+    compose::{
+        postgres_upgrade_warning, ComposeConfig, EnvVars, FeedSubscription, ServiceDiff,
+        TeardownPlan, PDS_IMAGE,
+    },
+    config::{normalize_domain, parse_byte_size, Config},
+    configure::{edit_config, edit_config_file, EnvEditor, Prompter, TerminalPrompter},
+    dns::{unresolved_names, wait_for_dns, DigResolver, DnsChecker, Resolver},
+    docker::{default_project_name, find_orphans, DockerService, DockerServiceTrait},
     error::{Error, Result},
     feed::FeedGenerator,
-    ozone::OzoneClient,
-    secrets::Secrets,
+    jetstream::{
+        load_cursor, JetstreamConfig, StreamEnd, JETSTREAM_CONFIG_PATH, JETSTREAM_CURSOR_PATH,
+    },
+    logs::{LogStyle, LogWindow},
+    monitor::Monitor,
+    notify::Notifier,
+    ozone::{OzoneAdmin, OzoneClient},
+    retry::{poll_until, Backoff},
+    secrets::{rotate_admin_password, rotate_secrets, Secrets},
+    self_update::SelfUpdater,
+    serve::MetricsServer,
 };
 use owo_colors::OwoColorize;
-use std::{fs, path::Path};
+use std::{fs, io::IsTerminal, path::Path};
 use tracing::{info, warn};
 
+const MAX_PARALLEL_PULLS: usize = 4;
+const RATE_LIMIT_RETRIES: u32 = 1;
+const DEFAULT_JETSTREAM_RECONNECT_DELAY: u32 = 200;
+
+/// Environment file compose reads from the project directory.
+const ENV_FILE_PATH: &str = ".env";
+const COMPOSE_PATH: &str = "docker-compose.yml";
+const CADDYFILE_PATH: &str = "config/caddy/Caddyfile";
+
+/// Where `certs --dns-provider` reads credentials from when neither
+/// `--api-token` nor `--api-token-file` is given.
+const DNS_API_TOKEN_ENV: &str = "ATC_DNS_API_TOKEN";
+
+/// Switches into the project directory so every relative path (config,
+/// compose file, secrets, certs) resolves against it.
+pub fn enter_project_dir(dir: Option<&Path>) -> Result<()> {
+    let Some(dir) = dir else {
+        return Ok(());
+    };
+
+    if !dir.is_dir() {
+        return Err(Error::Config(format!(
+            "Project directory not found: {}",
+            dir.display()
+        )));
+    }
+
+    std::env::set_current_dir(dir)?;
+    Ok(())
+}
+
 pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
     match cmd {
         Commands::Init(args) => {
             info!("Initializing new Bluesky configuration...");
             let mut config = Config::default();
-            config.network.domain = args.domain;
+            config.network.domain = normalize_domain(&args.domain)?;
             config.email.cert_email = args.cert_email;
+            if let Some(version) = &args.pds_version {
+                config
+                    .images
+                    .insert("pds".to_string(), format!("{}:{}", PDS_IMAGE, version));
+            }
+            config.validate()?;
+
+            if args.diff {
+                let compose = init_compose(&config, deployed_db_image().as_deref())?;
+                return print_file_diffs(&[
+                    (Path::new(COMPOSE_PATH), compose.to_canonical_yaml()?),
+                    (Path::new(CADDYFILE_PATH), caddy_config(&config).generate()),
+                ]);
+            }
 
             // Generate secrets
             info!("Generating secrets...");
             let secrets = Secrets::generate();
             fs::create_dir_all("config")?;
             secrets.save("config/secrets.toml")?;
+            // Lets docker-compose be run by hand with the same values
+            secrets.save_env_file(ENV_FILE_PATH, &config.network.domain)?;
             println!("{}", "Secrets generated successfully!".green());
 
             // Create directories
             fs::create_dir_all("config/caddy")?;
-            fs::create_dir_all("certs")?;
+            fs::create_dir_all(&config.storage.cert_dir)?;
             fs::create_dir_all("data")?;
 
             // Create docker-compose.yml
-            let mut compose = ComposeConfig::new();
-            let mut env_vars = create_env_vars(&config);
-            env_vars.extend(secrets.as_env_vars().into_iter());
-
-            compose
-                .add_caddy()
-                .add_pds(&config.network.domain)
-                .add_plc()
-                .add_bgs()
-                .add_appview();
+            let compose = init_compose(&config, deployed_db_image().as_deref())?;
 
             // Generate Caddyfile
-            let caddy = CaddyConfig::new(&config.network.domain);
+            let caddy = caddy_config(&config);
             caddy.save("config/caddy/Caddyfile")?;
             println!("{}", "Caddyfile generated successfully!".green());
 
@@ -57,16 +110,33 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
             config.save(config_path)?;
             compose.save("docker-compose.yml")?;
 
+            verify_generated(
+                config_path,
+                Path::new(COMPOSE_PATH),
+                Path::new(CADDYFILE_PATH),
+            )
+            .map_err(|e| {
+                Error::Config(format!(
+                    "{}; the generated files were left for inspection",
+                    e
+                ))
+            })?;
+
             println!("{}", "Configuration created successfully!".green());
             Ok(())
         }
 
         Commands::Start(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
+            config.validate()?;
             info!("Starting services...");
+            upgrade_secrets(&config)?;
 
             if !args.no_deps {
-                DockerService::check_dependencies().await?;
+                DockerService::new("docker-compose.yml")
+                    .with_requirements(config.requirements)
+                    .check_dependencies()
+                    .await?;
             }
 
             // Ensure compose file exists
@@ -78,8 +148,71 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
                 )));
             }
 
+            // Stamp limits and images set in config since the compose file
+            // was generated
+            let mut compose = ComposeConfig::load(compose_path)?;
+            compose.validate_container_names()?;
+            if !config.containers.resources.is_empty()
+                || !config.images.is_empty()
+                || compose.services.contains_key("db")
+            {
+                let before = compose.to_canonical_yaml()?;
+                let deployed_db = compose.services.get("db").map(|db| db.image.clone());
+                for (service, limit) in &config.containers.resources {
+                    compose.apply_resource_limits(service, limit.cpus, limit.memory.as_deref());
+                }
+                compose.apply_images(&config.images);
+                stamp_postgres_image(&mut compose, &config, deployed_db.as_deref(), args.force);
+                if compose.to_canonical_yaml()? != before {
+                    compose.save(compose_path)?;
+                }
+            }
+
             // Start services using the compose file
-            let docker = DockerService::new(compose_path).with_env_vars(create_env_vars(&config));
+            let docker = DockerService::new(compose_path)
+                .with_env_vars(create_env_vars(&config))
+                .with_remove_orphans(args.remove_orphans);
+
+            if args.parallel_pull {
+                let services = match &args.services {
+                    Some(services) => services.clone(),
+                    None => {
+                        let mut names: Vec<String> = ComposeConfig::load(compose_path)?
+                            .services
+                            .into_keys()
+                            .collect();
+                        names.sort();
+                        names
+                    }
+                };
+
+                info!("Pulling {} images in parallel...", services.len());
+                let report = docker
+                    .pull_parallel(&services, MAX_PARALLEL_PULLS, RATE_LIMIT_RETRIES)
+                    .await?;
+                for service in &report.succeeded {
+                    println!("  {} {}", "✓".green(), service);
+                }
+                for (service, reason) in &report.failed {
+                    println!("  {} {}: {}", "✗".red(), service, reason);
+                }
+
+                if let Some(error) = report.error() {
+                    return Err(error);
+                }
+            }
+
+            if args.build || args.no_cache {
+                let services =
+                    ComposeConfig::load(compose_path)?.build_services(args.services.as_deref());
+                if services.is_empty() {
+                    info!("No services declare a build section; nothing to build");
+                } else {
+                    info!("Building {}...", services.join(", "));
+                    docker.build_services(&services, args.no_cache).await?;
+                }
+            }
+
             docker.start_services(args.services.as_deref()).await?;
             println!("{}", "Services started successfully!".green());
             Ok(())
@@ -94,7 +227,10 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
                 )));
             }
 
-            let docker = DockerService::new(compose_path);
+            let compose = ComposeConfig::load(compose_path)?;
+            print_teardown_plan(&compose.teardown_plan(args.clean));
+
+            let docker = DockerService::new(compose_path).with_remove_orphans(args.remove_orphans);
 
             if args.clean {
                 warn!("Stopping services and cleaning data...");
@@ -108,9 +244,127 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
             Ok(())
         }
 
+        Commands::Restart(args) => {
+            let config = Config::load_with_env(config_path)?;
+            info!("Restarting services...");
+
+            let compose_path = "docker-compose.yml";
+            if !Path::new(compose_path).exists() {
+                return Err(Error::Docker(format!(
+                    "Docker Compose file not found: {}. Run init first.",
+                    compose_path
+                )));
+            }
+
+            DockerService::new(compose_path)
+                .with_env_vars(create_env_vars(&config))
+                .restart_services(args.services.as_deref())
+                .await?;
+            println!("{}", "Services restarted successfully!".green());
+            Ok(())
+        }
+
+        Commands::Update(args) => {
+            let config = Config::load_with_env(config_path)?;
+            info!("Updating services...");
+            upgrade_secrets(&config)?;
+
+            let compose_path = "docker-compose.yml";
+            if !Path::new(compose_path).exists() {
+                return Err(Error::Docker(format!(
+                    "Docker Compose file not found: {}. Run init first.",
+                    compose_path
+                )));
+            }
+
+            let docker = DockerService::new(compose_path).with_env_vars(create_env_vars(&config));
+            let services = args.services.as_deref();
+            let before = docker.image_digests(services).await?;
+            docker.pull_and_recreate(services, args.prune_after).await?;
+            let after = docker.image_digests(services).await?;
+
+            println!("{}", "Services updated successfully!".green());
+            for (service, digest) in &after {
+                let old = before.get(service).cloned().flatten();
+                let new = digest.as_deref().unwrap_or("unknown");
+                match old {
+                    Some(old) if old == new => {
+                        println!("  {} {} unchanged", "=".dimmed(), service)
+                    }
+                    old => println!(
+                        "  {} {}: {} -> {}",
+                        "↑".green(),
+                        service,
+                        old.as_deref().unwrap_or("none"),
+                        new
+                    ),
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Backup(args) => {
+            info!("Backing up to {}", args.output.display());
+            let split_size = args
+                .split_size
+                .as_deref()
+                .map(parse_byte_size)
+                .transpose()?;
+            let backup = Backup::create(&args.output, args.force, split_size).await?;
+
+            println!("{}", "Backup created successfully!".green());
+            println!("Archive: {}", backup.output.display());
+            for file in &backup.files {
+                println!("  {} {}", "✓".green(), file);
+            }
+            for volume in &backup.volumes {
+                println!("  {} volume {}", "✓".green(), volume);
+            }
+            if let Some(manifest) = &backup.manifest {
+                println!(
+                    "Split into {} chunks; see {}",
+                    manifest.chunks.len(),
+                    manifest_path(&backup.output).display()
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Restore(args) => {
+            info!("Restoring from {}", args.archive.display());
+            let restored = Backup::restore(&args.archive, args.force).await?;
+
+            println!("{}", "Backup restored successfully!".green());
+            for file in &restored.files {
+                println!("  {} {}", "✓".green(), file);
+            }
+            for volume in &restored.volumes {
+                println!("  {} volume {}", "✓".green(), volume);
+            }
+            println!("Run start to bring services back up.");
+            Ok(())
+        }
+
+        Commands::RenderCompose => {
+            let config = Config::load_with_env(config_path)?;
+
+            let compose_path = "docker-compose.yml";
+            if !Path::new(compose_path).exists() {
+                return Err(Error::Docker(format!(
+                    "Docker Compose file not found: {}. Run init first.",
+                    compose_path
+                )));
+            }
+
+            let docker = DockerService::new(compose_path);
+            print!("{}", render_compose(&config, compose_path, docker).await?);
+            Ok(())
+        }
+
         Commands::Check(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
             info!("Checking environment readiness...");
+            config.validate_with(args.strict)?;
 
             // Check required files exist
             if !Path::new("docker-compose.yml").exists() {
@@ -123,84 +377,662 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
                 return Err(Error::Config("config/caddy directory not found".into()));
             }
 
+            ComposeConfig::load("docker-compose.yml")?
+                .verify_cert_mount(&config.storage.cert_dir)?;
+
             if !args.no_dns {
                 info!("Checking DNS configuration...");
-                if DnsChecker::check_domain(&config.network.domain).await? {
+                let mut dns = config.dns.options();
+                if let Some(secs) = args.dns_timeout {
+                    dns.timeout = std::time::Duration::from_secs(secs);
+                }
+                if let Some(retries) = args.dns_retries {
+                    dns.retries = retries;
+                }
+
+                if let Some(secs) = args.wait_dns {
+                    let resolved = wait_for_dns(
+                        &DigResolver::new(dns),
+                        &config.network.domain,
+                        dns,
+                        std::time::Duration::from_secs(secs),
+                        Backoff::new(
+                            std::time::Duration::from_secs(1),
+                            std::time::Duration::from_secs(15),
+                        ),
+                        |pending| println!("Waiting for DNS: {}", pending.join(", ").yellow()),
+                    )
+                    .await?;
+                    if !resolved {
+                        return Err(Error::Network(format!(
+                            "DNS records did not propagate within {}s",
+                            secs
+                        )));
+                    }
+                    println!("{}", "DNS configuration: OK".green());
+                } else if DnsChecker::check_domain(&config.network.domain, dns).await? {
+                    let unresolved =
+                        DnsChecker::check_subdomains(&config.network.domain, dns).await?;
+                    if !unresolved.is_empty() {
+                        warn!("Subdomains not resolving yet: {}", unresolved.join(", "));
+                    }
                     println!("{}", "DNS configuration: OK".green());
                 } else {
                     return Err(Error::Network("DNS checks failed".into()));
                 }
 
-                info!("Testing HTTPS endpoint...");
-                if DnsChecker::check_ssl_test_endpoint(&config.network.domain).await? {
-                    println!("{}", "HTTPS endpoint: OK".green());
-                } else {
-                    return Err(Error::Network("HTTPS endpoint test failed".into()));
+                // Connecting to our own public address can fail behind NAT
+                // even when outside clients get through, so only warn
+                info!("Testing port reachability...");
+                let ports = [config.network.ports.http, config.network.ports.https];
+                for port in ports {
+                    if DnsChecker::check_port(&config.network.domain, port).await? {
+                        println!("{}", format!("Port {}: OK", port).green());
+                    } else {
+                        warn!(
+                            "Port {} on {} is not reachable",
+                            port, config.network.domain
+                        );
+                        println!("{}", format!("Port {}: unreachable", port).yellow());
+                    }
                 }
 
-                info!("Testing WebSocket endpoint...");
-                if DnsChecker::check_websocket_endpoint(&config.network.domain).await? {
-                    println!("{}", "WebSocket endpoint: OK".green());
+                if !config.network.test_endpoint_enabled() {
+                    println!(
+                        "{}",
+                        "Endpoint probes skipped: test-wss endpoint is disabled".yellow()
+                    );
                 } else {
-                    return Err(Error::Network("WebSocket endpoint test failed".into()));
+                    info!("Testing HTTPS endpoint...");
+                    if DnsChecker::check_ssl_test_endpoint(&config.network.domain).await? {
+                        println!("{}", "HTTPS endpoint: OK".green());
+                    } else {
+                        return Err(Error::Network("HTTPS endpoint test failed".into()));
+                    }
+
+                    info!("Testing WebSocket endpoint...");
+                    if DnsChecker::check_websocket_endpoint(&config.network.domain).await? {
+                        println!("{}", "WebSocket endpoint: OK".green());
+                    } else {
+                        return Err(Error::Network("WebSocket endpoint test failed".into()));
+                    }
                 }
             }
 
             if !args.no_docker {
                 info!("Checking Docker dependencies...");
-                DockerService::check_dependencies().await?;
-                println!("{}", "Docker dependencies: OK".green());
+                let environment = DockerService::new("docker-compose.yml")
+                    .with_requirements(config.requirements)
+                    .check_dependencies()
+                    .await?;
+                println!(
+                    "{}",
+                    format!("Docker dependencies: OK (using {})", environment.compose).green()
+                );
+            }
+
+            if args.health {
+                info!("Checking service health...");
+                let checker = crate::health::HealthChecker::for_network(&config.network)
+                    .with_endpoints(config.health.clone());
+                let statuses = checker.check_all(&default_health_services()).await?;
+
+                for status in &statuses {
+                    print_health_status(status, false);
+                }
+
+                let unhealthy: Vec<_> = statuses
+                    .iter()
+                    .filter(|s| s.status != crate::health::HealthState::Healthy)
+                    .map(|s| s.service.as_str())
+                    .collect();
+                if !unhealthy.is_empty() {
+                    return Err(Error::Network(format!(
+                        "Unhealthy services: {}",
+                        unhealthy.join(", ")
+                    )));
+                }
+                println!("{}", "Service health: OK".green());
             }
 
             println!("{}", "Environment check completed successfully!".green());
             Ok(())
         }
 
+        Commands::Doctor(args) => {
+            let config = Config::load_with_env(config_path)?;
+            info!("Running diagnostics...");
+            let docker =
+                DockerService::new("docker-compose.yml").with_requirements(config.requirements);
+            let resolver = DigResolver::new(config.dns.options());
+            run_doctor(&config, &docker, &resolver, &args.only).await
+        }
+
+        Commands::Graph(args) => {
+            let compose = ComposeConfig::load("docker-compose.yml")?;
+            match args.format {
+                GraphFormat::Tree => print!("{}", compose.dependency_tree()),
+                GraphFormat::Dot => print!("{}", compose.dependency_dot()),
+            }
+
+            if let Some(cycle) = compose.dependency_cycle() {
+                return Err(Error::Config(format!(
+                    "Dependency cycle: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+            Ok(())
+        }
+
+        Commands::Env(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let compose = ComposeConfig::load("docker-compose.yml")?;
+
+            // Same precedence as `start`: secrets first, explicit vars override
+            let mut env = std::collections::HashMap::new();
+            let mut secret_names = Vec::new();
+            if Path::new("config/secrets.toml").exists() {
+                let secrets = Secrets::load("config/secrets.toml")?;
+                for (key, value) in secrets.as_env_vars() {
+                    secret_names.push(key.clone());
+                    env.insert(key, value);
+                }
+            }
+            env.extend(create_env_vars(&config));
+
+            for line in env_lines(&compose, &args.service, &env, &secret_names, args.reveal)? {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+
+        Commands::Configure => {
+            if !std::io::stdin().is_terminal() {
+                return Err(Error::Config(format!(
+                    "configure needs an interactive terminal; edit {} manually instead",
+                    config_path.display()
+                )));
+            }
+
+            // Raw load: the prompts edit and save the file, so env overrides
+            // must not be shown as current values or written back
+            let current = Config::load(config_path)?;
+            let config = edit_config(&current, &mut TerminalPrompter)?;
+            config.save(config_path)?;
+
+            println!(
+                "{}",
+                format!("Configuration saved to {}", config_path.display()).green()
+            );
+            if config.network != current.network || config.containers != current.containers {
+                println!("Run init again to regenerate service files with the new settings.");
+            }
+            Ok(())
+        }
+
+        Commands::EditConfig => {
+            if !std::io::stdin().is_terminal() {
+                return Err(Error::Config(format!(
+                    "edit-config needs an interactive terminal; edit {} manually instead",
+                    config_path.display()
+                )));
+            }
+
+            let mut editor = EnvEditor::from_env()?;
+            // The current file may be the broken one the user wants to fix.
+            // Raw load, since this compares the file before and after the
+            // edit and env overrides are part of neither
+            let current = Config::load(config_path).ok();
+            let config = edit_config_file(config_path, &mut editor, &mut TerminalPrompter)?;
+
+            println!(
+                "{}",
+                format!("Configuration saved to {}", config_path.display()).green()
+            );
+            let changed = current.is_some_and(|current| {
+                config.network != current.network || config.containers != current.containers
+            });
+            if changed {
+                println!("Run init again to regenerate service files with the new settings.");
+            }
+            Ok(())
+        }
+
         Commands::CreateAccount(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
+            config.validate()?;
             info!("Creating account: {}", args.handle);
 
-            let client = PdsClient::new(&config.network.domain);
-            let account = client
-                .create_account(args.handle, args.email, args.password)
+            let client = PdsClient::new(&config.network);
+            // Probe the PDS the same way the client will reach it
+            let checker = crate::health::HealthChecker::for_network(&config.network)
+                .with_endpoints(config.health.clone());
+            let json = args.json;
+            let account = create_account(&client, &checker, args).await?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&account)?);
+            } else {
+                println!("{}", "Account created successfully!".green());
+                println!("DID: {}", account.did);
+                println!("Handle: {}", account.handle);
+                if let Some(app_password) = &account.app_password {
+                    println!("App password: {}", app_password);
+                }
+            }
+            Ok(())
+        }
+
+        Commands::CreateInviteCode(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let secrets = Secrets::load("config/secrets.toml")?;
+
+            let code = PdsClient::new(&config.network)
+                .create_invite_code(args.uses, &secrets.pds_admin_password)
+                .await?;
+            println!("{}", code);
+            Ok(())
+        }
+
+        Commands::CheckHandle(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let client = PdsClient::new(&config.network);
+            client.check_handle_domain(&args.handle).await?;
+            println!("{} {} is available on this PDS", "✓".green(), args.handle);
+            Ok(())
+        }
+
+        Commands::CreateAccountsBatch(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let records = load_records(&args.path)?;
+            info!(
+                "Creating {} accounts from {}",
+                records.len(),
+                args.path.display()
+            );
+
+            let client = PdsClient::new(&config.network);
+            let summary = create_accounts(&client, records, |result| match &result.outcome {
+                Ok(did) => println!("{} {} ({})", "✓".green(), result.handle, did),
+                Err(e) => println!(
+                    "{} {} (row {}): {}",
+                    "✗".red(),
+                    result.handle,
+                    result.row,
+                    e
+                ),
+            })
+            .await;
+
+            println!(
+                "\nCreated {}, failed {}",
+                summary.created(),
+                summary.failed()
+            );
+            if summary.failed() > 0 {
+                return Err(Error::Api(format!(
+                    "{} of {} accounts could not be created",
+                    summary.failed(),
+                    summary.results.len()
+                )));
+            }
+            Ok(())
+        }
+
+        Commands::ListAccounts(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let accounts = PdsClient::new(&config.network).list_accounts().await?;
+
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&accounts)?);
+                return Ok(());
+            }
+            if accounts.is_empty() {
+                println!("{}", "No accounts on this PDS.".yellow());
+                return Ok(());
+            }
+
+            println!("{:<32} {}", "DID".bold(), "HANDLE".bold());
+            for account in &accounts {
+                println!("{:<32} {}", account.did, account.handle);
+            }
+            Ok(())
+        }
+
+        Commands::DeleteAccount(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let secrets = Secrets::load("config/secrets.toml")?;
+
+            if !args.yes {
+                if !std::io::stdin().is_terminal() {
+                    return Err(Error::Config(
+                        "Refusing to delete an account without confirmation; pass --yes".into(),
+                    ));
+                }
+                println!(
+                    "{}",
+                    format!(
+                        "Account {} and all its data will be deleted permanently.",
+                        args.did
+                    )
+                    .red()
+                    .bold()
+                );
+                if !TerminalPrompter.confirm("Delete this account?", false)? {
+                    println!("Aborted; nothing was deleted.");
+                    return Ok(());
+                }
+            }
+
+            info!("Deleting account {}...", args.did);
+            PdsClient::new(&config.network)
+                .delete_account(&args.did, &secrets.pds_admin_password)
+                .await?;
+            println!("{} {}", "Deleted account".green(), args.did);
+            Ok(())
+        }
+
+        Commands::ResetPassword(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let secrets = Secrets::load("config/secrets.toml")?;
+
+            info!("Resetting password for {}...", args.handle);
+            PdsClient::new(&config.network)
+                .reset_account_password(&args.handle, &args.password, &secrets.pds_admin_password)
+                .await?;
+            println!("{} {}", "Password reset for".green(), args.handle);
+            Ok(())
+        }
+
+        Commands::RotateAdminPassword => {
+            let config = Config::load_with_env(config_path)?;
+            info!("Rotating PDS admin password...");
+
+            let secrets_path = "config/secrets.toml";
+            let compose_path = "docker-compose.yml";
+            let base_env = create_env_vars(&config);
+            let client = PdsClient::new(&config.network);
+
+            let result = rotate_admin_password(secrets_path, |password| {
+                let mut env_vars = base_env.clone();
+                env_vars.insert("PDS_ADMIN_PASSWORD".to_string(), password.clone());
+                let docker = DockerService::new(compose_path).with_env_vars(env_vars);
+                let client = &client;
+
+                async move {
+                    // Changing the env makes compose recreate the container
+                    docker.start_services(Some(&[String::from("pds")])).await?;
+
+                    let accepted = poll_until(
+                        std::time::Duration::from_secs(60),
+                        Backoff::default(),
+                        || async {
+                            Ok(client
+                                .verify_admin_password(&password)
+                                .await
+                                .unwrap_or(false))
+                        },
+                    )
+                    .await?;
+
+                    if accepted {
+                        Ok(())
+                    } else {
+                        Err(Error::Api(
+                            "PDS did not accept the new admin password".into(),
+                        ))
+                    }
+                }
+            })
+            .await;
+
+            if let Err(e) = result {
+                warn!("Rotation failed, restoring PDS with the existing password");
+                let docker = DockerService::new(compose_path).with_env_vars(base_env);
+                if let Err(restore) = docker.start_services(Some(&[String::from("pds")])).await {
+                    warn!("Failed to restore PDS: {}", restore);
+                }
+                return Err(e);
+            }
+
+            if Path::new(ENV_FILE_PATH).exists() {
+                Secrets::load(secrets_path)?
+                    .save_env_file(ENV_FILE_PATH, &config.network.domain)?;
+            }
+
+            println!("{}", "Admin password rotated successfully!".green());
+            println!("Previous secrets saved to {}.bak", secrets_path);
+            Ok(())
+        }
+
+        Commands::RotateSecrets(args) => {
+            if !args.yes {
+                return Err(Error::Config(
+                    "Rotating secrets invalidates existing sessions; pass --yes to confirm".into(),
+                ));
+            }
+            let config = Config::load_with_env(config_path)?;
+            if args.rotate_plc_key {
+                warn!(
+                    "Rotating PDS_PLC_ROTATION_KEY_K256: DIDs whose PLC documents list the old \
+                     key can no longer be updated by this PDS"
+                );
+                println!(
+                    "{}",
+                    "WARNING: the PLC rotation key is being replaced. DID rotation for existing \
+                     accounts may break."
+                        .red()
+                        .bold()
+                );
+            }
+
+            let secrets_path = "config/secrets.toml";
+            let secrets = rotate_secrets(secrets_path, args.rotate_plc_key)?;
+            if Path::new(ENV_FILE_PATH).exists() {
+                secrets.save_env_file(ENV_FILE_PATH, &config.network.domain)?;
+            }
+
+            // Only the PDS reads these secrets; the new env makes compose recreate it
+            info!("Restarting pds with the new secrets...");
+            let mut env_vars = create_env_vars(&config);
+            env_vars.extend(secrets.as_env_vars());
+            DockerService::new("docker-compose.yml")
+                .with_env_vars(env_vars)
+                .start_services(Some(&[String::from("pds")]))
                 .await?;
 
-            println!("{}", "Account created successfully!".green());
-            println!("DID: {}", account.did);
-            println!("Handle: {}", account.handle);
+            println!("{}", "Secrets rotated successfully!".green());
+            println!("Previous secrets saved to {}.bak", secrets_path);
+            Ok(())
+        }
+
+        Commands::DidDoc(args) => {
+            let config = Config::load_with_env(config_path)?;
+            info!("Resolving DID document for {}", args.handle);
+
+            let client = PdsClient::new(&config.network);
+            let repo = client.describe_repo(&args.handle).await?;
+            let doc = repo.document()?;
+            if doc.id != repo.did {
+                warn!("DID document id {} does not match {}", doc.id, repo.did);
+            }
+
+            println!("{} {}", "DID:".bold(), repo.did);
+            let handle_state = if repo.handle_is_correct {
+                "✓".green().to_string()
+            } else {
+                "✗ (does not resolve back to this DID)".red().to_string()
+            };
+            println!("{} {} {}", "Handle:".bold(), repo.handle, handle_state);
+            if !doc.claims_handle(&repo.handle) {
+                warn!(
+                    "DID document does not list at://{} in alsoKnownAs",
+                    repo.handle
+                );
+            }
+
+            match doc.pds_endpoint() {
+                Some(endpoint) => println!("{} {}", "PDS endpoint:".bold(), endpoint.cyan()),
+                None => println!("{} {}", "PDS endpoint:".bold(), "missing".red()),
+            }
+
+            for method in &doc.verification_method {
+                println!(
+                    "{} {} {}",
+                    "Signing key:".bold(),
+                    method.id,
+                    method
+                        .public_key_multibase
+                        .as_deref()
+                        .unwrap_or("-")
+                        .yellow()
+                );
+            }
+
+            if repo.did.starts_with("did:plc:") {
+                let plc_url = format!("https://plc.{}", config.network.domain);
+                match client.fetch_plc_data(&plc_url, &repo.did).await {
+                    Ok(data) => {
+                        for key in &data.rotation_keys {
+                            println!("{} {}", "Rotation key:".bold(), key.yellow());
+                        }
+                    }
+                    Err(e) => warn!("Could not fetch rotation keys: {}", e),
+                }
+            }
+
+            println!("\n{}", serde_json::to_string_pretty(&repo.did_doc)?);
             Ok(())
         }
 
         Commands::Certs(args) => {
+            // With --cert-dir the config is optional, so self-signed certs
+            // can be generated before init
+            let (cert_dir, config) = match args.cert_dir {
+                Some(dir) if !config_path.exists() => (dir, None),
+                Some(dir) => (dir, Some(Config::load_with_env(config_path)?)),
+                None => {
+                    let config = Config::load_with_env(config_path)?;
+                    (config.storage.cert_dir.clone(), Some(config))
+                }
+            };
+            if let Some(config) = config.as_ref().filter(|c| c.storage.cert_dir != cert_dir) {
+                warn!(
+                    "Caddy mounts {}; set storage.cert_dir to {} and re-run init so it finds these certificates",
+                    config.storage.cert_dir.display(),
+                    cert_dir.display()
+                );
+            }
+
             if args.self_signed {
-                info!("Generating self-signed certificates...");
-                CertManager::generate_self_signed_ca("certs").await?;
+                info!(
+                    "Generating self-signed certificates in {}...",
+                    cert_dir.display()
+                );
+                CertManager::generate_self_signed_ca(&cert_dir).await?;
                 println!("{}", "Certificates generated successfully!".green());
 
                 info!("Installing CA certificate...");
-                CertManager::install_ca_cert("certs/root.crt").await?;
+                CertManager::install_ca_cert(cert_dir.join("root.crt")).await?;
                 println!("{}", "CA certificate installed successfully!".green());
             }
+
+            if let Some(provider) = args.dns_provider {
+                let mut config = match config {
+                    Some(config) => config,
+                    None => Config::load_with_env(config_path)?,
+                };
+                let custom_caddy = config.images.contains_key("caddy");
+                if !custom_caddy && !args.force {
+                    return Err(Error::Config(format!(
+                        "The stock Caddy image has no caddy-dns/{} module and would not start; \
+                         set images.caddy to a build that includes it, or pass --force",
+                        provider
+                    )));
+                }
+                let token = dns_api_token(args.api_token, args.api_token_file.as_deref())?;
+                config.network.dns_provider = Some(provider);
+                let secrets_path = "config/secrets.toml";
+                CertManager::configure_dns_challenge(
+                    caddy_config(&config),
+                    provider,
+                    &token,
+                    secrets_path,
+                    CADDYFILE_PATH,
+                )?;
+
+                // Saved without the env overrides load_with_env applied
+                let mut stored = Config::load(config_path)?;
+                stored.network.dns_provider = Some(provider);
+                stored.save(config_path)?;
+
+                if Path::new(COMPOSE_PATH).exists() {
+                    let mut compose = ComposeConfig::load(COMPOSE_PATH)?;
+                    compose.apply_passthrough_env("caddy", provider.env_keys());
+                    compose.save(COMPOSE_PATH)?;
+                }
+                if Path::new(ENV_FILE_PATH).exists() {
+                    Secrets::load(secrets_path)?
+                        .save_env_file(ENV_FILE_PATH, &config.network.domain)?;
+                }
+
+                println!(
+                    "{}",
+                    format!("DNS-01 challenges enabled with {}", provider).green()
+                );
+                if custom_caddy {
+                    println!("Restart caddy to apply the new Caddyfile");
+                } else {
+                    println!(
+                        "Caddy needs the caddy-dns/{} module; point images.caddy at a build \
+                         that includes it, then restart caddy",
+                        provider
+                    );
+                }
+            }
             Ok(())
         }
 
         Commands::DeployFeed(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
+            config.validate()?;
             info!("Deploying feed generator...");
 
             // Add feed generator to compose
             let mut compose = ComposeConfig::load("docker-compose.yml")?;
-            compose.add_feed_generator(&args.publisher_did);
+            let subscription = config.feed.subscription;
+            let mut services = vec![String::from("feed-generator")];
+            if subscription == FeedSubscription::Jetstream
+                && !compose.services.contains_key("jetstream")
+            {
+                info!("Adding jetstream for the feed generator subscription...");
+                compose.add_jetstream(DEFAULT_JETSTREAM_RECONNECT_DELAY);
+                services.insert(0, String::from("jetstream"));
+            }
+            compose.add_feed_generator(&args.publisher_did, subscription);
+            finalize_compose(&mut compose, &config, deployed_db_image().as_deref())?;
+            if args.diff {
+                return print_file_diffs(&[(
+                    Path::new(COMPOSE_PATH),
+                    compose.to_canonical_yaml()?,
+                )]);
+            }
             compose.save("docker-compose.yml")?;
 
             // Start feed generator
-            let docker = DockerService::new("docker-compose.yml");
-            docker
-                .start_services(Some(&[String::from("feed-generator")]))
-                .await?;
+            let docker =
+                DockerService::new("docker-compose.yml").with_force_recreate(args.recreate);
+            docker.start_services(Some(&services)).await?;
+
+            // Publishing points the feed record at the generator, so only
+            // do it once the generator answers
+            info!("Waiting for the feed generator to become healthy...");
+            let checker = crate::health::HealthChecker::for_network(&config.network)
+                .with_endpoints(config.health.clone());
+            wait_until_healthy(&checker, "feed-generator", args.wait_timeout).await?;
 
             // Publish feed
-            let feed_gen = FeedGenerator::new(&config.network.domain, &args.publisher_did);
+            let feed_gen = FeedGenerator::new(&config.network, &args.publisher_did);
             let response = feed_gen.publish_feed().await?;
 
             println!("{}", "Feed generator deployed successfully!".green());
@@ -210,35 +1042,72 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
         }
 
         Commands::DeployOzone(args) => {
-            let config = Config::load(config_path)?;
+            let mut config = Config::load_with_env(config_path)?;
+            config.validate()?;
             info!("Deploying Ozone service...");
 
-            // Parse admin DIDs
-            let admin_dids: Vec<String> = args
-                .admin_dids
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect();
+            // Flags take precedence and are remembered for future deploys
+            let flags_given = args.server_did.is_some() || args.admin_dids.is_some();
+            if let Some(server_did) = args.server_did {
+                config.ozone.server_did = Some(server_did);
+            }
+            if let Some(admin_dids) = args.admin_dids {
+                config.ozone.admin_dids = admin_dids
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+            }
+
+            let server_did = config.ozone.server_did.clone().ok_or_else(|| {
+                Error::Config(
+                    "No Ozone server DID: pass --server-did or set ozone.server_did in config"
+                        .into(),
+                )
+            })?;
+            let admin_dids = config.ozone.admin_dids.clone();
 
             // Add Ozone to compose
             let mut compose = ComposeConfig::load("docker-compose.yml")?;
-            compose.add_ozone(&args.server_did, &admin_dids);
+            compose.add_ozone(&server_did, &admin_dids);
+            finalize_compose(&mut compose, &config, deployed_db_image().as_deref())?;
+            if args.diff {
+                return print_file_diffs(&[
+                    (Path::new(COMPOSE_PATH), compose.to_canonical_yaml()?),
+                    (Path::new(CADDYFILE_PATH), caddy_config(&config).generate()),
+                ]);
+            }
+
+            if flags_given {
+                // Save over the file as written so env overrides don't get persisted
+                let mut saved = Config::load(config_path)?;
+                saved.ozone = config.ozone.clone();
+                saved.save(config_path)?;
+            }
             compose.save("docker-compose.yml")?;
 
             // Update Caddy configuration
-            let caddy = CaddyConfig::new(&config.network.domain);
+            let caddy = caddy_config(&config);
             caddy.save("config/caddy/Caddyfile")?;
 
+            if args.recreate {
+                info!("Recreating the ozone container...");
+                DockerService::new("docker-compose.yml")
+                    .with_force_recreate(true)
+                    .start_services(Some(&[String::from("ozone")]))
+                    .await?;
+            }
+
             println!("{}", "Ozone service deployed successfully!".green());
-            println!("Server DID: {}", args.server_did);
-            println!("Admin DIDs: {}", args.admin_dids);
+            println!("Server DID: {}", server_did);
+            println!("Admin DIDs: {}", admin_dids.join(","));
             println!("\nNext step: Configure admin using:");
             println!("  atc configure-ozone --handle <handle> --plc-sign-token <token>");
             Ok(())
         }
 
         Commands::ConfigureOzone(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
             info!("Configuring Ozone admin settings...");
 
             // Create Ozone client with base URL
@@ -248,70 +1117,275 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
             // Use provided Ozone URL or construct default
             let ozone_url = args.ozone_url.unwrap_or_else(|| base_url.clone());
 
-            // Update DID doc with the PLC sign token
-            let response = ozone
-                .update_did_doc(&args.plc_sign_token, &args.handle, &ozone_url)
-                .await?;
+            if args.handles.len() != args.plc_sign_tokens.len() {
+                return Err(Error::Config(format!(
+                    "Got {} handles but {} PLC sign tokens; pass one token per admin",
+                    args.handles.len(),
+                    args.plc_sign_tokens.len()
+                )));
+            }
+            let admins: Vec<OzoneAdmin> = args
+                .handles
+                .into_iter()
+                .zip(args.plc_sign_tokens)
+                .map(|(handle, plc_sign_token)| OzoneAdmin {
+                    handle,
+                    plc_sign_token,
+                })
+                .collect();
+
+            // Update each admin's DID doc with their PLC sign token
+            let results = ozone
+                .configure_admins(&admins, &ozone_url, |result| match &result.outcome {
+                    Ok(response) => println!(
+                        "{} {} ({}, updated: {})",
+                        "✓".green(),
+                        result.handle,
+                        response.did,
+                        response.updated
+                    ),
+                    Err(e) => println!("{} {}: {}", "✗".red(), result.handle, e),
+                })
+                .await;
+
+            let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+            println!("\nConfigured {}, failed {}", results.len() - failed, failed);
+            if failed > 0 {
+                return Err(Error::Api(format!(
+                    "{} of {} Ozone admins could not be configured",
+                    failed,
+                    results.len()
+                )));
+            }
 
-            println!("{}", "Ozone admin configured successfully!".green());
-            println!("DID: {}", response.did);
-            println!("Updated: {}", response.updated);
             println!("\nYou can now sign in to Ozone at:");
             println!("https://ozone.{}", config.network.domain);
             Ok(())
         }
 
         Commands::Status(args) => {
-            let _config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
             info!("Getting service status...");
+            verify_pds_certificate(&config, args.strict_host_key, args.accept_new_cert).await?;
 
             let docker = DockerService::new("docker-compose.yml");
             let status_manager = crate::status::StatusManager::new(docker);
 
             let system_status = status_manager.get_status(args.verbose).await?;
-            status_manager.print_status(&system_status, args.verbose);
+            match args.format {
+                OutputFormat::Text => {
+                    status_manager.print_status(&system_status, args.verbose);
+                    warn_about_orphans().await;
+                }
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&system_status)?);
+                }
+            }
+
+            if args.fail_on_down {
+                let expected: Vec<String> = ComposeConfig::load("docker-compose.yml")?
+                    .services
+                    .into_keys()
+                    .collect();
+                let stopped = system_status.stopped_services(&expected);
+                if !stopped.is_empty() {
+                    return Err(Error::Docker(format!(
+                        "Services not running: {}",
+                        stopped.join(", ")
+                    )));
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Stats(args) => {
+            let stats = DockerService::new("docker-compose.yml").stats().await?;
 
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            if stats.is_empty() {
+                println!("{}", "No containers are running.".yellow());
+                return Ok(());
+            }
+
+            println!(
+                "{:<16} {:>8} {:>24} {:>8}",
+                "SERVICE".bold(),
+                "CPU %".bold(),
+                "MEMORY".bold(),
+                "MEM %".bold()
+            );
+            for usage in &stats {
+                println!(
+                    "{:<16} {:>7.2}% {:>24} {:>7.2}%",
+                    usage.service,
+                    usage.cpu_percent,
+                    format!("{} / {}", usage.memory_usage, usage.memory_limit),
+                    usage.memory_percent
+                );
+            }
             Ok(())
         }
 
+        Commands::Logs(args) => {
+            let style = LogStyle {
+                prefix: !args.no_prefix,
+                color: !args.no_color && std::io::stdout().is_terminal(),
+            };
+            let window = LogWindow::parse(args.since.as_deref(), args.until.as_deref())?;
+            let tail = crate::logs::effective_tail(args.tail, args.follow, &window);
+            DockerService::new("docker-compose.yml")
+                .with_log_style(style)
+                .with_log_window(window)
+                .logs(args.service.as_deref(), args.follow, tail)
+                .await
+        }
+
         Commands::Health(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
             info!("Checking service health...");
+            verify_pds_certificate(&config, args.strict_host_key, args.accept_new_cert).await?;
+
+            let checker = crate::health::HealthChecker::for_network_with_options(
+                &config.network,
+                std::time::Duration::from_secs(args.timeout),
+                args.retries,
+            )
+            .with_endpoints(config.health.clone());
+
+            let services = args.services.unwrap_or_else(default_health_services);
+            if args.watch {
+                let mut monitor = Monitor::new(checker, services)
+                    .with_interval(std::time::Duration::from_secs(args.interval))
+                    .with_notifier(Notifier::from_config(&config.notifications));
+                let format = args.format;
+                let verbose = args.verbose;
+                return monitor
+                    .watch(
+                        |status| match format {
+                            OutputFormat::Text => print_health_status(status, verbose),
+                            OutputFormat::Json => match serde_json::to_string(status) {
+                                Ok(line) => println!("{}", line),
+                                Err(e) => warn!("Failed to serialize health status: {}", e),
+                            },
+                        },
+                        tokio::signal::ctrl_c(),
+                    )
+                    .await;
+            }
+            let statuses = checker.check_all(&services).await?;
 
-            let checker = crate::health::HealthChecker::new(&config.network.domain);
-
-            let services = args.services.unwrap_or_else(|| {
-                vec![
-                    "pds".to_string(),
-                    "plc".to_string(),
-                    "bgs".to_string(),
-                    "appview".to_string(),
-                    "social-app".to_string(),
-                    "ozone".to_string(),
-                    "feed-generator".to_string(),
-                    "jetstream".to_string(),
-                ]
-            });
+            match args.format {
+                OutputFormat::Text => {
+                    for status in &statuses {
+                        print_health_status(status, args.verbose);
+                    }
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+            }
+
+            health_outcome(&statuses)
+        }
+
+        Commands::WaitReady(args) => {
+            let config = Config::load_with_env(config_path)?;
+            info!("Waiting for services to become healthy...");
+
+            let checker = crate::health::HealthChecker::for_network(&config.network)
+                .with_endpoints(config.health.clone());
+            let services = args.services.unwrap_or_else(default_health_services);
 
             for service in services {
-                let status = checker.check_service(&service).await?;
-                print_health_status(&status, args.verbose);
+                wait_until_healthy(&checker, &service, args.timeout).await?;
+                println!("{} {}", "✓".green(), service.bold());
             }
 
+            println!("{}", "All services are ready!".green());
             Ok(())
         }
 
+        Commands::Monitor(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let services = args.services.unwrap_or_else(default_health_services);
+            info!(
+                "Monitoring {} (alert after {}s unhealthy)...",
+                services.join(", "),
+                args.threshold
+            );
+
+            let checker = crate::health::HealthChecker::for_network(&config.network)
+                .with_endpoints(config.health.clone());
+            let mut monitor = Monitor::new(checker, services)
+                .with_interval(std::time::Duration::from_secs(args.interval))
+                .with_threshold(std::time::Duration::from_secs(args.threshold))
+                .with_webhook(args.webhook)
+                .with_notifier(Notifier::from_config(&config.notifications));
+
+            monitor
+                .run(|status| print_health_status(status, false))
+                .await
+        }
+
+        Commands::Serve(args) => {
+            let config = Config::load_with_env(config_path)?;
+            let checker = crate::health::HealthChecker::for_network(&config.network)
+                .with_endpoints(config.health.clone());
+            let services = args.services.unwrap_or_else(default_health_services);
+
+            MetricsServer::bind(args.listen, checker, services)
+                .await?
+                .run()
+                .await
+        }
+
         Commands::DeployJetstream(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
+            let reconnect_delay = args
+                .reconnect_delay
+                .unwrap_or(DEFAULT_JETSTREAM_RECONNECT_DELAY);
+
+            if args.check_only {
+                let diffs = check_jetstream_deploy(
+                    Path::new("docker-compose.yml"),
+                    &config,
+                    reconnect_delay,
+                )?;
+                if diffs.is_empty() {
+                    println!("docker-compose.yml already has this Jetstream configuration");
+                }
+                for diff in &diffs {
+                    println!("{}:", diff.service.bold());
+                    for line in &diff.removed {
+                        println!("{}", format!("  - {}", line).red());
+                    }
+                    for line in &diff.added {
+                        println!("{}", format!("  + {}", line).green());
+                    }
+                }
+                println!(
+                    "Jetstream endpoint: {}",
+                    crate::jetstream::JetstreamClient::new(&config.network.domain).endpoint()
+                );
+                println!(
+                    "{}",
+                    "Check passed; nothing was written or started.".green()
+                );
+                return Ok(());
+            }
+
             info!("Deploying Jetstream service...");
 
             // Update docker-compose with Jetstream service
             let mut compose = ComposeConfig::load("docker-compose.yml")?;
-            compose.add_jetstream(args.reconnect_delay.unwrap_or(200));
+            compose.add_jetstream(reconnect_delay);
+            finalize_compose(&mut compose, &config, deployed_db_image().as_deref())?;
             compose.save("docker-compose.yml")?;
 
             // Start Jetstream service
-            let docker = DockerService::new("docker-compose.yml");
+            let docker =
+                DockerService::new("docker-compose.yml").with_force_recreate(args.recreate);
             docker
                 .start_services(Some(&[String::from("jetstream")]))
                 .await?;
@@ -325,13 +1399,86 @@ pub async fn handle_command(cmd: Commands, config_path: &Path) -> Result<()> {
         }
 
         Commands::Subscribe(args) => {
-            let config = Config::load(config_path)?;
+            let config = Config::load_with_env(config_path)?;
             info!("Subscribing to Jetstream collections...");
 
             let client = crate::jetstream::JetstreamClient::new(&config.network.domain);
-            client.subscribe(&args.collections).await?;
+            let mut subscription = if args.resume {
+                let saved = JetstreamConfig::load(JETSTREAM_CONFIG_PATH)?;
+                saved.check_endpoint(&client.endpoint())?;
+                saved
+            } else {
+                let collections = if args.collections.is_empty() {
+                    crate::jetstream::standard_collections()
+                } else {
+                    args.collections
+                };
+                let subscription = JetstreamConfig {
+                    collections,
+                    wanted_dids: args.dids,
+                    cursor: args.cursor,
+                    subscription_endpoint: client.endpoint(),
+                    reconnect_delay: DEFAULT_JETSTREAM_RECONNECT_DELAY,
+                };
+                subscription.validate()?;
+                subscription
+            };
+
+            if args.save {
+                subscription.save(JETSTREAM_CONFIG_PATH)?;
+                info!("Saved subscription to {}", JETSTREAM_CONFIG_PATH);
+            }
+
+            // An explicit --cursor wins over where the last run left off
+            if let Some(cursor) = args.cursor.or(load_cursor(JETSTREAM_CURSOR_PATH)?) {
+                info!("Replaying from cursor {}", cursor);
+                subscription.cursor = Some(cursor);
+            }
+
+            let end = client
+                .subscribe(
+                    &subscription,
+                    args.output.as_deref(),
+                    args.split_dir.as_deref(),
+                    args.max_events,
+                )
+                .await?;
+
+            let reason = match end {
+                StreamEnd::MaxEvents(_) => "reached --max-events",
+                StreamEnd::Interrupted(_) => "interrupted",
+            };
+            println!(
+                "{}",
+                format!(
+                    "Subscription ended after {} events ({})",
+                    end.events(),
+                    reason
+                )
+                .green()
+            );
+            Ok(())
+        }
+
+        Commands::SelfUpdate(args) => {
+            info!("Checking for atc updates...");
+
+            let updater = SelfUpdater::new()?;
+            let current = SelfUpdater::current_version();
+
+            let Some((latest, release)) = updater.check().await? else {
+                println!("{}", format!("atc {} is up to date", current).green());
+                return Ok(());
+            };
+
+            println!("New version available: {} (current: {})", latest, current);
+            if args.check {
+                return Ok(());
+            }
 
-            println!("{}", "Subscribed to collections successfully!".green());
+            let exe = std::env::current_exe()?;
+            updater.install(&release, &exe).await?;
+            println!("{}", format!("Updated atc to {}!", latest).green());
             Ok(())
         }
     }
@@ -348,6 +1495,607 @@ fn create_env_vars(config: &Config) -> std::collections::HashMap<String, String>
     vars
 }
 
+/// What `render-compose` prints: `docker compose config` output, or atc's
+/// own substitution when Compose isn't installed.
+async fn render_compose(
+    config: &Config,
+    compose_path: &str,
+    docker: DockerService,
+) -> Result<String> {
+    let mut env_vars = create_env_vars(config);
+    if Path::new("config/secrets.toml").exists() {
+        env_vars.extend(Secrets::load("config/secrets.toml")?.as_env_vars());
+    }
+
+    match docker
+        .with_env_vars(env_vars.clone())
+        .render_config()
+        .await?
+    {
+        Some(rendered) => Ok(rendered),
+        None => {
+            warn!("Docker Compose not found, rendering with atc's own substitution");
+            ComposeConfig::load(compose_path)?.render(&env_vars)
+        }
+    }
+}
+
+/// Applies config-driven adjustments that span every generated service.
+/// `deployed_db` is the db image in the compose file on disk, if any.
+fn finalize_compose(
+    compose: &mut ComposeConfig,
+    config: &Config,
+    deployed_db: Option<&str>,
+) -> Result<()> {
+    compose.apply_pds_limits(config.pds.blob_upload_limit_bytes()?);
+    if config.network.ipv6 {
+        compose.apply_dual_stack(&config.network.bind_address);
+    }
+    compose.apply_cert_dir(&config.storage.cert_dir);
+    compose.apply_db_credentials(&config.database.credentials());
+    if config.containers.harden {
+        compose.apply_hardening();
+    }
+    if let Some(logging) = config.containers.logging.compose_logging() {
+        compose.apply_logging(&logging);
+    }
+    for (service, limit) in &config.containers.resources {
+        compose.apply_resource_limits(service, limit.cpus, limit.memory.as_deref());
+    }
+    if let Some(user) = &config.containers.user {
+        compose.apply_user(user);
+    }
+    if let Some(provider) = config.network.dns_provider {
+        compose.apply_passthrough_env("caddy", provider.env_keys());
+    }
+    compose.apply_images(&config.images);
+    stamp_postgres_image(compose, config, deployed_db, false);
+    compose.apply_health_conditions();
+    Ok(())
+}
+
+/// Sets the db image to `images.db`, or else the configured Postgres tag.
+/// If that changes the major version from `deployed`, the db image in the
+/// compose file on disk, it is only applied with `force`; otherwise
+/// `deployed` is kept. Returns the warning when the major version changes.
+/// Without a deployed image there is no data to protect yet.
+fn stamp_postgres_image(
+    compose: &mut ComposeConfig,
+    config: &Config,
+    deployed: Option<&str>,
+    force: bool,
+) -> Option<String> {
+    let image = config
+        .images
+        .get("db")
+        .cloned()
+        .unwrap_or_else(|| config.database.postgres_image());
+    let warning = deployed.and_then(|deployed| postgres_upgrade_warning(deployed, &image));
+    match (&warning, deployed) {
+        (Some(warning), Some(deployed)) if !force => {
+            warn!(
+                "{}; keeping the current image (start with --force to apply)",
+                warning
+            );
+            compose.set_image("db", deployed);
+            return Some(warning.clone());
+        }
+        (Some(warning), _) => warn!("{}", warning),
+        _ => {}
+    }
+    compose.set_image("db", &image);
+    warning
+}
+
+/// The db image in the compose file on disk, or `None` before `init`.
+fn deployed_db_image() -> Option<String> {
+    let compose = ComposeConfig::load(COMPOSE_PATH).ok()?;
+    compose.services.get("db").map(|db| db.image.clone())
+}
+
+/// Adds Jetstream to an in-memory copy of `compose_path` and validates the
+/// result and its endpoint, returning what would change. Nothing is written.
+fn check_jetstream_deploy(
+    compose_path: &Path,
+    config: &Config,
+    reconnect_delay: u32,
+) -> Result<Vec<ServiceDiff>> {
+    let before = ComposeConfig::load(compose_path)?;
+    let mut compose = ComposeConfig::load(compose_path)?;
+    compose.add_jetstream(reconnect_delay);
+    let deployed_db = before.services.get("db").map(|db| db.image.as_str());
+    finalize_compose(&mut compose, config, deployed_db)?;
+    compose.validate()?;
+
+    let endpoint = crate::jetstream::JetstreamClient::new(&config.network.domain).endpoint();
+    reqwest::Url::parse(&endpoint)
+        .map_err(|e| Error::Config(format!("Invalid Jetstream endpoint {}: {}", endpoint, e)))?;
+
+    compose.diff_services(&before)
+}
+
+/// The compose file `init` generates for `config`, replacing one whose db
+/// runs `deployed_db`.
+fn init_compose(config: &Config, deployed_db: Option<&str>) -> Result<ComposeConfig> {
+    let mut compose = ComposeConfig::new();
+    compose
+        .add_caddy()
+        .add_pds(&config.network.domain)
+        .add_plc()
+        .add_bgs()
+        .add_appview();
+    finalize_compose(&mut compose, config, deployed_db)?;
+    Ok(compose)
+}
+
+/// Unified diffs of generated `files` against what is on disk. Compose files
+/// on disk are re-serialized first so only content changes show up.
+fn file_diffs(files: &[(&Path, String)]) -> Result<Vec<String>> {
+    let mut diffs = Vec::new();
+    for (path, new) in files {
+        let old = if !path.exists() {
+            String::new()
+        } else if path.extension().is_some_and(|ext| ext == "yml") {
+            match ComposeConfig::load(path) {
+                Ok(compose) => compose.to_canonical_yaml()?,
+                Err(_) => fs::read_to_string(path)?,
+            }
+        } else {
+            fs::read_to_string(path)?
+        };
+        diffs.extend(crate::diff::unified_diff(path, &old, new));
+    }
+    Ok(diffs)
+}
+
+fn print_file_diffs(files: &[(&Path, String)]) -> Result<()> {
+    let diffs = file_diffs(files)?;
+    if diffs.is_empty() {
+        println!("No changes");
+    }
+    for diff in diffs {
+        for line in diff.lines() {
+            if line.starts_with('+') && !line.starts_with("+++") {
+                println!("{}", line.green());
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                println!("{}", line.red());
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+    println!("{}", "Diff only; nothing was written.".dimmed());
+    Ok(())
+}
+
+/// Loads back the files `init` wrote and runs every validator over them,
+/// naming the file at fault.
+fn verify_generated(
+    config_path: impl AsRef<Path>,
+    compose_path: &Path,
+    caddyfile_path: &Path,
+) -> Result<()> {
+    let config_path = config_path.as_ref();
+    let invalid =
+        |path: &Path, e: Error| Error::Config(format!("{} is invalid: {}", path.display(), e));
+
+    // Raw load: this checks the file init wrote, not the env on this run
+    let config = Config::load(config_path)
+        .and_then(|config| config.validate().map(|()| config))
+        .map_err(|e| invalid(config_path, e))?;
+
+    let compose = ComposeConfig::load(compose_path).map_err(|e| invalid(compose_path, e))?;
+    compose
+        .validate()
+        .and_then(|()| compose.validate_container_names())
+        .and_then(|()| compose.validate_host_ports())
+        .and_then(|()| compose.verify_cert_mount(&config.storage.cert_dir))
+        .and_then(|()| match compose.dependency_cycle() {
+            Some(cycle) => Err(Error::Config(format!(
+                "Dependency cycle: {}",
+                cycle.join(" -> ")
+            ))),
+            None => Ok(()),
+        })
+        .map_err(|e| invalid(compose_path, e))?;
+
+    fs::read_to_string(caddyfile_path)
+        .map_err(Error::from)
+        .and_then(|content| validate_caddyfile(&content))
+        .map_err(|e| invalid(caddyfile_path, e))
+}
+
+/// Persists secrets added since the secrets file was written, before
+/// containers are created with them.
+fn upgrade_secrets(config: &Config) -> Result<()> {
+    if Path::new("config/secrets.toml").exists() {
+        Secrets::upgrade("config/secrets.toml", ENV_FILE_PATH, &config.network.domain)?;
+    }
+    Ok(())
+}
+
+/// DNS provider credentials from `--api-token`, `--api-token-file` or
+/// `ATC_DNS_API_TOKEN`, in that order.
+fn dns_api_token(token: Option<String>, file: Option<&Path>) -> Result<String> {
+    if let Some(token) = token {
+        return Ok(token);
+    }
+    if let Some(file) = file {
+        return Ok(fs::read_to_string(file)?.trim().to_string());
+    }
+    std::env::var(DNS_API_TOKEN_ENV)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "--dns-provider needs credentials: pass --api-token-file or set {}",
+                DNS_API_TOKEN_ENV
+            ))
+        })
+}
+
+fn caddy_config(config: &Config) -> CaddyConfig {
+    let mut caddy = CaddyConfig::new(&config.network.domain)
+        .with_test_endpoint(config.network.test_endpoint_enabled());
+    if let Some(provider) = config.network.dns_provider {
+        caddy = caddy.with_dns_challenge(provider);
+    }
+    if config.network.ipv6 {
+        caddy.with_dual_stack(&config.network.bind_address)
+    } else {
+        caddy
+    }
+}
+
+fn default_health_services() -> Vec<String> {
+    [
+        "pds",
+        "plc",
+        "bgs",
+        "appview",
+        "social-app",
+        "ozone",
+        "feed-generator",
+        "jetstream",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+/// Renders a service's resolved environment as `KEY=value` lines, masking
+/// any value derived from a secret unless `reveal` is set.
+fn env_lines(
+    compose: &ComposeConfig,
+    service: &str,
+    env: &std::collections::HashMap<String, String>,
+    secret_names: &[String],
+    reveal: bool,
+) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for var in compose.resolve_environment(service, env)? {
+        for name in &var.missing {
+            warn!("{} references unset variable {}", var.key, name);
+        }
+
+        let is_secret = secret_names.contains(&var.key)
+            || var
+                .references
+                .iter()
+                .any(|name| secret_names.contains(name));
+        let value = if is_secret && !reveal && !var.value.is_empty() {
+            "********".to_string()
+        } else {
+            var.value
+        };
+        lines.push(format!("{}={}", var.key, value));
+    }
+    Ok(lines)
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CreatedAccount {
+    did: String,
+    handle: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_password: Option<String>,
+}
+
+/// Polls `service` until it reports healthy, failing after `timeout_secs`.
+async fn wait_until_healthy(
+    checker: &crate::health::HealthChecker,
+    service: &str,
+    timeout_secs: u64,
+) -> Result<()> {
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    let ready = poll_until(timeout, Backoff::default(), || async {
+        let status = checker.check_service(service).await?;
+        Ok(status.status == crate::health::HealthState::Healthy)
+    })
+    .await?;
+
+    if !ready {
+        return Err(Error::Network(format!(
+            "Timed out after {}s waiting for {} to become healthy",
+            timeout_secs, service
+        )));
+    }
+    Ok(())
+}
+
+/// `checker` is only consulted with `--wait-healthy`.
+async fn create_account(
+    client: &PdsClient,
+    checker: &crate::health::HealthChecker,
+    args: CreateAccountArgs,
+) -> Result<CreatedAccount> {
+    if args.wait_healthy {
+        info!("Waiting for the PDS to become healthy...");
+        wait_until_healthy(checker, "pds", args.wait_timeout).await?;
+    }
+    if !args.skip_domain_check {
+        client.check_handle_domain(&args.handle).await?;
+    }
+
+    let account = client
+        .create_account(args.handle, args.email, args.password, args.invite_code)
+        .await?;
+
+    let app_password = match (&args.app_password, &account.access_jwt) {
+        (Some(name), Some(token)) => Some(client.create_app_password(token, name).await?.password),
+        (Some(_), None) => {
+            return Err(Error::Api(
+                "Account created, but the PDS returned no session to mint an app password".into(),
+            ))
+        }
+        (None, _) => None,
+    };
+
+    Ok(CreatedAccount {
+        did: account.did,
+        handle: account.handle,
+        app_password,
+    })
+}
+
+/// Trust-on-first-use check that the PDS still presents the same certificate.
+async fn verify_pds_certificate(config: &Config, strict: bool, accept_new: bool) -> Result<()> {
+    if !config.network.use_tls {
+        return Ok(());
+    }
+    let host = format!("pds.{}", config.network.domain);
+    crate::known_hosts::verify_host(
+        &crate::known_hosts::TlsCertificateSource,
+        &host,
+        crate::known_hosts::KNOWN_HOSTS_PATH,
+        strict,
+        accept_new,
+    )
+    .await
+}
+
+/// Warns about project containers whose service left the compose file.
+async fn warn_about_orphans() {
+    let project = std::env::current_dir()
+        .map(|dir| default_project_name(&dir))
+        .unwrap_or_default();
+    let (containers, compose) = match (
+        DockerService::new("docker-compose.yml")
+            .project_services(&project)
+            .await,
+        ComposeConfig::load("docker-compose.yml"),
+    ) {
+        (Ok(containers), Ok(compose)) => (containers, compose),
+        _ => return,
+    };
+
+    let orphans = find_orphans(&containers, compose.services.keys());
+    if !orphans.is_empty() {
+        warn!(
+            "Containers for services not in docker-compose.yml: {} (stop or start with --remove-orphans to clean up)",
+            orphans.join(", ")
+        );
+    }
+}
+
+/// Tally of doctor checks; each result is printed as it is recorded.
+#[derive(Default)]
+struct DoctorReport {
+    failures: Vec<String>,
+}
+
+impl DoctorReport {
+    fn phase(&self, name: &str) {
+        println!("{}", name.bold());
+    }
+
+    /// Prints a caveat that doesn't count as a failure.
+    fn note(&self, message: &str) {
+        println!("  {} {}", "!".yellow(), message);
+    }
+
+    fn record(&mut self, check: &str, result: Result<()>) {
+        match result {
+            Ok(()) => println!("  {} {}", "✓".green(), check),
+            Err(e) => {
+                println!("  {} {}: {}", "✗".red(), check, e);
+                self.failures.push(check.to_string());
+            }
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.failures.is_empty() {
+            println!("{}", "No problems found!".green());
+            return Ok(());
+        }
+        Err(Error::Config(format!(
+            "Doctor found problems: {}",
+            self.failures.join("; ")
+        )))
+    }
+}
+
+/// Runs every diagnostic, continuing past failures so all problems are shown.
+/// With `only` non-empty, just the listed phases run.
+async fn run_doctor(
+    config: &Config,
+    docker: &impl DockerServiceTrait,
+    resolver: &impl Resolver,
+    only: &[DoctorPhase],
+) -> Result<()> {
+    let mut report = DoctorReport::default();
+    let runs = |phase| only.is_empty() || only.contains(&phase);
+
+    if runs(DoctorPhase::Compose) {
+        report.phase("Compose");
+        report.record("configuration is valid", config.validate());
+        report.record(
+            "docker-compose.yml exists",
+            if Path::new("docker-compose.yml").exists() {
+                Ok(())
+            } else {
+                Err(Error::Config("not found; run init first".into()))
+            },
+        );
+    }
+
+    if runs(DoctorPhase::Certs) {
+        report.phase("Certs");
+        report.record(
+            "Caddy mounts the configured cert directory",
+            ComposeConfig::load("docker-compose.yml")
+                .and_then(|compose| compose.verify_cert_mount(&config.storage.cert_dir)),
+        );
+    }
+
+    if runs(DoctorPhase::Dns) {
+        report.phase("DNS");
+        let domain = &config.network.domain;
+        report.record(
+            "domain and subdomains resolve",
+            unresolved_names(resolver, domain, config.dns.options())
+                .await
+                .and_then(|unresolved| {
+                    if unresolved.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(Error::Network(format!("{} missing", unresolved.join(", "))))
+                    }
+                }),
+        );
+    }
+
+    if runs(DoctorPhase::Docker) {
+        doctor_docker(config, docker, &mut report).await;
+    }
+
+    if runs(DoctorPhase::Health) {
+        report.phase("Health");
+        let checker = crate::health::HealthChecker::for_network(&config.network)
+            .with_endpoints(config.health.clone());
+        let statuses = checker.check_all(&default_health_services()).await;
+        report.record(
+            "services are healthy",
+            statuses.and_then(|statuses| health_outcome(&statuses)),
+        );
+    }
+
+    report.finish()
+}
+
+/// The Docker phase of `run_doctor`: installed tools, daemon caveats and
+/// whether secrets reached the pds container.
+async fn doctor_docker(
+    config: &Config,
+    docker: &impl DockerServiceTrait,
+    report: &mut DoctorReport,
+) {
+    report.phase("Docker");
+    let environment = docker.check_dependencies().await;
+    let ports = &config.network.ports;
+    let warnings = environment
+        .as_ref()
+        .map(|environment| environment.warnings(&[ports.http, ports.https]))
+        .unwrap_or_default();
+    report.record(
+        "docker and Docker Compose are installed",
+        environment.map(|_| ()),
+    );
+    for warning in &warnings {
+        report.note(warning);
+    }
+    report.record(
+        "pds received its secrets",
+        verify_secrets_injected(docker, "pds").await,
+    );
+}
+
+/// Checks that every generated secret is set in `service`'s running
+/// container, e.g. to catch secrets that never made it past compose.
+async fn verify_secrets_injected(docker: &impl DockerServiceTrait, service: &str) -> Result<()> {
+    let mut keys: Vec<String> = Secrets::load("config/secrets.toml")?
+        .as_env_vars()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+
+    // Only expect the secrets the compose file hands to this service by name
+    let environment = ComposeConfig::load(COMPOSE_PATH)
+        .ok()
+        .and_then(|compose| compose.services.get(service)?.environment.clone());
+    if let Some(environment) = environment {
+        keys.retain(|key| environment.contains_key(key));
+    }
+
+    let missing = docker.verify_env(service, &keys).await?;
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Docker(format!("missing {}", missing.join(", "))))
+    }
+}
+
+fn print_teardown_plan(plan: &TeardownPlan) {
+    if !plan.persisted.is_empty() {
+        println!("{}", "Volumes that will persist:".bold());
+        for volume in &plan.persisted {
+            println!("  {} {}", "✓".green(), volume);
+        }
+    }
+
+    if !plan.destroyed.is_empty() {
+        println!("{}", "Volumes that will be destroyed:".bold());
+        for volume in &plan.destroyed {
+            println!("  {} {}", "✗".red(), volume.red());
+        }
+    }
+}
+
+/// Fails with `Error::Unhealthy` if any service is unhealthy, otherwise
+/// with `Error::Degraded` if any is degraded, so each gets its own exit code.
+fn health_outcome(statuses: &[crate::health::HealthStatus]) -> Result<()> {
+    use crate::health::HealthState;
+
+    let in_state = |state: HealthState| {
+        statuses
+            .iter()
+            .filter(|s| s.status == state)
+            .map(|s| s.service.as_str())
+            .collect::<Vec<_>>()
+    };
+
+    let unhealthy = in_state(HealthState::Unhealthy);
+    if !unhealthy.is_empty() {
+        return Err(Error::Unhealthy(unhealthy.join(", ")));
+    }
+    let degraded = in_state(HealthState::Degraded);
+    if !degraded.is_empty() {
+        return Err(Error::Degraded(degraded.join(", ")));
+    }
+    Ok(())
+}
+
 fn print_health_status(status: &crate::health::HealthStatus, verbose: bool) {
     use crate::health::HealthState;
 
@@ -444,6 +2192,7 @@ mod tests {
                     cert_email: "test@example.com".to_string(),
                     ..Default::default()
                 },
+                ..Default::default()
             };
             config.save(&self.config_path)?;
 
@@ -468,8 +2217,20 @@ mod tests {
             Ok(())
         }
 
+        /// Sets up a plain-HTTP deployment on 127.0.0.1 whose Caddy port is
+        /// `mock_server`'s, so clients built from the config reach the mock.
+        fn setup_mock_config(&self, mock_server: &MockServer) -> Result<()> {
+            self.setup_initial_config("127.0.0.1")?;
+
+            let mut config = Config::load(&self.config_path)?;
+            config.network.use_tls = false;
+            config.network.ports.http = mock_server.address().port();
+            config.save(&self.config_path)
+        }
+
         fn verify_files_exist(&self) -> bool {
             self.get_path("config/secrets.toml").exists() &&
+            self.get_path(".env").exists() &&
             self.get_path("docker-compose.yml").exists() &&
             self.get_path("config/caddy/Caddyfile").exists() &&
             self.config_path.exists()
@@ -483,6 +2244,8 @@ mod tests {
         let cmd = Commands::Init(InitArgs {
             domain: "test.com".to_string(),
             cert_email: "admin@test.com".to_string(),
+            pds_version: None,
+            diff: false,
         });
 
         handle_command(cmd, &ctx.config_path).await?;
@@ -496,6 +2259,26 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_init_pins_pds_version() -> Result<()> {
+        let ctx = TestContext::new();
+
+        let cmd = Commands::Init(InitArgs {
+            domain: "test.com".to_string(),
+            cert_email: "admin@test.com".to_string(),
+            pds_version: Some("v0.4.0".to_string()),
+            diff: false,
+        });
+        handle_command(cmd, &ctx.config_path).await?;
+
+        let config = Config::load(&ctx.config_path)?;
+        assert_eq!(config.images["pds"], "ghcr.io/bluesky-social/pds:v0.4.0");
+        let compose = ComposeConfig::load(ctx.get_path("docker-compose.yml").path())?;
+        assert_eq!(compose.services["pds"].image, config.images["pds"]);
+
+        Ok(())
+    }
+
     #[test_case("test.com", "admin@test.com" ; "valid domain and email")]
     #[test_case("localhost", "admin@localhost" ; "localhost config")]
     fn test_config_init_variations(domain: &str, email: &str) -> Result<()> {
@@ -530,7 +2313,12 @@ mod tests {
 
         let cmd = Commands::Check(CheckArgs {
             no_dns: true,
+            wait_dns: None,
+            dns_timeout: None,
+            dns_retries: None,
             no_docker: true,
+            health: false,
+            strict: false,
         });
 
         handle_command(cmd, &ctx.config_path).await?;
@@ -548,7 +2336,12 @@ mod tests {
 
         let cmd = Commands::Check(CheckArgs {
             no_dns: false,
+            wait_dns: None,
+            dns_timeout: None,
+            dns_retries: None,
             no_docker: true,
+            health: false,
+            strict: false,
         });
 
         match handle_command(cmd, &ctx.config_path).await {
@@ -558,6 +2351,203 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_check_command_health_fails_when_unhealthy() -> Result<()> {
+        let ctx = TestContext::new();
+        // `.invalid` never resolves, so every probe comes back unhealthy
+        ctx.setup_initial_config("atc.invalid")?;
+
+        let cmd = Commands::Check(CheckArgs {
+            no_dns: true,
+            wait_dns: None,
+            dns_timeout: None,
+            dns_retries: None,
+            no_docker: true,
+            health: true,
+            strict: false,
+        });
+
+        match handle_command(cmd, &ctx.config_path).await {
+            Err(Error::Network(msg)) => assert!(msg.contains("pds")),
+            other => panic!("expected unhealthy check to fail, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_dir() -> Result<()> {
+        let elsewhere = assert_fs::TempDir::new().unwrap();
+        let project = assert_fs::TempDir::new().unwrap();
+        let _guard = DirectoryGuard::new(elsewhere.path());
+
+        assert!(matches!(
+            enter_project_dir(Some(&project.path().join("missing"))),
+            Err(Error::Config(_))
+        ));
+
+        enter_project_dir(Some(project.path()))?;
+        let cmd = Commands::Init(InitArgs {
+            domain: "test.com".to_string(),
+            cert_email: "admin@test.com".to_string(),
+            pds_version: None,
+            diff: false,
+        });
+        handle_command(cmd, Path::new("config.toml")).await?;
+
+        project
+            .child("config.toml")
+            .assert(predicates::path::exists());
+        project
+            .child("docker-compose.yml")
+            .assert(predicates::path::exists());
+        project
+            .child("config/secrets.toml")
+            .assert(predicates::path::exists());
+        elsewhere
+            .child("config.toml")
+            .assert(predicates::path::missing());
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_lines_mask_secrets() -> Result<()> {
+        let mut compose = ComposeConfig::new();
+        compose.add_pds("test.com");
+
+        let secrets = Secrets::generate();
+        let env: std::collections::HashMap<_, _> = secrets.as_env_vars().into_iter().collect();
+        let secret_names: Vec<String> = env.keys().cloned().collect();
+
+        let lines = env_lines(&compose, "pds", &env, &secret_names, false)?;
+        assert!(lines.contains(&"PDS_HOSTNAME=pds.test.com".to_string()));
+        assert!(lines.contains(&"PDS_JWT_SECRET=********".to_string()));
+
+        let revealed = env_lines(&compose, "pds", &env, &secret_names, true)?;
+        assert!(revealed.contains(&format!("PDS_JWT_SECRET={}", secrets.pds_jwt_secret)));
+        Ok(())
+    }
+
+    /// Answers every DNS query the same way.
+    struct StaticResolver(bool);
+
+    #[async_trait::async_trait]
+    impl Resolver for StaticResolver {
+        async fn query(&self, _name: &str, _record: crate::dns::RecordType) -> Result<bool> {
+            Ok(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_doctor_only_runs_selected_phases() -> Result<()> {
+        let mut config = Config::default();
+        config.network.domain = "example.com".into();
+
+        // No secrets exist here, so the Docker phase would fail if it ran
+        let docker = crate::docker::mock::MockDockerService::new();
+        run_doctor(&config, &docker, &StaticResolver(true), &[DoctorPhase::Dns]).await?;
+
+        match run_doctor(
+            &config,
+            &docker,
+            &StaticResolver(false),
+            &[DoctorPhase::Dns],
+        )
+        .await
+        {
+            Err(Error::Config(msg)) => assert!(msg.contains("domain and subdomains resolve")),
+            other => panic!("expected doctor to fail, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_doctor_reports_missing_secret() -> Result<()> {
+        let ctx = TestContext::new();
+        ctx.setup_initial_config("test.com")?;
+
+        let docker = crate::docker::mock::MockDockerService::new();
+        docker
+            .set_env_output("pds", "PDS_ADMIN_PASSWORD=x\nPDS_PLC_ROTATION_KEY_K256=y\n")
+            .await;
+        assert!(matches!(
+            verify_secrets_injected(&docker, "pds").await,
+            Err(Error::Docker(msg)) if msg.contains("PDS_JWT_SECRET")
+        ));
+
+        let config = Config::load(&ctx.config_path)?;
+        match run_doctor(
+            &config,
+            &docker,
+            &StaticResolver(true),
+            &[DoctorPhase::Docker],
+        )
+        .await
+        {
+            Err(Error::Config(msg)) => assert!(msg.contains("pds received its secrets")),
+            other => panic!("expected doctor to fail, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_doctor_reports_missing_docker() {
+        let config = Config::default();
+        let docker = crate::docker::mock::MockDockerService::new();
+        docker
+            .set_missing_dependency("docker: command not found")
+            .await;
+
+        match run_doctor(
+            &config,
+            &docker,
+            &StaticResolver(true),
+            &[DoctorPhase::Docker],
+        )
+        .await
+        {
+            Err(Error::Config(msg)) => {
+                assert!(msg.contains("docker and Docker Compose are installed"))
+            }
+            other => panic!("expected doctor to fail, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_compose_command() -> Result<()> {
+        let ctx = TestContext::new();
+        ctx.setup_initial_config("test.com")?;
+
+        let mut compose = ComposeConfig::load(ctx.get_path("docker-compose.yml").path())?;
+        compose.add_feed_generator("did:plc:feed123", FeedSubscription::Bgs);
+        compose.save(ctx.get_path("docker-compose.yml").path())?;
+
+        let config = Config::load(&ctx.config_path)?;
+
+        let runner = crate::docker::mock::MockRunner::without_compose();
+        let docker = DockerService::new(COMPOSE_PATH).with_runner(runner);
+        let rendered = render_compose(&config, COMPOSE_PATH, docker).await?;
+        assert!(rendered.contains("feed-generator.test.com"));
+        assert!(!rendered.contains("${DOMAIN}"));
+
+        let runner = crate::docker::mock::MockRunner::new();
+        runner.push_output(crate::docker::CommandOutput {
+            success: true,
+            stdout: "name: atc\n".to_string(),
+            ..Default::default()
+        });
+        let docker = DockerService::new(COMPOSE_PATH).with_runner(runner.clone());
+        assert_eq!(
+            render_compose(&config, COMPOSE_PATH, docker).await?,
+            "name: atc\n"
+        );
+        let invocation = &runner.invocations()[0];
+        assert_eq!(invocation.args.last().map(String::as_str), Some("config"));
+        assert_eq!(invocation.envs["DOMAIN"], "test.com");
+
+        handle_command(Commands::RenderCompose, &ctx.config_path).await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_start_command() -> Result<()> {
         let ctx = TestContext::new();
@@ -566,6 +2556,11 @@ mod tests {
         let cmd = Commands::Start(StartArgs {
             services: Some(vec!["pds".to_string(), "plc".to_string()]),
             no_deps: true,
+            parallel_pull: false,
+            remove_orphans: false,
+            build: false,
+            no_cache: false,
+            force: false,
         });
 
         match handle_command(cmd, &ctx.config_path).await {
@@ -580,7 +2575,10 @@ mod tests {
         let ctx = TestContext::new();
         ctx.setup_initial_config("test.com")?;
 
-        let cmd = Commands::Stop(StopArgs { clean: true });
+        let cmd = Commands::Stop(StopArgs {
+            clean: true,
+            remove_orphans: false,
+        });
 
         match handle_command(cmd, &ctx.config_path).await {
             Ok(_) => Ok(()),
@@ -594,9 +2592,7 @@ mod tests {
         let mock_server = MockServer::start().await;
         let ctx = TestContext::new();
 
-        let uri = mock_server.uri();
-        let domain = uri.trim_start_matches("http://");
-        ctx.setup_initial_config(domain)?;
+        ctx.setup_mock_config(&mock_server)?;
 
         Mock::given(method("POST"))
             .and(path("/xrpc/com.atproto.server.createAccount"))
@@ -612,20 +2608,178 @@ mod tests {
             handle: "test.example.com".to_string(),
             email: "test@example.com".to_string(),
             password: "password123".to_string(),
+            json: false,
+            app_password: None,
+            skip_domain_check: true,
+            wait_healthy: false,
+            wait_timeout: 120,
+            invite_code: None,
         });
 
         handle_command(cmd, &ctx.config_path).await?;
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_create_account_json() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:testuser123",
+                "handle": "test.example.com",
+                "accessJwt": "access-token"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAppPassword"))
+            .and(header("Authorization", "Bearer access-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "name": "bot",
+                "password": "abcd-efgh-ijkl-mnop"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PdsClient::with_base_url(&mock_server.uri());
+        let checker = crate::health::HealthChecker::with_base_url(&mock_server.uri());
+        let account = create_account(
+            &client,
+            &checker,
+            CreateAccountArgs {
+                handle: "test.example.com".to_string(),
+                email: "test@example.com".to_string(),
+                password: "password123".to_string(),
+                json: true,
+                app_password: Some("bot".to_string()),
+                skip_domain_check: true,
+                wait_healthy: false,
+                wait_timeout: 120,
+                invite_code: None,
+            },
+        )
+        .await?;
+
+        let json: serde_json::Value = serde_json::to_value(&account)?;
+        assert_eq!(json["did"], "did:plc:testuser123");
+        assert_eq!(json["handle"], "test.example.com");
+        assert_eq!(json["app_password"], "abcd-efgh-ijkl-mnop");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_account_waits_for_healthy_pds() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // The PDS is still starting for the first two probes
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:alice",
+                "handle": "alice.example.com"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PdsClient::with_base_url(&mock_server.uri());
+        let checker = crate::health::HealthChecker::with_base_url(&mock_server.uri());
+        let account = create_account(
+            &client,
+            &checker,
+            CreateAccountArgs {
+                handle: "alice.example.com".to_string(),
+                email: "alice@example.com".to_string(),
+                password: "password123".to_string(),
+                json: false,
+                app_password: None,
+                skip_domain_check: true,
+                wait_healthy: true,
+                wait_timeout: 30,
+                invite_code: None,
+            },
+        )
+        .await?;
+
+        assert_eq!(account.did, "did:plc:alice");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_account_rejects_handle_outside_domains() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.server.describeServer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:web:pds.example.com",
+                "availableUserDomains": [".example.com"]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createAccount"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let client = PdsClient::with_base_url(&mock_server.uri());
+        let checker = crate::health::HealthChecker::with_base_url(&mock_server.uri());
+        let result = create_account(
+            &client,
+            &checker,
+            CreateAccountArgs {
+                handle: "alice.other.com".to_string(),
+                email: "alice@other.com".to_string(),
+                password: "password123".to_string(),
+                json: false,
+                app_password: None,
+                skip_domain_check: false,
+                wait_healthy: false,
+                wait_timeout: 120,
+                invite_code: None,
+            },
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Config(msg))
+                if msg.contains("alice.other.com") && msg.contains(".example.com")
+        ));
+        assert!(client
+            .check_handle_domain("alice.example.com")
+            .await
+            .is_ok());
+        assert!(client.check_handle_domain("example.com").await.is_err());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_deploy_feed() -> Result<()> {
         let mock_server = MockServer::start().await;
         let ctx = TestContext::new();
 
-        let uri = mock_server.uri();
-        let domain = uri.trim_start_matches("http://");
-        ctx.setup_initial_config(domain)?;
+        ctx.setup_mock_config(&mock_server)?;
 
         Mock::given(method("POST"))
             .and(path("/scripts/publishFeedGen.ts"))
@@ -638,20 +2792,46 @@ mod tests {
 
         let cmd = Commands::DeployFeed(DeployFeedArgs {
             publisher_did: "did:plc:feed123".to_string(),
+            recreate: false,
+            diff: false,
+            wait_timeout: 120,
         });
 
         handle_command(cmd, &ctx.config_path).await?;
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_invalid_config_fails_before_deploying() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.network.bind_address = String::new();
+        config.save(&config_path)?;
+
+        let cmd = Commands::DeployFeed(DeployFeedArgs {
+            publisher_did: "did:plc:feed123".to_string(),
+            recreate: false,
+            diff: false,
+            wait_timeout: 120,
+        });
+        assert!(matches!(
+            handle_command(cmd, &config_path).await,
+            Err(Error::Config(msg)) if msg.contains("Bind address")
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_deploy_ozone() -> Result<()> {
         let ctx = TestContext::new();
         ctx.setup_initial_config("test.com")?;
 
         let cmd = Commands::DeployOzone(DeployOzoneArgs {
-            server_did: "did:plc:test123".to_string(),
-            admin_dids: "did:plc:admin456".to_string(),
+            server_did: Some("did:plc:test123".to_string()),
+            admin_dids: Some("did:plc:admin456".to_string()),
+            recreate: false,
+            diff: false,
         });
 
         handle_command(cmd, &ctx.config_path).await?;
@@ -670,6 +2850,45 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_deploy_ozone_from_config() -> Result<()> {
+        let ctx = TestContext::new();
+        ctx.setup_initial_config("test.com")?;
+
+        let mut config = Config::load(&ctx.config_path)?;
+        config.ozone.server_did = Some("did:plc:fromconfig".to_string());
+        config.ozone.admin_dids = vec!["did:plc:admin789".to_string()];
+        config.save(&ctx.config_path)?;
+
+        let cmd = Commands::DeployOzone(DeployOzoneArgs {
+            server_did: None,
+            admin_dids: None,
+            recreate: false,
+            diff: false,
+        });
+        handle_command(cmd, &ctx.config_path).await?;
+
+        let compose = ComposeConfig::load(ctx.get_path("docker-compose.yml").path())?;
+        let env = compose.services["ozone"].environment.as_ref().unwrap();
+        assert!(env.contains(&"OZONE_SERVER_DID=did:plc:fromconfig".to_string()));
+        assert!(env.contains(&"OZONE_ADMIN_DIDS=did:plc:admin789".to_string()));
+
+        // Without a server DID from either source the deploy is rejected
+        ctx.setup_initial_config("test.com")?;
+        let cmd = Commands::DeployOzone(DeployOzoneArgs {
+            server_did: None,
+            admin_dids: None,
+            recreate: false,
+            diff: false,
+        });
+        assert!(matches!(
+            handle_command(cmd, &ctx.config_path).await,
+            Err(Error::Config(_))
+        ));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_configure_ozone() -> Result<()> {
         let mock_server = MockServer::start().await;
@@ -691,8 +2910,8 @@ mod tests {
         ctx.setup_initial_config(domain)?;
 
         let cmd = Commands::ConfigureOzone(ConfigureOzoneArgs {
-            handle: format!("admin.{}", domain),
-            plc_sign_token: "test_token".to_string(),
+            handles: vec![format!("admin.{}", domain)],
+            plc_sign_tokens: vec!["test_token".to_string()],
             ozone_url: Some(format!("https://ozone.{}", domain)),
         });
 
@@ -705,7 +2924,13 @@ mod tests {
         let ctx = TestContext::new();
         ctx.setup_initial_config("test.com")?;
 
-        let cmd = Commands::Status(StatusArgs { verbose: true });
+        let cmd = Commands::Status(StatusArgs {
+            verbose: true,
+            strict_host_key: false,
+            accept_new_cert: false,
+            format: OutputFormat::Text,
+            fail_on_down: false,
+        });
 
         match handle_command(cmd, &ctx.config_path).await {
             Ok(_) => Ok(()),
@@ -732,12 +2957,44 @@ mod tests {
         let cmd = Commands::Health(HealthArgs {
             services: Some(vec!["pds".to_string()]),
             verbose: true,
+            strict_host_key: false,
+            accept_new_cert: false,
+            format: OutputFormat::Text,
+            timeout: 5,
+            retries: 0,
+            watch: false,
+            interval: 10,
         });
 
         handle_command(cmd, &ctx.config_path).await?;
         Ok(())
     }
 
+    #[test]
+    fn test_health_outcome_exit_codes() {
+        use crate::health::{HealthState, HealthStatus};
+
+        let status = |service: &str, status: HealthState| HealthStatus {
+            service: service.to_string(),
+            status,
+            latency_ms: 1,
+            details: None,
+        };
+
+        let mut statuses = vec![status("pds", HealthState::Healthy)];
+        assert!(health_outcome(&statuses).is_ok());
+
+        statuses.push(status("plc", HealthState::Degraded));
+        let err = health_outcome(&statuses).unwrap_err();
+        assert!(matches!(&err, Error::Degraded(services) if services == "plc"));
+        assert_eq!(err.exit_code(), 2);
+
+        statuses.push(status("bgs", HealthState::Unhealthy));
+        let err = health_outcome(&statuses).unwrap_err();
+        assert!(matches!(&err, Error::Unhealthy(services) if services == "bgs"));
+        assert_eq!(err.exit_code(), 1);
+    }
+
     #[tokio::test]
     async fn test_jetstream_commands() -> Result<()> {
         let ctx = TestContext::new();
@@ -745,6 +3002,8 @@ mod tests {
 
         let deploy_cmd = Commands::DeployJetstream(DeployJetstreamArgs {
             reconnect_delay: Some(300),
+            recreate: false,
+            check_only: false,
         });
 
         match handle_command(deploy_cmd, &ctx.config_path).await {
@@ -754,7 +3013,14 @@ mod tests {
         }?;
 
         let subscribe_cmd = Commands::Subscribe(SubscribeArgs {
-            collections: vec!["app.bsky.feed.post".to_string()],
+            collections: vec!["app.bsky.feed.post".parse()?],
+            dids: Vec::new(),
+            save: false,
+            resume: false,
+            cursor: None,
+            output: None,
+            split_dir: None,
+            max_events: None,
         });
 
         handle_command(subscribe_cmd, &ctx.config_path).await?;
@@ -769,12 +3035,230 @@ mod tests {
         ctx.get_path("certs/root.crt").write_str("test certificate").unwrap();
         ctx.get_path("certs/root.key").write_str("test key").unwrap();
 
-        let cmd = Commands::Certs(CertArgs { self_signed: false });
+        let cmd = Commands::Certs(CertArgs {
+            self_signed: false,
+            cert_dir: None,
+            dns_provider: None,
+            api_token: None,
+            api_token_file: None,
+            force: false,
+        });
         handle_command(cmd, &ctx.config_path).await?;
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_certs_with_cert_dir_before_init() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cmd = Commands::Certs(CertArgs {
+            self_signed: false,
+            cert_dir: Some(dir.path().join("certs")),
+            dns_provider: None,
+            api_token: None,
+            api_token_file: None,
+            force: false,
+        });
+        handle_command(cmd, &dir.path().join("missing.toml")).await?;
+
+        let cmd = Commands::Certs(CertArgs {
+            self_signed: false,
+            cert_dir: None,
+            dns_provider: None,
+            api_token: None,
+            api_token_file: None,
+            force: false,
+        });
+        assert!(handle_command(cmd, &dir.path().join("missing.toml"))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dns_provider_needs_custom_caddy_image() -> Result<()> {
+        let ctx = TestContext::new();
+        ctx.setup_initial_config("test.com")?;
+        let caddyfile = fs::read_to_string(ctx.get_path("config/caddy/Caddyfile").path())?;
+
+        let cmd = Commands::Certs(CertArgs {
+            self_signed: false,
+            cert_dir: None,
+            dns_provider: Some(crate::certs::DnsProvider::Cloudflare),
+            api_token: Some("token".into()),
+            api_token_file: None,
+            force: false,
+        });
+        let err = handle_command(cmd, &ctx.config_path).await.unwrap_err();
+        assert!(matches!(err, Error::Config(msg) if msg.contains("images.caddy")));
+        assert_eq!(
+            fs::read_to_string(ctx.get_path("config/caddy/Caddyfile").path())?,
+            caddyfile
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dns_api_token_from_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("token");
+        fs::write(&file, "secret-token\n")?;
+
+        assert_eq!(dns_api_token(None, Some(&file))?, "secret-token");
+        assert_eq!(dns_api_token(Some("flag".into()), None)?, "flag");
+        Ok(())
+    }
+
+    #[test]
+    fn test_postgres_tag_flows_into_db_service() -> Result<()> {
+        let mut config = Config::default();
+        config.database.postgres_tag = "15.6-alpine".into();
+        let mut compose = ComposeConfig::new();
+        compose.add_appview();
+        finalize_compose(&mut compose, &config, None)?;
+        assert_eq!(compose.services["db"].image, "postgres:15.6-alpine");
+
+        // A fresh install takes any major version
+        config.database.postgres_tag = "16-alpine".into();
+        assert_eq!(
+            stamp_postgres_image(&mut compose, &config, None, false),
+            None
+        );
+        assert_eq!(compose.services["db"].image, "postgres:16-alpine");
+
+        let deployed = Some("postgres:15.6-alpine");
+        let warning = stamp_postgres_image(&mut compose, &config, deployed, false);
+        assert!(warning.is_some_and(|w| w.contains("major version")));
+        assert_eq!(compose.services["db"].image, "postgres:15.6-alpine");
+
+        assert!(stamp_postgres_image(&mut compose, &config, deployed, true).is_some());
+        assert_eq!(compose.services["db"].image, "postgres:16-alpine");
+
+        // An images.db override goes through the same guard
+        config.database.postgres_tag = "15.6-alpine".into();
+        config.images.insert("db".into(), "postgres:17".into());
+        assert!(stamp_postgres_image(&mut compose, &config, deployed, false).is_some());
+        assert_eq!(compose.services["db"].image, "postgres:15.6-alpine");
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_generated_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join("atc.toml");
+        let compose_path = dir.path().join("docker-compose.yml");
+        let caddyfile_path = dir.path().join("Caddyfile");
+
+        let mut config = Config::default();
+        config.network.domain = "test.com".into();
+        config.save(&config_path)?;
+        init_compose(&config, None)?.save(&compose_path)?;
+        caddy_config(&config).save(&caddyfile_path)?;
+        verify_generated(&config_path, &compose_path, &caddyfile_path)?;
+
+        config.network.ports.https = config.network.ports.http;
+        config.save(&config_path)?;
+        match verify_generated(&config_path, &compose_path, &caddyfile_path) {
+            Err(Error::Config(msg)) => {
+                assert!(msg.contains("atc.toml is invalid"), "{}", msg);
+                assert!(msg.contains("Port numbers must be unique"), "{}", msg);
+            }
+            other => panic!("expected a port conflict, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_user_applies_to_stateful_services() -> Result<()> {
+        let mut config = Config::default();
+        assert!(init_compose(&config, None)?.services["pds"].user.is_none());
+
+        config.containers.user = Some("1000:1000".into());
+        let compose = init_compose(&config, None)?;
+        assert_eq!(compose.services["pds"].user.as_deref(), Some("1000:1000"));
+        assert!(compose.services["db"].user.is_none());
+        assert!(compose.services["caddy"].user.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_diffs_show_domain_change() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let compose_path = dir.path().join("docker-compose.yml");
+        let caddyfile_path = dir.path().join("Caddyfile");
+
+        let mut config = Config::default();
+        config.network.domain = "old.test.com".into();
+        init_compose(&config, None)?.save(&compose_path)?;
+        caddy_config(&config).save(&caddyfile_path)?;
+
+        let unchanged = file_diffs(&[
+            (
+                compose_path.as_path(),
+                init_compose(&config, None)?.to_canonical_yaml()?,
+            ),
+            (caddyfile_path.as_path(), caddy_config(&config).generate()),
+        ])?;
+        assert!(unchanged.is_empty());
+
+        config.network.domain = "new.test.com".into();
+        let diffs = file_diffs(&[
+            (
+                compose_path.as_path(),
+                init_compose(&config, None)?.to_canonical_yaml()?,
+            ),
+            (caddyfile_path.as_path(), caddy_config(&config).generate()),
+        ])?;
+        assert_eq!(diffs.len(), 2);
+        for diff in &diffs {
+            assert!(diff
+                .lines()
+                .any(|l| l.starts_with('-') && l.contains("old.test.com")));
+            assert!(diff
+                .lines()
+                .any(|l| l.starts_with('+') && l.contains("new.test.com")));
+        }
+
+        let written = ComposeConfig::load(&compose_path)?;
+        assert!(written.to_canonical_yaml()?.contains("old.test.com"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_finalize_compose_mounts_cert_dir() -> Result<()> {
+        let mut config = Config::default();
+        config.storage.cert_dir = PathBuf::from("tls/live");
+        let mut compose = ComposeConfig::new();
+        compose.add_caddy();
+        finalize_compose(&mut compose, &config, None)?;
+
+        let volumes = compose.services["caddy"].volumes.as_ref().unwrap();
+        assert!(volumes.contains(&"./tls/live:/etc/ssl/certs:ro".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jetstream_check_only_leaves_compose_unmodified() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let compose_path = dir.path().join("docker-compose.yml");
+        let config = Config::default();
+
+        let mut compose = ComposeConfig::new();
+        compose
+            .add_caddy()
+            .add_pds(&config.network.domain)
+            .add_bgs();
+        finalize_compose(&mut compose, &config, None)?;
+        compose.save(&compose_path)?;
+        let original = fs::read_to_string(&compose_path)?;
+
+        let diffs = check_jetstream_deploy(&compose_path, &config, 300)?;
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].service, "jetstream");
+        assert_eq!(fs::read_to_string(&compose_path)?, original);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_error_cases() -> Result<()> {
         let ctx = TestContext::new();
@@ -783,6 +3267,11 @@ mod tests {
         let cmd = Commands::Start(StartArgs {
             services: None,
             no_deps: true,
+            parallel_pull: false,
+            remove_orphans: false,
+            build: false,
+            no_cache: false,
+            force: false,
         });
         assert!(matches!(
             handle_command(cmd, &ctx.config_path).await,
@@ -792,7 +3281,12 @@ mod tests {
         // Test checking without config/files
         let cmd = Commands::Check(CheckArgs {
             no_dns: true,
+            wait_dns: None,
+            dns_timeout: None,
+            dns_retries: None,
             no_docker: true,
+            health: false,
+            strict: false,
         });
         assert!(matches!(
             handle_command(cmd, &ctx.config_path).await,
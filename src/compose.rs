@@ -1,6 +1,9 @@
 use crate::error::Result;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ComposeConfig {
@@ -11,11 +14,18 @@ pub struct ComposeConfig {
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Service {
+    /// Empty for services that are only built locally
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub image: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<Build>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub container_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub restart: Option<String>,
+    /// `uid[:gid]` or user name the container runs as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub environment: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -23,9 +33,161 @@ pub struct Service {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub volumes: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub depends_on: Option<Vec<String>>,
+    pub depends_on: Option<DependsOn>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub networks: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_drop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_add: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_opt: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmpfs: Option<Vec<String>>,
+    /// Time to wait after SIGTERM before killing, e.g. `"30s"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_grace_period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Logging>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<Deploy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<HealthCheck>,
+}
+
+/// The host address (empty for all) and port/protocol a `ports` entry
+/// publishes on, or `None` if it leaves the host port to Docker.
+fn host_binding(port: &str) -> Option<(String, String)> {
+    let (spec, protocol) = port.split_once('/').unwrap_or((port, "tcp"));
+    let (address, rest) = match spec.strip_prefix('[') {
+        Some(v6) => {
+            let (address, rest) = v6.split_once("]:")?;
+            (format!("[{}]", address), rest)
+        }
+        None => match spec.matches(':').count() {
+            2 => spec.split_once(':').map(|(a, r)| (a.to_string(), r))?,
+            _ => (String::new(), spec),
+        },
+    };
+    let (host, _) = rest.split_once(':')?;
+    Some((address, format!("{}/{}", host, protocol)))
+}
+
+/// A service's `build` section: either a bare context path or a mapping.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Build {
+    Context(String),
+    Config {
+        context: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        dockerfile: Option<String>,
+    },
+}
+
+/// A service's `depends_on`, either as a plain list or in the long form
+/// that gives each dependency a start condition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DependsOn {
+    List(Vec<String>),
+    Conditions(BTreeMap<String, DependsOnCondition>),
+}
+
+/// One dependency in the long `depends_on` form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DependsOnCondition {
+    /// `service_started`, `service_healthy` or
+    /// `service_completed_successfully`
+    pub condition: String,
+}
+
+impl DependsOn {
+    /// The services depended on, whichever form is used.
+    pub fn services(&self) -> Vec<String> {
+        match self {
+            DependsOn::List(services) => services.clone(),
+            DependsOn::Conditions(conditions) => conditions.keys().cloned().collect(),
+        }
+    }
+}
+
+/// A service's `healthcheck` block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthCheck {
+    pub test: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl HealthCheck {
+    /// Runs `command` through the container's shell every 10s, giving up
+    /// after 5 consecutive failures.
+    pub fn shell(command: &str) -> Self {
+        Self {
+            test: vec!["CMD-SHELL".to_string(), command.to_string()],
+            interval: Some("10s".to_string()),
+            timeout: Some("5s".to_string()),
+            retries: Some(5),
+        }
+    }
+}
+
+/// A service's `deploy` block; only resource limits are modelled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Deploy {
+    #[serde(default)]
+    pub resources: Resources,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resources {
+    #[serde(default)]
+    pub limits: ResourceLimits,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// CPU cores, e.g. `"0.5"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    /// Memory in Docker's notation, e.g. `"512m"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
+
+/// A service's `logging` block: the driver and its options.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Logging {
+    pub driver: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, String>,
+}
+
+impl Logging {
+    /// Docker's default driver, rotated at `max_size` keeping `max_file` files.
+    pub fn json_file(max_size: &str, max_file: u32) -> Self {
+        Self {
+            driver: "json-file".to_string(),
+            options: BTreeMap::from([
+                ("max-size".to_string(), max_size.to_string()),
+                ("max-file".to_string(), max_file.to_string()),
+            ]),
+        }
+    }
+
+    pub fn journald() -> Self {
+        Self {
+            driver: "journald".to_string(),
+            options: BTreeMap::new(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,6 +206,150 @@ pub struct Volume {
     pub driver: Option<String>,
 }
 
+/// Repository of the PDS image; `init --pds-version` pins a tag of it.
+pub const PDS_IMAGE: &str = "ghcr.io/bluesky-social/pds";
+
+/// Where Caddy looks for certificates inside its container.
+pub const CADDY_CERT_PATH: &str = "/etc/ssl/certs";
+
+/// Compose treats a bare relative name as a named volume, so relative
+/// host paths need a leading `./`.
+fn bind_mount_source(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if path.starts_with('/') || path.starts_with('.') || path.starts_with('~') {
+        path.into_owned()
+    } else {
+        format!("./{}", path)
+    }
+}
+
+/// Jetstream's subscribe endpoint on the internal compose network.
+pub const JETSTREAM_INTERNAL_ENDPOINT: &str = "ws://jetstream:6008/subscribe";
+
+/// Where the feed generator reads repo events from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedSubscription {
+    /// The raw BGS firehose
+    #[default]
+    Bgs,
+    /// The lighter-weight JSON stream from the jetstream service
+    Jetstream,
+}
+
+/// Keyed access to a service's `KEY=VALUE` environment entries, which stay
+/// a plain list so the compose file keeps its usual form.
+pub trait EnvVars {
+    /// Sets `key`, replacing any existing entries for it in place.
+    fn set(&mut self, key: &str, value: &str);
+    fn get(&self, key: &str) -> Option<&str>;
+    fn contains_key(&self, key: &str) -> bool;
+}
+
+impl EnvVars for Vec<String> {
+    fn set(&mut self, key: &str, value: &str) {
+        let entry = format!("{}={}", key, value);
+        if !self.contains_key(key) {
+            self.push(entry);
+            return;
+        }
+
+        // The first entry for the key takes the new value, later ones go
+        let mut replaced = false;
+        self.retain_mut(|var| {
+            if env_key(var) != key {
+                return true;
+            }
+            let keep = !replaced;
+            if keep {
+                *var = entry.clone();
+                replaced = true;
+            }
+            keep
+        });
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .find(|var| env_key(var) == key)
+            .map(|var| var.split_once('=').map_or("", |(_, value)| value))
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.iter().any(|var| env_key(var) == key)
+    }
+}
+
+/// Postgres login and main database shared by the db service and the
+/// connection URLs of the services using it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbCredentials {
+    pub user: String,
+    pub password: String,
+    pub name: String,
+}
+
+impl DbCredentials {
+    /// Connection URL for `database` on the db service.
+    pub fn url(&self, database: &str) -> String {
+        format!(
+            "postgres://{}:{}@db:5432/{}",
+            self.user, self.password, database
+        )
+    }
+}
+
+impl Default for DbCredentials {
+    /// The password is the `POSTGRES_PASSWORD` secret; installs older than
+    /// that secret get the legacy `postgres` value for it.
+    fn default() -> Self {
+        Self {
+            user: "postgres".into(),
+            password: "${POSTGRES_PASSWORD}".into(),
+            name: "appview".into(),
+        }
+    }
+}
+
+/// Describes why switching a db service running `current` to `image` would
+/// break its data directory, i.e. when the Postgres major version changes.
+pub fn postgres_upgrade_warning(current: &str, image: &str) -> Option<String> {
+    match (postgres_major(current), postgres_major(image)) {
+        (Some(from), Some(to)) if from != to => Some(format!(
+            "Changing Postgres from {} to {} changes the major version ({} to {}); \
+             the existing data directory must be migrated first",
+            current, image, from, to
+        )),
+        _ => None,
+    }
+}
+
+/// The major version in a `postgres:<tag>` ref, e.g. 15 for `postgres:15.6-alpine`.
+fn postgres_major(image: &str) -> Option<u32> {
+    let (_, tag) = image.rsplit_once(':')?;
+    let digits: String = tag.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+fn env_key(var: &str) -> &str {
+    var.split_once('=').map_or(var, |(key, _)| key)
+}
+
+/// Named volumes split by whether they survive `docker-compose down`.
+#[derive(Debug, Default, PartialEq)]
+pub struct TeardownPlan {
+    pub persisted: Vec<String>,
+    pub destroyed: Vec<String>,
+}
+
+/// Lines of a service's YAML that a change removes and adds.
+#[derive(Debug, PartialEq)]
+pub struct ServiceDiff {
+    pub service: String,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
 impl Service {
     fn new(image: &str) -> Self {
         Service {
@@ -67,6 +373,11 @@ impl Service {
         self
     }
 
+    /// The environment, created empty if the service had none.
+    pub fn env_mut(&mut self) -> &mut Vec<String> {
+        self.environment.get_or_insert_with(Vec::new)
+    }
+
     fn with_ports(mut self, ports: Vec<&str>) -> Self {
         self.ports = Some(ports.into_iter().map(String::from).collect());
         self
@@ -78,7 +389,9 @@ impl Service {
     }
 
     fn with_depends_on(mut self, deps: Vec<&str>) -> Self {
-        self.depends_on = Some(deps.into_iter().map(String::from).collect());
+        self.depends_on = Some(DependsOn::List(
+            deps.into_iter().map(String::from).collect(),
+        ));
         self
     }
 
@@ -86,6 +399,44 @@ impl Service {
         self.networks = Some(networks.into_iter().map(String::from).collect());
         self
     }
+
+    fn with_healthcheck(mut self, healthcheck: HealthCheck) -> Self {
+        self.healthcheck = Some(healthcheck);
+        self
+    }
+
+    fn with_stop_grace_period(mut self, period: &str) -> Self {
+        self.stop_grace_period = Some(period.to_string());
+        self
+    }
+
+    /// Caps CPU and memory through `deploy.resources.limits`. With neither
+    /// limit set, no `deploy` block is written.
+    fn with_resource_limits(mut self, cpus: Option<f64>, memory: Option<&str>) -> Self {
+        self.deploy = if cpus.is_none() && memory.is_none() {
+            None
+        } else {
+            Some(Deploy {
+                resources: Resources {
+                    limits: ResourceLimits {
+                        cpus: cpus.map(|cpus| cpus.to_string()),
+                        memory: memory.map(String::from),
+                    },
+                },
+            })
+        };
+        self
+    }
+
+    /// Read-only root filesystem, all capabilities dropped and privilege
+    /// escalation disabled; `/tmp` stays writable as a tmpfs.
+    fn with_hardening(mut self) -> Self {
+        self.read_only = Some(true);
+        self.cap_drop = Some(vec!["ALL".to_string()]);
+        self.security_opt = Some(vec!["no-new-privileges:true".to_string()]);
+        self.tmpfs = Some(vec!["/tmp".to_string()]);
+        self
+    }
 }
 
 impl ComposeConfig {
@@ -123,6 +474,41 @@ impl ComposeConfig {
         }
     }
 
+    /// Checks that no two services publish the same host port on the same
+    /// address, which compose would only report once the second one starts.
+    pub fn validate_host_ports(&self) -> Result<()> {
+        let mut owners: BTreeMap<(String, String), Vec<&str>> = BTreeMap::new();
+        for (service, config) in &self.services {
+            for port in config.ports.iter().flatten() {
+                if let Some(binding) = host_binding(port) {
+                    owners.entry(binding).or_default().push(service);
+                }
+            }
+        }
+
+        let conflicts: Vec<String> = owners
+            .into_iter()
+            .filter(|(_, services)| services.len() > 1)
+            .map(|((address, port), mut services)| {
+                services.sort();
+                let address = if address.is_empty() {
+                    port
+                } else {
+                    format!("{}:{}", address, port)
+                };
+                format!("{} (published by {})", address, services.join(", "))
+            })
+            .collect();
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::error::Error::Config(format!(
+                "Host port conflicts: {}",
+                conflicts.join("; ")
+            )))
+        }
+    }
+
     pub fn add_caddy(&mut self) -> &mut Self {
         let service = Service::new("caddy:2")
             .with_container_name("caddy")
@@ -130,7 +516,7 @@ impl ComposeConfig {
             .with_ports(vec!["80:80", "443:443"])
             .with_volumes(vec![
                 "./config/caddy/Caddyfile:/etc/caddy/Caddyfile",
-                "./certs:/etc/ssl/certs:ro",
+                &format!("./certs:{}:ro", CADDY_CERT_PATH),
                 "caddy_data:/data",
                 "caddy_config:/config",
             ])
@@ -141,7 +527,7 @@ impl ComposeConfig {
     }
 
     pub fn add_pds(&mut self, domain: &str) -> &mut Self {
-        let service = Service::new("ghcr.io/bluesky-social/pds:latest")
+        let service = Service::new(&format!("{}:latest", PDS_IMAGE))
             .with_container_name("pds")
             .with_restart("unless-stopped")
             .with_environment(vec![
@@ -153,7 +539,11 @@ impl ComposeConfig {
             ])
             .with_volumes(vec!["pds_data:/data"])
             .with_depends_on(vec!["caddy"])
-            .with_networks(vec!["bluesky"]);
+            .with_networks(vec!["bluesky"])
+            .with_healthcheck(HealthCheck::shell(
+                "curl -f http://localhost:3000/xrpc/_health",
+            ))
+            .with_stop_grace_period("30s");
 
         self.services.insert("pds".to_string(), service);
         self
@@ -164,7 +554,8 @@ impl ComposeConfig {
             .with_container_name("plc")
             .with_restart("unless-stopped")
             .with_depends_on(vec!["caddy"])
-            .with_networks(vec!["bluesky"]);
+            .with_networks(vec!["bluesky"])
+            .with_healthcheck(HealthCheck::shell("curl -f http://localhost:2582/_health"));
 
         self.services.insert("plc".to_string(), service);
         self
@@ -188,13 +579,14 @@ impl ComposeConfig {
     }
 
     pub fn add_appview(&mut self) -> &mut Self {
+        let db = DbCredentials::default();
         let service = Service::new("ghcr.io/bluesky-social/appview:latest")
             .with_container_name("appview")
             .with_restart("unless-stopped")
             .with_environment(vec![
                 "APPVIEW_SUBSCRIBE_REPOS=wss://pds:2470",
                 "APPVIEW_SUBSCRIBE_FROM_SEQ=0",
-                "APPVIEW_DATABASE_URL=postgres://postgres:postgres@db:5432/appview",
+                &format!("APPVIEW_DATABASE_URL={}", db.url(&db.name)),
             ])
             .with_ports(vec!["3000:3000"])
             .with_depends_on(vec!["pds", "db"])
@@ -205,34 +597,46 @@ impl ComposeConfig {
     }
 
     pub fn add_db(&mut self) -> &mut Self {
+        let db = DbCredentials::default();
         let service = Service::new("postgres:15-alpine")
             .with_container_name("db")
             .with_restart("unless-stopped")
             .with_environment(vec![
-                "POSTGRES_USER=postgres",
-                "POSTGRES_PASSWORD=postgres",
-                "POSTGRES_DB=appview",
+                &format!("POSTGRES_USER={}", db.user),
+                &format!("POSTGRES_PASSWORD={}", db.password),
+                &format!("POSTGRES_DB={}", db.name),
             ])
             .with_ports(vec!["5432:5432"])
             .with_volumes(vec!["postgres_data:/var/lib/postgresql/data"])
-            .with_networks(vec!["bluesky"]);
+            .with_networks(vec!["bluesky"])
+            .with_healthcheck(HealthCheck::shell(&format!("pg_isready -U {}", db.user)))
+            .with_stop_grace_period("60s");
 
         self.services.insert("db".to_string(), service);
         self
     }
 
-    pub fn add_feed_generator(&mut self, publisher_did: &str) -> &mut Self {
+    pub fn add_feed_generator(
+        &mut self,
+        publisher_did: &str,
+        subscription: FeedSubscription,
+    ) -> &mut Self {
+        let (endpoint, upstream) = match subscription {
+            FeedSubscription::Bgs => ("wss://bgs.${DOMAIN}", "bgs"),
+            FeedSubscription::Jetstream => (JETSTREAM_INTERNAL_ENDPOINT, "jetstream"),
+        };
+
         let service = Service::new("ghcr.io/bluesky-social/feed-generator:latest")
             .with_container_name("feed-generator")
             .with_restart("unless-stopped")
             .with_environment(vec![
                 &format!("FEEDGEN_PUBLISHER_DID={}", publisher_did),
                 "FEEDGEN_HOSTNAME=feed-generator.${DOMAIN}",
-                "FEEDGEN_SUBSCRIPTION_ENDPOINT=wss://bgs.${DOMAIN}",
+                &format!("FEEDGEN_SUBSCRIPTION_ENDPOINT={}", endpoint),
                 "FEEDGEN_SUBSCRIPTION_RECONNECT_DELAY=200",
             ])
             .with_ports(vec!["3000:3000"])
-            .with_depends_on(vec!["bgs"])
+            .with_depends_on(vec![upstream])
             .with_networks(vec!["bluesky"]);
 
         self.services.insert("feed-generator".to_string(), service);
@@ -240,6 +644,7 @@ impl ComposeConfig {
     }
 
     pub fn add_ozone(&mut self, server_did: &str, admin_dids: &[String]) -> &mut Self {
+        let db = DbCredentials::default();
         let service = Service::new("ghcr.io/bluesky-social/ozone:latest")
             .with_container_name("ozone")
             .with_restart("unless-stopped")
@@ -248,7 +653,7 @@ impl ComposeConfig {
                 &format!("OZONE_ADMIN_DIDS={}", admin_dids.join(",")),
                 "OZONE_PLC_HOST=http://plc:2582",
                 "OZONE_APP_VIEW_HOST=http://appview:3000",
-                "OZONE_DATABASE_URL=postgres://postgres:postgres@db:5432/ozone",
+                &format!("OZONE_DATABASE_URL={}", db.url("ozone")),
             ])
             .with_ports(vec!["3000:3000"])
             .with_depends_on(vec!["plc", "appview", "db"])
@@ -256,11 +661,17 @@ impl ComposeConfig {
 
         self.services.insert("ozone".to_string(), service);
 
-        // Update DB environment for multiple databases
-        if let Some(db) = self.services.get_mut("db") {
-            if let Some(env) = &mut db.environment {
-                env.push("POSTGRES_MULTIPLE_DATABASES=appview,ozone".to_string());
-            }
+        // Add the ozone database alongside any the db already creates
+        if let Some(service) = self.services.get_mut("db") {
+            let env = service.env_mut();
+            let databases = match env.get("POSTGRES_MULTIPLE_DATABASES") {
+                Some(existing) if existing.split(',').any(|name| name == "ozone") => {
+                    existing.to_string()
+                }
+                Some(existing) => format!("{},ozone", existing),
+                None => format!("{},ozone", env.get("POSTGRES_DB").unwrap_or(&db.name)),
+            };
+            env.set("POSTGRES_MULTIPLE_DATABASES", &databases);
         }
 
         self
@@ -282,6 +693,482 @@ impl ComposeConfig {
         self
     }
 
+    /// Publishes every unbound port on both `bind_v4` and `[::]`.
+    ///
+    /// Ports that already name a host address are left alone, so this is safe
+    /// to call again after adding services.
+    pub fn apply_dual_stack(&mut self, bind_v4: &str) -> &mut Self {
+        for service in self.services.values_mut() {
+            let Some(ports) = &service.ports else {
+                continue;
+            };
+
+            let mut bound = Vec::with_capacity(ports.len() * 2);
+            for port in ports {
+                if port.starts_with('[') || port.split(':').count() > 2 {
+                    bound.push(port.clone());
+                } else {
+                    bound.push(format!("{}:{}", bind_v4, port));
+                    bound.push(format!("[::]:{}", port));
+                }
+            }
+            service.ports = Some(bound);
+        }
+        self
+    }
+
+    /// Hardens every service except those that need a writable root or
+    /// extra privileges (Postgres manages its own users and sockets).
+    pub fn apply_hardening(&mut self) -> &mut Self {
+        const EXEMPT: &[&str] = &["db"];
+
+        for (name, service) in self.services.iter_mut() {
+            if EXEMPT.contains(&name.as_str()) {
+                continue;
+            }
+            *service = std::mem::take(service).with_hardening();
+
+            // Caddy still has to bind 80/443 after dropping everything else
+            if name == "caddy" {
+                service.cap_add = Some(vec!["NET_BIND_SERVICE".to_string()]);
+            }
+        }
+        self
+    }
+
+    /// Runs the pds and bgs as `user`, so their named data volumes are
+    /// owned by that uid. The db is left alone: the postgres entrypoint
+    /// needs root to initialise its data directory before dropping to the
+    /// `postgres` user.
+    pub fn apply_user(&mut self, user: &str) -> &mut Self {
+        const STATEFUL: &[&str] = &["pds", "bgs"];
+
+        for (name, service) in self.services.iter_mut() {
+            if STATEFUL.contains(&name.as_str()) {
+                service.user = Some(user.to_string());
+            }
+        }
+        self
+    }
+
+    /// Passes `keys` through from the host environment (or `.env`) to
+    /// `service` as `KEY=${KEY}` entries.
+    pub fn apply_passthrough_env(&mut self, service: &str, keys: &[&str]) -> &mut Self {
+        if let Some(service) = self.services.get_mut(service) {
+            let env = service.env_mut();
+            for key in keys {
+                env.set(key, &format!("${{{}}}", key));
+            }
+        }
+        self
+    }
+
+    /// Bind-mounts `cert_dir` as Caddy's certificate directory, replacing
+    /// the default `./certs` mount.
+    pub fn apply_cert_dir(&mut self, cert_dir: &Path) -> &mut Self {
+        if let Some(volumes) = self
+            .services
+            .get_mut("caddy")
+            .and_then(|caddy| caddy.volumes.as_mut())
+        {
+            let mount = format!("{}:{}:ro", bind_mount_source(cert_dir), CADDY_CERT_PATH);
+            for volume in volumes.iter_mut() {
+                if volume.contains(&format!(":{}", CADDY_CERT_PATH)) {
+                    *volume = mount.clone();
+                }
+            }
+        }
+        self
+    }
+
+    /// Host directory Caddy reads certificates from, if it has one mounted.
+    pub fn caddy_cert_source(&self) -> Option<&str> {
+        let suffix = format!(":{}", CADDY_CERT_PATH);
+        self.services
+            .get("caddy")?
+            .volumes
+            .as_ref()?
+            .iter()
+            .find_map(|volume| {
+                let volume = volume.strip_suffix(":ro").unwrap_or(volume);
+                volume.strip_suffix(&suffix)
+            })
+    }
+
+    /// Errors if Caddy's certificate mount isn't `cert_dir`, since Caddy
+    /// would then never see the certificates written there.
+    pub fn verify_cert_mount(&self, cert_dir: &Path) -> Result<()> {
+        let Some(source) = self.caddy_cert_source() else {
+            return Ok(());
+        };
+
+        let expected = bind_mount_source(cert_dir);
+        if source.trim_end_matches('/') != expected.trim_end_matches('/') {
+            return Err(crate::error::Error::Config(format!(
+                "Caddy mounts certificates from {} but storage.cert_dir is {}; \
+                 re-run init or fix the caddy volume in docker-compose.yml",
+                source, expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// Sets the logging driver on every service.
+    pub fn apply_logging(&mut self, logging: &Logging) -> &mut Self {
+        for service in self.services.values_mut() {
+            service.logging = Some(logging.clone());
+        }
+        self
+    }
+
+    /// Replaces `service`'s image, returning whether the service exists.
+    pub fn set_image(&mut self, service: &str, image: &str) -> bool {
+        match self.services.get_mut(service) {
+            Some(service) => {
+                service.image = image.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies configured image refs, keyed by service name, over the
+    /// builders' `:latest` defaults. Services not in the file are skipped.
+    pub fn apply_images(&mut self, images: &HashMap<String, String>) -> &mut Self {
+        for (service, image) in images {
+            self.set_image(service, image);
+        }
+        self
+    }
+
+    /// Replaces `service`'s resource limits, if the service exists.
+    pub fn apply_resource_limits(
+        &mut self,
+        service: &str,
+        cpus: Option<f64>,
+        memory: Option<&str>,
+    ) -> &mut Self {
+        if let Some(service) = self.services.get_mut(service) {
+            *service = std::mem::take(service).with_resource_limits(cpus, memory);
+        }
+        self
+    }
+
+    /// Points the db service and every connection URL at `db`'s login and
+    /// main database, replacing the builders' defaults.
+    pub fn apply_db_credentials(&mut self, db: &DbCredentials) -> &mut Self {
+        if let Some(service) = self.services.get_mut("db") {
+            let env = service.env_mut();
+            let old_name = env.get("POSTGRES_DB").map(str::to_string);
+            if let (Some(old_name), Some(databases)) =
+                (old_name, env.get("POSTGRES_MULTIPLE_DATABASES"))
+            {
+                let databases: Vec<&str> = databases
+                    .split(',')
+                    .map(|name| {
+                        if name == old_name {
+                            db.name.as_str()
+                        } else {
+                            name
+                        }
+                    })
+                    .collect();
+                let databases = databases.join(",");
+                env.set("POSTGRES_MULTIPLE_DATABASES", &databases);
+            }
+            env.set("POSTGRES_USER", &db.user);
+            env.set("POSTGRES_PASSWORD", &db.password);
+            env.set("POSTGRES_DB", &db.name);
+            service.healthcheck = Some(HealthCheck::shell(&format!("pg_isready -U {}", db.user)));
+        }
+        if let Some(appview) = self.services.get_mut("appview") {
+            appview
+                .env_mut()
+                .set("APPVIEW_DATABASE_URL", &db.url(&db.name));
+        }
+        if let Some(ozone) = self.services.get_mut("ozone") {
+            ozone.env_mut().set("OZONE_DATABASE_URL", &db.url("ozone"));
+        }
+        self
+    }
+
+    /// Sets `PDS_BLOB_UPLOAD_LIMIT` on the pds service, replacing any prior value.
+    pub fn apply_pds_limits(&mut self, blob_upload_limit: u64) -> &mut Self {
+        if let Some(pds) = self.services.get_mut("pds") {
+            pds.env_mut()
+                .set("PDS_BLOB_UPLOAD_LIMIT", &blob_upload_limit.to_string());
+        }
+        self
+    }
+
+    /// Services with a `build` section, limited to `only` when given.
+    pub fn build_services(&self, only: Option<&[String]>) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .services
+            .iter()
+            .filter(|(_, service)| service.build.is_some())
+            .filter(|(name, _)| only.is_none_or(|only| only.contains(name)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn teardown_plan(&self, clean: bool) -> TeardownPlan {
+        let mut plan = TeardownPlan::default();
+        let Some(volumes) = &self.volumes else {
+            return plan;
+        };
+
+        for (name, volume) in volumes {
+            // `down -v` never removes volumes managed outside the project
+            if clean && volume.external != Some(true) {
+                plan.destroyed.push(name.clone());
+            } else {
+                plan.persisted.push(name.clone());
+            }
+        }
+
+        plan.persisted.sort();
+        plan.destroyed.sort();
+        plan
+    }
+
+    /// Makes services wait for dependencies that define a healthcheck to
+    /// report healthy, not just to have started.
+    pub fn apply_health_conditions(&mut self) -> &mut Self {
+        let checked: Vec<String> = self
+            .services
+            .iter()
+            .filter(|(_, service)| service.healthcheck.is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for service in self.services.values_mut() {
+            let Some(deps) = service.depends_on.as_ref().map(DependsOn::services) else {
+                continue;
+            };
+            service.depends_on = Some(if deps.iter().any(|dep| checked.contains(dep)) {
+                DependsOn::Conditions(
+                    deps.into_iter()
+                        .map(|dep| {
+                            let condition = if checked.contains(&dep) {
+                                "service_healthy"
+                            } else {
+                                "service_started"
+                            };
+                            let condition = DependsOnCondition {
+                                condition: condition.to_string(),
+                            };
+                            (dep, condition)
+                        })
+                        .collect(),
+                )
+            } else {
+                DependsOn::List(deps)
+            });
+        }
+        self
+    }
+
+    /// Checks that services only depend on services and networks defined
+    /// in this file.
+    pub fn validate(&self) -> Result<()> {
+        let mut names: Vec<&String> = self.services.keys().collect();
+        names.sort();
+
+        for name in names {
+            let service = &self.services[name];
+            for dependency in service.depends_on.iter().flat_map(DependsOn::services) {
+                if !self.services.contains_key(&dependency) {
+                    return Err(crate::error::Error::Config(format!(
+                        "Service {} depends on undefined service {}",
+                        name, dependency
+                    )));
+                }
+            }
+            for network in service.networks.iter().flatten() {
+                let defined = self
+                    .networks
+                    .as_ref()
+                    .is_some_and(|networks| networks.contains_key(network));
+                if !defined {
+                    return Err(crate::error::Error::Config(format!(
+                        "Service {} uses undefined network {}",
+                        name, network
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that no two services claim the same `container_name`, which
+    /// compose would otherwise only report once it tries to create them.
+    pub fn validate_container_names(&self) -> Result<()> {
+        let mut owners: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (service, config) in &self.services {
+            if let Some(name) = &config.container_name {
+                owners.entry(name).or_default().push(service);
+            }
+        }
+
+        let collisions: Vec<String> = owners
+            .into_iter()
+            .filter(|(_, services)| services.len() > 1)
+            .map(|(name, mut services)| {
+                services.sort();
+                format!("{} (used by {})", name, services.join(", "))
+            })
+            .collect();
+        if collisions.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::error::Error::Config(format!(
+                "Duplicate container names: {}",
+                collisions.join("; ")
+            )))
+        }
+    }
+
+    /// Each service's `depends_on` list, both sorted by name.
+    pub fn dependency_graph(&self) -> BTreeMap<String, Vec<String>> {
+        self.services
+            .iter()
+            .map(|(name, service)| {
+                let mut deps = service
+                    .depends_on
+                    .as_ref()
+                    .map(DependsOn::services)
+                    .unwrap_or_default();
+                deps.sort();
+                (name.clone(), deps)
+            })
+            .collect()
+    }
+
+    /// The first dependency cycle found, as a path that starts and ends on
+    /// the same service.
+    pub fn dependency_cycle(&self) -> Option<Vec<String>> {
+        fn visit(
+            graph: &BTreeMap<String, Vec<String>>,
+            name: &str,
+            path: &mut Vec<String>,
+            done: &mut Vec<String>,
+        ) -> Option<Vec<String>> {
+            if let Some(start) = path.iter().position(|n| n == name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name.to_string());
+                return Some(cycle);
+            }
+            if done.iter().any(|n| n == name) {
+                return None;
+            }
+            path.push(name.to_string());
+            for dep in graph.get(name).into_iter().flatten() {
+                if let Some(cycle) = visit(graph, dep, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.push(name.to_string());
+            None
+        }
+
+        let graph = self.dependency_graph();
+        let mut done = Vec::new();
+        graph
+            .keys()
+            .find_map(|name| visit(&graph, name, &mut Vec::new(), &mut done))
+    }
+
+    /// Services as an indented tree, each followed by what it depends on.
+    /// Roots are the services nothing else depends on; a dependency that
+    /// loops back is marked instead of being expanded again.
+    pub fn dependency_tree(&self) -> String {
+        fn render(
+            graph: &BTreeMap<String, Vec<String>>,
+            name: &str,
+            path: &mut Vec<String>,
+            out: &mut String,
+        ) {
+            let indent = "  ".repeat(path.len());
+            if path.iter().any(|n| n == name) {
+                out.push_str(&format!("{}{} (cycle)\n", indent, name));
+                return;
+            }
+            out.push_str(&format!("{}{}\n", indent, name));
+            path.push(name.to_string());
+            for dep in graph.get(name).into_iter().flatten() {
+                render(graph, dep, path, out);
+            }
+            path.pop();
+        }
+
+        let graph = self.dependency_graph();
+        let mut roots: Vec<&String> = graph
+            .keys()
+            .filter(|name| !graph.values().flatten().any(|dep| dep == *name))
+            .collect();
+        if roots.is_empty() {
+            // Everything is part of a cycle; start from every service
+            roots = graph.keys().collect();
+        }
+
+        let mut out = String::new();
+        for root in roots {
+            render(&graph, root, &mut Vec::new(), &mut out);
+        }
+        out
+    }
+
+    /// The dependency graph in Graphviz DOT, with edges pointing from a
+    /// service to what it depends on.
+    pub fn dependency_dot(&self) -> String {
+        let mut out = String::from("digraph services {\n");
+        for (name, deps) in self.dependency_graph() {
+            if deps.is_empty() {
+                out.push_str(&format!("    \"{}\";\n", name));
+            }
+            for dep in deps {
+                out.push_str(&format!("    \"{}\" -> \"{}\";\n", name, dep));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Per-service YAML lines that differ from `before`, sorted by service.
+    /// Unchanged services are left out.
+    pub fn diff_services(&self, before: &ComposeConfig) -> Result<Vec<ServiceDiff>> {
+        let mut names: Vec<&String> = self.services.keys().chain(before.services.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        let mut diffs = Vec::new();
+        for name in names {
+            let old = service_lines(before.services.get(name))?;
+            let new = service_lines(self.services.get(name))?;
+            if old == new {
+                continue;
+            }
+            diffs.push(ServiceDiff {
+                service: name.clone(),
+                removed: old
+                    .iter()
+                    .filter(|line| !new.contains(line))
+                    .cloned()
+                    .collect(),
+                added: new
+                    .iter()
+                    .filter(|line| !old.contains(line))
+                    .cloned()
+                    .collect(),
+            });
+        }
+        Ok(diffs)
+    }
+
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
         let content = serde_yaml::to_string(self).map_err(|e| {
             crate::error::Error::Yaml(format!("Failed to serialize compose config: {}", e))
@@ -290,6 +1177,64 @@ impl ComposeConfig {
         Ok(())
     }
 
+    /// YAML with every mapping's keys sorted, so two configs with the same
+    /// content serialize identically regardless of map order.
+    pub fn to_canonical_yaml(&self) -> Result<String> {
+        let value = serde_json::to_value(self).map_err(|e| {
+            crate::error::Error::Yaml(format!("Failed to serialize compose config: {}", e))
+        })?;
+        serde_yaml::to_string(&value).map_err(|e| {
+            crate::error::Error::Yaml(format!("Failed to serialize compose config: {}", e))
+        })
+    }
+
+    /// Serializes the config with `${VAR}` and `${VAR:-default}` references
+    /// substituted from `env`, mirroring what Compose itself would run.
+    pub fn render(&self, env: &HashMap<String, String>) -> Result<String> {
+        let content = serde_yaml::to_string(self).map_err(|e| {
+            crate::error::Error::Yaml(format!("Failed to serialize compose config: {}", e))
+        })?;
+        Ok(interpolate(&content, env))
+    }
+
+    /// Interpolates `service`'s environment against `env`, returning each
+    /// `KEY=value` pair alongside the variables it referenced that are unset.
+    pub fn resolve_environment(
+        &self,
+        service: &str,
+        env: &HashMap<String, String>,
+    ) -> Result<Vec<ResolvedVar>> {
+        let service = self
+            .services
+            .get(service)
+            .ok_or_else(|| crate::error::Error::Config(format!("Unknown service: {}", service)))?;
+
+        Ok(service
+            .environment
+            .iter()
+            .flatten()
+            .map(|entry| {
+                let (key, template) = entry.split_once('=').unwrap_or((entry, ""));
+                let references = referenced_variables(template);
+                ResolvedVar {
+                    key: key.to_string(),
+                    value: interpolate(template, env),
+                    missing: references
+                        .iter()
+                        .filter(|(name, has_default)| {
+                            !has_default && env.get(*name).is_none_or(|v| v.is_empty())
+                        })
+                        .map(|(name, _)| name.to_string())
+                        .collect(),
+                    references: references
+                        .into_iter()
+                        .map(|(name, _)| name.to_string())
+                        .collect(),
+                }
+            })
+            .collect())
+    }
+
     pub fn load(path: impl AsRef<Path>) -> Result<Self> {
         let content = std::fs::read_to_string(path)?;
         let config = serde_yaml::from_str(&content).map_err(|e| {
@@ -299,6 +1244,89 @@ impl ComposeConfig {
     }
 }
 
+fn service_lines(service: Option<&Service>) -> Result<Vec<String>> {
+    let Some(service) = service else {
+        return Ok(Vec::new());
+    };
+    let content = serde_yaml::to_string(service).map_err(|e| {
+        crate::error::Error::Yaml(format!("Failed to serialize compose service: {}", e))
+    })?;
+    Ok(content.lines().map(String::from).collect())
+}
+
+/// One entry of a service's environment after interpolation.
+#[derive(Debug, PartialEq)]
+pub struct ResolvedVar {
+    pub key: String,
+    pub value: String,
+    /// Variables the template referred to
+    pub references: Vec<String>,
+    /// Referenced variables that were unset or empty
+    pub missing: Vec<String>,
+}
+
+/// Names of the `${VAR}`/`${VAR:-default}` references in `template`,
+/// each with whether it has a default.
+fn referenced_variables(template: &str) -> Vec<(&str, bool)> {
+    let mut names = Vec::new();
+    let mut rest = template;
+
+    while let Some(pos) = rest.find('$') {
+        rest = &rest[pos..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            rest = after;
+        } else if let Some(end) = rest.find('}').filter(|_| rest.starts_with("${")) {
+            let expr = &rest[2..end];
+            names.push(match expr.split_once(":-") {
+                Some((name, _)) => (name, true),
+                None => (expr, false),
+            });
+            rest = &rest[end + 1..];
+        } else {
+            rest = &rest[1..];
+        }
+    }
+
+    names
+}
+
+fn interpolate(template: &str, env: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(pos) = rest.find('$') {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            output.push('$');
+            rest = after;
+        } else if let Some(end) = rest.find('}').filter(|_| rest.starts_with("${")) {
+            let expr = &rest[2..end];
+            let (name, default) = match expr.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (expr, None),
+            };
+            match env.get(name).filter(|v| !v.is_empty()) {
+                Some(value) => output.push_str(value),
+                None => {
+                    if default.is_none() {
+                        tracing::warn!("Variable {} is not set, substituting empty string", name);
+                    }
+                    output.push_str(default.unwrap_or_default());
+                }
+            }
+            rest = &rest[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -329,7 +1357,10 @@ mod tests {
         assert_eq!(service.environment, Some(vec!["KEY=VALUE".to_string()]));
         assert_eq!(service.ports, Some(vec!["8080:80".to_string()]));
         assert_eq!(service.volumes, Some(vec!["/host:/container".to_string()]));
-        assert_eq!(service.depends_on, Some(vec!["db".to_string()]));
+        assert_eq!(
+            service.depends_on,
+            Some(DependsOn::List(vec!["db".to_string()]))
+        );
         assert_eq!(service.networks, Some(vec!["test_net".to_string()]));
     }
 
@@ -394,7 +1425,7 @@ mod tests {
     #[test]
     fn test_add_feed_generator() {
         let mut config = ComposeConfig::new();
-        config.add_feed_generator("did:plc:test123");
+        config.add_feed_generator("did:plc:test123", FeedSubscription::Bgs);
 
         let feed_gen = config.services.get("feed-generator").unwrap();
         assert_eq!(
@@ -413,7 +1444,7 @@ mod tests {
     fn test_add_ozone() {
         let mut config = ComposeConfig::new();
         config.add_appview(); // This adds db service too
-        config.add_ozone("did:plc:test123", &vec!["did:plc:admin456".to_string()]);
+        config.add_ozone("did:plc:test123", &["did:plc:admin456".to_string()]);
 
         let ozone = config.services.get("ozone").unwrap();
         assert_eq!(ozone.image, "ghcr.io/bluesky-social/ozone:latest");
@@ -431,6 +1462,41 @@ mod tests {
             .contains(&"POSTGRES_MULTIPLE_DATABASES=appview,ozone".to_string()));
     }
 
+    #[test]
+    fn test_env_vars() {
+        let mut env = vec![
+            "A=1".to_string(),
+            "B=2".to_string(),
+            "A=3".to_string(),
+            "EMPTY=".to_string(),
+        ];
+        assert_eq!(env.get("A"), Some("1"));
+        assert_eq!(env.get("EMPTY"), Some(""));
+        assert_eq!(env.get("MISSING"), None);
+        assert!(env.contains_key("B"));
+        assert!(!env.contains_key("AB"));
+
+        env.set("A", "4");
+        assert_eq!(env, vec!["A=4", "B=2", "EMPTY="]);
+
+        env.set("C", "url=with=equals");
+        assert_eq!(env.get("C"), Some("url=with=equals"));
+        assert_eq!(env.len(), 4);
+
+        let mut config = ComposeConfig::new();
+        config.add_db();
+        config.add_ozone("did:plc:test123", &[]);
+        config.add_ozone("did:plc:test123", &[]);
+        let db_env = config.services["db"].environment.as_ref().unwrap();
+        assert_eq!(
+            db_env
+                .iter()
+                .filter(|var| var.starts_with("POSTGRES_MULTIPLE_DATABASES="))
+                .count(),
+            1
+        );
+    }
+
     #[test]
     fn test_add_jetstream() {
         let mut config = ComposeConfig::new();
@@ -445,4 +1511,511 @@ mod tests {
             .iter()
             .any(|e| e.contains("RECONNECT_DELAY=200")));
     }
+
+    #[test]
+    fn test_db_credentials_reach_db_and_appview() {
+        let mut compose = ComposeConfig::new();
+        compose.add_caddy().add_pds("test.com").add_appview();
+        compose.add_ozone("did:plc:server", &[]);
+
+        let defaults = compose.services["appview"].environment.clone().unwrap();
+        assert_eq!(
+            defaults.get("APPVIEW_DATABASE_URL"),
+            Some("postgres://postgres:${POSTGRES_PASSWORD}@db:5432/appview")
+        );
+
+        compose.apply_db_credentials(&DbCredentials {
+            user: "bsky".into(),
+            password: "hunter2-hunter2".into(),
+            name: "bsky_appview".into(),
+        });
+
+        let db = &compose.services["db"];
+        let env = db.environment.as_ref().unwrap();
+        assert_eq!(env.get("POSTGRES_USER"), Some("bsky"));
+        assert_eq!(env.get("POSTGRES_PASSWORD"), Some("hunter2-hunter2"));
+        assert_eq!(env.get("POSTGRES_DB"), Some("bsky_appview"));
+        assert_eq!(
+            env.get("POSTGRES_MULTIPLE_DATABASES"),
+            Some("bsky_appview,ozone")
+        );
+        assert_eq!(
+            db.healthcheck.as_ref().unwrap().test[1],
+            "pg_isready -U bsky"
+        );
+
+        let appview = compose.services["appview"].environment.as_ref().unwrap();
+        assert_eq!(
+            appview.get("APPVIEW_DATABASE_URL"),
+            Some("postgres://bsky:hunter2-hunter2@db:5432/bsky_appview")
+        );
+        let ozone = compose.services["ozone"].environment.as_ref().unwrap();
+        assert_eq!(
+            ozone.get("OZONE_DATABASE_URL"),
+            Some("postgres://bsky:hunter2-hunter2@db:5432/ozone")
+        );
+    }
+
+    #[test]
+    fn test_validate_container_names() -> Result<()> {
+        let mut compose = ComposeConfig::new();
+        compose.add_caddy().add_pds("test.com").add_plc();
+        compose.validate_container_names()?;
+
+        let custom = Service::new("example/custom:latest").with_container_name("pds");
+        compose.services.insert("custom".to_string(), custom);
+        match compose.validate_container_names() {
+            Err(crate::error::Error::Config(msg)) => {
+                assert!(msg.contains("pds (used by custom, pds)"), "{}", msg)
+            }
+            other => panic!("expected a collision, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_host_ports() -> Result<()> {
+        let mut compose = ComposeConfig::new();
+        compose.add_caddy().add_pds("test.com");
+        compose.apply_dual_stack("0.0.0.0");
+        compose.validate_host_ports()?;
+
+        let proxy = Service::new("nginx:latest").with_ports(vec!["0.0.0.0:443:8443", "53:53/udp"]);
+        compose.services.insert("proxy".to_string(), proxy);
+        match compose.validate_host_ports() {
+            Err(crate::error::Error::Config(msg)) => {
+                assert_eq!(
+                    msg,
+                    "Host port conflicts: 0.0.0.0:443/tcp (published by caddy, proxy)"
+                )
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        assert_eq!(host_binding("3000"), None);
+        assert_eq!(
+            host_binding("[::]:80:80"),
+            Some(("[::]".to_string(), "80/tcp".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dependency_graph_output() {
+        let mut compose = ComposeConfig::new();
+        compose
+            .add_caddy()
+            .add_pds("test.com")
+            .add_plc()
+            .add_bgs()
+            .add_appview();
+        assert_eq!(compose.dependency_cycle(), None);
+
+        let dot = compose.dependency_dot();
+        assert!(dot.contains("\"appview\" -> \"db\";"));
+        assert!(dot.contains("\"pds\" -> \"caddy\";"));
+
+        let tree = compose.dependency_tree();
+        assert!(tree.contains("appview\n  db\n"));
+        assert!(tree.contains("  pds\n    caddy\n"));
+
+        compose.services.get_mut("caddy").unwrap().depends_on =
+            Some(DependsOn::List(vec!["pds".to_string()]));
+        assert_eq!(
+            compose.dependency_cycle(),
+            Some(vec![
+                "pds".to_string(),
+                "caddy".to_string(),
+                "pds".to_string()
+            ])
+        );
+        assert!(compose.dependency_tree().contains("(cycle)"));
+    }
+
+    #[test]
+    fn test_validate_and_diff_services() -> Result<()> {
+        let mut before = ComposeConfig::new();
+        before.add_caddy().add_pds("test.com").add_bgs();
+        before.validate()?;
+
+        let mut after = ComposeConfig::new();
+        after
+            .add_caddy()
+            .add_pds("test.com")
+            .add_bgs()
+            .add_jetstream(500);
+        after.validate()?;
+
+        let diffs = after.diff_services(&before)?;
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].service, "jetstream");
+        assert!(diffs[0].removed.is_empty());
+        assert!(diffs[0]
+            .added
+            .contains(&"- JETSTREAM_SUBSCRIPTION_RECONNECT_DELAY=500".to_string()));
+
+        let mut orphaned = ComposeConfig::new();
+        orphaned.add_jetstream(500);
+        assert!(matches!(
+            orphaned.validate(),
+            Err(crate::error::Error::Config(msg)) if msg.contains("bgs")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_teardown_plan() -> Result<()> {
+        let dir = tempdir()?;
+        let config_path = dir.path().join("docker-compose.yml");
+        std::fs::write(
+            &config_path,
+            "services: {}\nvolumes:\n  pds_data: {}\n  postgres_data: {}\n  shared:\n    external: true\n",
+        )?;
+        let config = ComposeConfig::load(&config_path)?;
+
+        let plan = config.teardown_plan(false);
+        assert_eq!(plan.persisted, vec!["pds_data", "postgres_data", "shared"]);
+        assert!(plan.destroyed.is_empty());
+
+        let plan = config.teardown_plan(true);
+        assert_eq!(plan.persisted, vec!["shared"]);
+        assert_eq!(plan.destroyed, vec!["pds_data", "postgres_data"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_substitutes_env() -> Result<()> {
+        let mut config = ComposeConfig::new();
+        config
+            .add_pds("example.com")
+            .add_feed_generator("did:plc:test123", FeedSubscription::Bgs);
+
+        let mut env = HashMap::new();
+        env.insert("DOMAIN".to_string(), "example.com".to_string());
+        env.insert("PDS_JWT_SECRET".to_string(), "jwtsecret".to_string());
+
+        let rendered = config.render(&env)?;
+        assert!(rendered.contains("FEEDGEN_HOSTNAME=feed-generator.example.com"));
+        assert!(rendered.contains("PDS_JWT_SECRET=jwtsecret"));
+        assert!(!rendered.contains("${DOMAIN}"));
+
+        assert_eq!(
+            interpolate("a$$b ${X:-def} $5", &HashMap::new()),
+            "a$b def $5"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dual_stack_ports() {
+        let mut config = ComposeConfig::new();
+        config.add_caddy().add_bgs();
+        config.apply_dual_stack("0.0.0.0");
+
+        let caddy_ports = config.services["caddy"].ports.as_ref().unwrap();
+        assert_eq!(
+            caddy_ports,
+            &vec![
+                "0.0.0.0:80:80".to_string(),
+                "[::]:80:80".to_string(),
+                "0.0.0.0:443:443".to_string(),
+                "[::]:443:443".to_string(),
+            ]
+        );
+
+        // Applying again must not duplicate bindings
+        config.apply_dual_stack("0.0.0.0");
+        let bgs_ports = config.services["bgs"].ports.as_ref().unwrap();
+        assert_eq!(bgs_ports.len(), 2);
+        assert!(bgs_ports.contains(&"[::]:2470:2470".to_string()));
+    }
+
+    #[test]
+    fn test_hardening_roundtrip() -> Result<()> {
+        let dir = tempdir()?;
+        let config_path = dir.path().join("docker-compose.yml");
+
+        let mut config = ComposeConfig::new();
+        config.add_caddy().add_pds("test.com").add_appview();
+        config.apply_hardening();
+        config.save(&config_path)?;
+
+        let loaded = ComposeConfig::load(&config_path)?;
+
+        let pds = &loaded.services["pds"];
+        assert_eq!(pds.read_only, Some(true));
+        assert_eq!(pds.cap_drop, Some(vec!["ALL".to_string()]));
+        assert_eq!(
+            pds.security_opt,
+            Some(vec!["no-new-privileges:true".to_string()])
+        );
+        assert_eq!(pds.tmpfs, Some(vec!["/tmp".to_string()]));
+        assert!(pds.cap_add.is_none());
+
+        let caddy = &loaded.services["caddy"];
+        assert_eq!(caddy.cap_add, Some(vec!["NET_BIND_SERVICE".to_string()]));
+
+        let db = &loaded.services["db"];
+        assert!(db.read_only.is_none());
+        assert!(db.cap_drop.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_grace_period() -> Result<()> {
+        let dir = tempdir()?;
+        let config_path = dir.path().join("docker-compose.yml");
+
+        let mut config = ComposeConfig::new();
+        config.add_caddy().add_pds("test.com").add_appview();
+        config.save(&config_path)?;
+
+        let loaded = ComposeConfig::load(&config_path)?;
+
+        assert_eq!(
+            loaded.services["db"].stop_grace_period.as_deref(),
+            Some("60s")
+        );
+        assert_eq!(
+            loaded.services["pds"].stop_grace_period.as_deref(),
+            Some("30s")
+        );
+        assert!(loaded.services["caddy"].stop_grace_period.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_cert_dir_mount() {
+        let mut config = ComposeConfig::new();
+        config.add_caddy();
+        assert!(config.services["caddy"]
+            .volumes
+            .as_ref()
+            .unwrap()
+            .contains(&"./certs:/etc/ssl/certs:ro".to_string()));
+
+        config.apply_cert_dir(Path::new("/srv/atc/certs"));
+        let volumes = config.services["caddy"].volumes.as_ref().unwrap();
+        assert!(volumes.contains(&"/srv/atc/certs:/etc/ssl/certs:ro".to_string()));
+        assert!(!volumes.iter().any(|v| v.starts_with("./certs:")));
+
+        config.apply_cert_dir(Path::new("tls"));
+        let volumes = config.services["caddy"].volumes.as_ref().unwrap();
+        assert!(volumes.contains(&"./tls:/etc/ssl/certs:ro".to_string()));
+    }
+
+    #[test]
+    fn test_cert_mount_mismatch() {
+        let mut config = ComposeConfig::new();
+        config.add_caddy();
+
+        assert_eq!(config.caddy_cert_source(), Some("./certs"));
+        assert!(config.verify_cert_mount(Path::new("certs")).is_ok());
+        assert!(config.verify_cert_mount(Path::new("./certs/")).is_ok());
+
+        let err = config
+            .verify_cert_mount(Path::new("/srv/certs"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::Error::Config(msg) if msg.contains("./certs") && msg.contains("/srv/certs")
+        ));
+    }
+
+    #[test]
+    fn test_logging_roundtrip() -> Result<()> {
+        let dir = tempdir()?;
+        let config_path = dir.path().join("docker-compose.yml");
+
+        let mut config = ComposeConfig::new();
+        config.add_caddy().add_pds("test.com");
+        config.apply_logging(&Logging::json_file("10m", 3));
+        config.save(&config_path)?;
+
+        let content = std::fs::read_to_string(&config_path)?;
+        assert!(content.contains("driver: json-file"));
+        assert!(content.contains("max-size: 10m"));
+        assert!(content.contains("max-file: '3'"));
+
+        let loaded = ComposeConfig::load(&config_path)?;
+        for service in loaded.services.values() {
+            assert_eq!(service.logging, Some(Logging::json_file("10m", 3)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_limits_roundtrip() -> Result<()> {
+        let mut config = ComposeConfig::new();
+        config.add_db().add_bgs();
+        config.apply_resource_limits("db", Some(0.5), Some("512m"));
+
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        let parsed: ComposeConfig = serde_yaml::from_str(&yaml).unwrap();
+        let limits = &parsed.services["db"]
+            .deploy
+            .as_ref()
+            .unwrap()
+            .resources
+            .limits;
+        assert_eq!(limits.cpus.as_deref(), Some("0.5"));
+        assert_eq!(limits.memory.as_deref(), Some("512m"));
+
+        // Services without limits get no deploy block at all
+        assert!(parsed.services["bgs"].deploy.is_none());
+        let bgs = serde_yaml::to_string(&parsed.services["bgs"]).unwrap();
+        assert!(!bgs.contains("deploy"));
+
+        config.apply_resource_limits("db", None, None);
+        assert!(config.services["db"].deploy.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_postgres_upgrade_warning() {
+        let current = "postgres:15-alpine";
+        assert_eq!(
+            postgres_upgrade_warning(current, "postgres:15.6-alpine"),
+            None
+        );
+
+        let warning = postgres_upgrade_warning(current, "postgres:16-alpine").unwrap();
+        assert!(warning.contains("15 to 16"));
+        assert_eq!(postgres_major("postgres:latest"), None);
+    }
+
+    #[test]
+    fn test_image_overrides() {
+        let mut config = ComposeConfig::new();
+        config.add_pds("test.com").add_plc();
+
+        assert!(config.set_image("plc", "ghcr.io/bluesky-social/plc:v0.1.0"));
+        assert!(!config.set_image("ozone", "ghcr.io/bluesky-social/ozone:v1"));
+        assert_eq!(
+            config.services["plc"].image,
+            "ghcr.io/bluesky-social/plc:v0.1.0"
+        );
+
+        config.apply_images(&HashMap::from([(
+            "pds".to_string(),
+            format!("{}:v0.4.0", PDS_IMAGE),
+        )]));
+        assert_eq!(
+            config.services["pds"].image,
+            "ghcr.io/bluesky-social/pds:v0.4.0"
+        );
+        assert!(!config.services.contains_key("ozone"));
+    }
+
+    #[test]
+    fn test_healthchecks_roundtrip() -> Result<()> {
+        let mut config = ComposeConfig::new();
+        config.add_caddy().add_pds("test.com").add_plc().add_db();
+
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        let parsed: ComposeConfig = serde_yaml::from_str(&yaml).unwrap();
+
+        let pds = parsed.services["pds"].healthcheck.as_ref().unwrap();
+        assert_eq!(
+            pds.test,
+            vec!["CMD-SHELL", "curl -f http://localhost:3000/xrpc/_health"]
+        );
+        assert_eq!(pds.retries, Some(5));
+        assert!(
+            parsed.services["db"].healthcheck.as_ref().unwrap().test[1].starts_with("pg_isready")
+        );
+        assert!(parsed.services["plc"].healthcheck.is_some());
+        assert!(parsed.services["caddy"].healthcheck.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_health_conditions() -> Result<()> {
+        let mut config = ComposeConfig::new();
+        config
+            .add_caddy()
+            .add_pds("test.com")
+            .add_plc()
+            .add_bgs()
+            .add_appview()
+            .apply_health_conditions();
+
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(yaml.contains("depends_on:\n      db:\n        condition: service_healthy"));
+        let parsed: ComposeConfig = serde_yaml::from_str(&yaml).unwrap();
+
+        let condition = |service: &str, dep: &str| match &parsed.services[service].depends_on {
+            Some(DependsOn::Conditions(conditions)) => conditions[dep].condition.clone(),
+            other => panic!("expected conditions for {}, got {:?}", service, other),
+        };
+        assert_eq!(condition("appview", "db"), "service_healthy");
+        assert_eq!(condition("appview", "pds"), "service_healthy");
+        assert_eq!(condition("bgs", "pds"), "service_healthy");
+        assert_eq!(
+            parsed.services["pds"].depends_on,
+            Some(DependsOn::List(vec!["caddy".to_string()]))
+        );
+        parsed.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_pds_blob_limit() {
+        let mut config = ComposeConfig::new();
+        config.add_caddy().add_pds("test.com");
+        config
+            .apply_pds_limits(1024)
+            .apply_pds_limits(50 * 1024 * 1024);
+
+        let env = config.services["pds"].environment.as_ref().unwrap();
+        let limits: Vec<_> = env
+            .iter()
+            .filter(|var| var.starts_with("PDS_BLOB_UPLOAD_LIMIT="))
+            .collect();
+        assert_eq!(limits, vec!["PDS_BLOB_UPLOAD_LIMIT=52428800"]);
+        assert!(config.services["caddy"].environment.is_none());
+    }
+
+    #[test]
+    fn test_resolve_environment() -> Result<()> {
+        let mut config = ComposeConfig::new();
+        config.add_pds("test.com");
+
+        let env = HashMap::from([("PDS_JWT_SECRET".to_string(), "jwt".to_string())]);
+        let resolved = config.resolve_environment("pds", &env)?;
+
+        let jwt = resolved.iter().find(|v| v.key == "PDS_JWT_SECRET").unwrap();
+        assert_eq!(jwt.value, "jwt");
+        assert_eq!(jwt.references, vec!["PDS_JWT_SECRET"]);
+        assert!(jwt.missing.is_empty());
+
+        let admin = resolved
+            .iter()
+            .find(|v| v.key == "PDS_ADMIN_PASSWORD")
+            .unwrap();
+        assert_eq!(admin.missing, vec!["PDS_ADMIN_PASSWORD"]);
+
+        assert!(config.resolve_environment("nope", &env).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_feed_generator_via_jetstream() {
+        let mut config = ComposeConfig::new();
+        config
+            .add_jetstream(200)
+            .add_feed_generator("did:plc:test123", FeedSubscription::Jetstream);
+
+        let feed_gen = &config.services["feed-generator"];
+        assert!(feed_gen.environment.as_ref().unwrap().contains(&format!(
+            "FEEDGEN_SUBSCRIPTION_ENDPOINT={}",
+            JETSTREAM_INTERNAL_ENDPOINT
+        )));
+        assert_eq!(
+            feed_gen.depends_on,
+            Some(DependsOn::List(vec!["jetstream".to_string()]))
+        );
+    }
 }
@@ -1,12 +1,42 @@
+use crate::certs::DnsProvider;
+use crate::compose::{DbCredentials, FeedSubscription, Logging};
+use crate::dns::DnsOptions;
+use crate::docker::VersionRequirements;
 use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct Config {
     pub network: NetworkConfig,
     pub storage: StorageConfig,
     pub email: EmailConfig,
+    #[serde(default)]
+    pub ozone: OzoneConfig,
+    #[serde(default)]
+    pub containers: ContainerConfig,
+    #[serde(default)]
+    pub pds: PdsConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub feed: FeedConfig,
+    #[serde(default)]
+    pub dns: DnsConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    /// Oldest docker and Compose versions `start` and `check` accept
+    #[serde(default)]
+    pub requirements: VersionRequirements,
+    /// Per-service overrides of the health endpoint, keyed by service name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub health: HashMap<String, HealthEndpoint>,
+    /// Full image refs keyed by service name; unlisted services use `:latest`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub images: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -14,7 +44,50 @@ pub struct NetworkConfig {
     pub domain: String,
     pub bind_address: String,
     pub use_tls: bool,
+    /// Bind published ports and Caddy on IPv6 as well as `bind_address`
+    #[serde(default)]
+    pub ipv6: bool,
     pub ports: Ports,
+    /// Serve the `test-wss` probe endpoint; unset means only for local
+    /// development setups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test_endpoint: Option<bool>,
+    /// Obtain certificates through a DNS-01 challenge with this provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_provider: Option<DnsProvider>,
+}
+
+impl NetworkConfig {
+    /// The scheme and port clients reach Caddy's sites on: HTTPS with TLS
+    /// on, otherwise plain HTTP. The port is `None` when it is the default.
+    pub fn origin(&self) -> (&'static str, Option<u16>) {
+        let (scheme, port, default_port) = if self.use_tls {
+            ("https", self.ports.https, 443)
+        } else {
+            ("http", self.ports.http, 80)
+        };
+        (scheme, (port != default_port).then_some(port))
+    }
+
+    /// Where clients reach the PDS through Caddy.
+    pub fn pds_url(&self) -> String {
+        self.service_url("pds")
+    }
+
+    /// Where clients reach `service`'s subdomain through Caddy.
+    pub fn service_url(&self, service: &str) -> String {
+        match self.origin() {
+            (scheme, Some(port)) => format!("{}://{}.{}:{}", scheme, service, self.domain, port),
+            (scheme, None) => format!("{}://{}.{}", scheme, service, self.domain),
+        }
+    }
+
+    /// Whether the Caddyfile should include the `test-wss` endpoint.
+    pub fn test_endpoint_enabled(&self) -> bool {
+        self.test_endpoint.unwrap_or_else(|| {
+            !self.use_tls || self.domain == "localhost" || self.domain.ends_with(".localhost")
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -39,12 +112,330 @@ pub struct EmailConfig {
     pub admin_email: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct OzoneConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_did: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub admin_dids: Vec<String>,
+}
+
+/// Settings stamped onto generated compose services.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ContainerConfig {
+    /// Run services read-only with dropped capabilities and no-new-privileges
+    #[serde(default)]
+    pub harden: bool,
+    /// Log driver for every service; rotated json-file logs by default
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// CPU and memory caps, keyed by service name (e.g. appview, db, bgs)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub resources: HashMap<String, ResourceLimit>,
+    /// `uid[:gid]` (or user name) for the pds and bgs containers;
+    /// unset keeps each image's default user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// Limits for one service; unset limits leave the service uncapped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ResourceLimit {
+    /// CPU cores, e.g. `0.5`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    /// Memory in Docker's notation, e.g. `"512m"` or `"2g"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
+
+impl ResourceLimit {
+    fn validate(&self, service: &str) -> Result<()> {
+        if let Some(cpus) = self.cpus {
+            if !cpus.is_finite() || cpus <= 0.0 {
+                return Err(Error::Config(format!(
+                    "CPU limit for {} must be positive, got {}",
+                    service, cpus
+                )));
+            }
+        }
+        if let Some(memory) = &self.memory {
+            // Docker takes an optional b, k, m or g unit in any case, with
+            // a trailing b allowed after the others (e.g. 512mb, 2GB)
+            let lower = memory.to_ascii_lowercase();
+            let number = lower.strip_suffix('b').unwrap_or(&lower);
+            let number = number.strip_suffix(['k', 'm', 'g']).unwrap_or(number);
+            let valid = !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && number.parse::<f64>().is_ok_and(|n| n > 0.0);
+            if !valid {
+                return Err(Error::Config(format!(
+                    "Memory limit for {} must look like 512m or 2g, got {:?}",
+                    service, memory
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "driver", rename_all = "kebab-case")]
+pub enum LoggingConfig {
+    /// Leave Docker's daemon-wide logging settings in place
+    Docker,
+    JsonFile {
+        #[serde(default = "default_log_max_size")]
+        max_size: String,
+        #[serde(default = "default_log_max_file")]
+        max_file: u32,
+    },
+    Journald,
+}
+
+impl LoggingConfig {
+    /// The compose `logging` block, or `None` to leave services untouched.
+    pub fn compose_logging(&self) -> Option<Logging> {
+        match self {
+            Self::Docker => None,
+            Self::JsonFile { max_size, max_file } => Some(Logging::json_file(max_size, *max_file)),
+            Self::Journald => Some(Logging::journald()),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self::JsonFile {
+            max_size: default_log_max_size(),
+            max_file: default_log_max_file(),
+        }
+    }
+}
+
+fn default_log_max_size() -> String {
+    "10m".into()
+}
+
+fn default_log_max_file() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PdsConfig {
+    /// Largest accepted blob upload, as bytes or a size like `"50MiB"`.
+    /// Defaults to the PDS's own 5MiB limit.
+    #[serde(default = "default_blob_upload_limit")]
+    pub blob_upload_limit: String,
+}
+
+impl PdsConfig {
+    pub fn blob_upload_limit_bytes(&self) -> Result<u64> {
+        parse_byte_size(&self.blob_upload_limit)
+    }
+}
+
+impl Default for PdsConfig {
+    fn default() -> Self {
+        Self {
+            blob_upload_limit: default_blob_upload_limit(),
+        }
+    }
+}
+
+fn default_blob_upload_limit() -> String {
+    "5MiB".into()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct FeedConfig {
+    /// `"bgs"` (default) or `"jetstream"`
+    #[serde(default)]
+    pub subscription: FeedSubscription,
+}
+
+/// DNS lookups made by `check`, kept separate from HTTP timeouts.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DnsConfig {
+    /// Seconds to wait for each A/AAAA query
+    #[serde(default = "default_dns_timeout_secs")]
+    pub timeout_secs: u64,
+    /// How many times a timed-out query is retried
+    #[serde(default)]
+    pub retries: u32,
+}
+
+impl DnsConfig {
+    pub fn options(&self) -> DnsOptions {
+        DnsOptions {
+            timeout: Duration::from_secs(self.timeout_secs),
+            retries: self.retries,
+        }
+    }
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_dns_timeout_secs(),
+            retries: 0,
+        }
+    }
+}
+
+fn default_dns_timeout_secs() -> u64 {
+    2
+}
+
+/// The Postgres service backing the appview.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DatabaseConfig {
+    /// Tag of the `postgres` image; changing the major version needs a
+    /// data migration
+    #[serde(default = "default_postgres_tag")]
+    pub postgres_tag: String,
+    #[serde(default = "default_db_user")]
+    pub user: String,
+    /// The generated secret in `config/secrets.toml` is used when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The appview's database
+    #[serde(default = "default_db_name")]
+    pub name: String,
+}
+
+impl DatabaseConfig {
+    pub fn postgres_image(&self) -> String {
+        format!("postgres:{}", self.postgres_tag)
+    }
+
+    pub fn credentials(&self) -> DbCredentials {
+        let default = DbCredentials::default();
+        DbCredentials {
+            user: self.user.clone(),
+            password: self.password.clone().unwrap_or(default.password),
+            name: self.name.clone(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let is_identifier = |value: &str| {
+            !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !is_identifier(&self.user) || !is_identifier(&self.name) {
+            return Err(Error::Config(format!(
+                "Database user and name may only contain letters, digits and _ (got {:?}, {:?})",
+                self.user, self.name
+            )));
+        }
+
+        // The password goes into connection URLs and compose interpolation
+        if let Some(password) = &self.password {
+            let url_safe = |c: char| c.is_ascii_alphanumeric() || "-._~".contains(c);
+            if password.is_empty() || !password.chars().all(url_safe) {
+                return Err(Error::Config(
+                    "Database password may only contain letters, digits and -._~".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            postgres_tag: default_postgres_tag(),
+            user: default_db_user(),
+            password: None,
+            name: default_db_name(),
+        }
+    }
+}
+
+fn default_postgres_tag() -> String {
+    "15-alpine".into()
+}
+
+fn default_db_user() -> String {
+    "postgres".into()
+}
+
+fn default_db_name() -> String {
+    "appview".into()
+}
+
+/// Webhook for health state changes seen by `monitor`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotificationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// How long a new state must hold before it is reported
+    #[serde(default = "default_debounce_secs")]
+    pub debounce_secs: u64,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            debounce_secs: default_debounce_secs(),
+        }
+    }
+}
+
+fn default_debounce_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthEndpoint {
+    pub path: String,
+    #[serde(default = "default_expected_status")]
+    pub expected_status: u16,
+}
+
+fn default_expected_status() -> u16 {
+    200
+}
+
 impl Config {
     pub fn load(path: impl AsRef<Path>) -> Result<Self> {
         let content = std::fs::read_to_string(path.as_ref())
             .map_err(|e| Error::Config(format!("Failed to read config file: {e}")))?;
 
-        toml::from_str(&content).map_err(|e| Error::Config(format!("Failed to parse config: {e}")))
+        let mut config: Self = toml::from_str(&content)
+            .map_err(|e| Error::Config(format!("Failed to parse config: {e}")))?;
+        config.network.domain = normalize_domain(&config.network.domain)?;
+        Ok(config)
+    }
+
+    /// Like `load`, but `ATC_DOMAIN`, `ATC_BIND_ADDRESS`, `ATC_USE_TLS` and
+    /// `ATC_CERT_EMAIL` override the file when set to a non-empty value.
+    pub fn load_with_env(path: impl AsRef<Path>) -> Result<Self> {
+        let mut config = Self::load(path)?;
+        config.apply_env_overrides(|key| std::env::var(key).ok())?;
+        Ok(config)
+    }
+
+    fn apply_env_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let var = |key: &str| lookup(key).filter(|value| !value.is_empty());
+
+        if let Some(domain) = var("ATC_DOMAIN") {
+            self.network.domain = normalize_domain(&domain)?;
+        }
+        if let Some(bind_address) = var("ATC_BIND_ADDRESS") {
+            self.network.bind_address = bind_address;
+        }
+        if let Some(use_tls) = var("ATC_USE_TLS") {
+            self.network.use_tls = use_tls.parse().map_err(|_| {
+                Error::Config(format!("ATC_USE_TLS must be true or false, got {use_tls}"))
+            })?;
+        }
+        if let Some(cert_email) = var("ATC_CERT_EMAIL") {
+            self.email.cert_email = cert_email;
+        }
+        Ok(())
     }
 
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
@@ -59,7 +450,13 @@ impl Config {
         Ok(())
     }
 
+    /// Validates the config, logging non-fatal problems as warnings.
     pub fn validate(&self) -> Result<()> {
+        self.validate_with(false)
+    }
+
+    /// Like `validate`, but `strict` turns warnings into errors.
+    pub fn validate_with(&self, strict: bool) -> Result<()> {
         if self.network.domain.is_empty() {
             return Err(Error::Config("Domain cannot be empty".into()));
         }
@@ -77,8 +474,157 @@ impl Config {
             return Err(Error::Config("Port numbers must be unique".into()));
         }
 
+        self.pds.blob_upload_limit_bytes()?;
+
+        for (service, limit) in &self.containers.resources {
+            limit.validate(service)?;
+        }
+
+        if let Some(user) = &self.containers.user {
+            let valid = user.split(':').count() <= 2
+                && user
+                    .split(':')
+                    .all(|part| !part.is_empty() && !part.contains(char::is_whitespace));
+            if !valid {
+                return Err(Error::Config(format!(
+                    "Container user must look like 1000 or 1000:1000, got {:?}",
+                    user
+                )));
+            }
+        }
+
+        let tag = &self.database.postgres_tag;
+        if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ':') {
+            return Err(Error::Config(format!(
+                "Invalid Postgres tag {:?} (expected e.g. 15-alpine)",
+                tag
+            )));
+        }
+        self.database.validate()?;
+
+        for (service, image) in &self.images {
+            if image.is_empty() || image.contains(char::is_whitespace) {
+                return Err(Error::Config(format!(
+                    "Invalid image {:?} for {} (expected e.g. ghcr.io/bluesky-social/pds:v0.4.0)",
+                    image, service
+                )));
+            }
+        }
+
+        for warning in self.privileged_port_warnings(is_root()) {
+            if strict {
+                return Err(Error::Config(warning));
+            }
+            warn!("{}", warning);
+        }
+
         Ok(())
     }
+
+    /// Ports below 1024 can't be bound by rootless Docker, which only shows
+    /// up as a failure at `docker compose up`.
+    pub fn privileged_port_warnings(&self, is_root: bool) -> Vec<String> {
+        if is_root {
+            return Vec::new();
+        }
+
+        let ports = &self.network.ports;
+        [
+            ("http", ports.http),
+            ("https", ports.https),
+            ("pds", ports.pds),
+            ("plc", ports.plc),
+        ]
+        .into_iter()
+        .filter(|(_, port)| *port < 1024)
+        .map(|(name, port)| {
+            format!(
+                "{} port {} is privileged and atc is not running as root; \
+                 use a port >= 1024 or rootful Docker",
+                name, port
+            )
+        })
+        .collect()
+    }
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+/// Reduces user input like `https://Example.com/` to a bare `example.com`.
+///
+/// A `:port` is rejected: the domain is reused in service hostnames and Caddy
+/// site addresses, so ports belong in `[network.ports]` instead.
+pub fn normalize_domain(input: &str) -> Result<String> {
+    let mut domain = input.trim();
+    if let Some((_, rest)) = domain.split_once("://") {
+        domain = rest;
+    }
+    if let Some((host, _)) = domain.split_once(['/', '?', '#']) {
+        domain = host;
+    }
+
+    if domain.contains(':') {
+        return Err(Error::Config(format!(
+            "Domain must not include a port, set it under [network.ports]: {:?}",
+            input
+        )));
+    }
+    let host = domain.trim_end_matches('.').to_ascii_lowercase();
+
+    if host.is_empty() {
+        return Err(Error::Config(format!("Invalid domain: {:?}", input)));
+    }
+
+    let valid_host = host.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    if !valid_host {
+        return Err(Error::Config(format!("Invalid domain: {:?}", input)));
+    }
+
+    Ok(host)
+}
+
+/// Parses `"1048576"`, `"500kb"`, `"50MiB"` etc. into bytes. Decimal units
+/// (kb, mb, gb) are powers of 1000, binary units (kib, mib, gib) of 1024.
+pub fn parse_byte_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "kib" => 1 << 10,
+        "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        _ => {
+            return Err(Error::Config(format!("Invalid byte size: {:?}", input)));
+        }
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| Error::Config(format!("Invalid byte size: {:?}", input)))
 }
 
 impl Default for NetworkConfig {
@@ -87,7 +633,10 @@ impl Default for NetworkConfig {
             domain: "localhost".into(),
             bind_address: "0.0.0.0".into(),
             use_tls: true,
+            ipv6: false,
             ports: Ports::default(),
+            test_endpoint: None,
+            dns_provider: None,
         }
     }
 }
@@ -128,6 +677,7 @@ mod tests {
     use super::*;
     use std::fs;
     use tempfile::tempdir;
+    use test_case::test_case;
 
     #[test]
     fn test_default_config() {
@@ -137,6 +687,20 @@ mod tests {
         assert!(config.storage.persist_data);
     }
 
+    #[test]
+    fn test_version_requirements() {
+        let requirements: VersionRequirements = toml::from_str("docker = \"24.0\"\n").unwrap();
+        assert_eq!(requirements.docker, "24.0.0".parse().unwrap());
+        assert_eq!(requirements.compose, VersionRequirements::default().compose);
+        assert!(requirements.compose > "1.29.2".parse().unwrap());
+
+        assert!(toml::from_str::<VersionRequirements>("docker = \"latest\"\n").is_err());
+        assert_eq!(
+            Config::default().requirements,
+            VersionRequirements::default()
+        );
+    }
+
     #[test]
     fn test_config_validation() {
         let mut config = Config::default();
@@ -152,6 +716,198 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_resource_limit_validation() {
+        let mut config = Config::default();
+        config.containers.resources.insert(
+            "db".to_string(),
+            ResourceLimit {
+                cpus: Some(0.5),
+                memory: Some("512m".into()),
+            },
+        );
+        assert!(config.validate().is_ok());
+
+        for memory in ["1.5g", "512mb", "2GB", "1024", "64K"] {
+            config.containers.resources.insert(
+                "db".to_string(),
+                ResourceLimit {
+                    cpus: None,
+                    memory: Some(memory.into()),
+                },
+            );
+            assert!(config.validate().is_ok(), "{}", memory);
+        }
+
+        for (cpus, memory) in [
+            (Some(0.0), None),
+            (None, Some("lots")),
+            (None, Some("5tb")),
+            (None, Some("1.2.3m")),
+            (None, Some("mb")),
+            (None, Some("0g")),
+        ] {
+            config.containers.resources.insert(
+                "db".to_string(),
+                ResourceLimit {
+                    cpus,
+                    memory: memory.map(String::from),
+                },
+            );
+            assert!(matches!(config.validate(), Err(Error::Config(_))));
+        }
+    }
+
+    #[test]
+    fn test_pds_url() {
+        let mut network = NetworkConfig {
+            domain: "example.com".into(),
+            ..Default::default()
+        };
+        assert_eq!(network.pds_url(), "https://pds.example.com");
+
+        network.ports.https = 8443;
+        assert_eq!(network.pds_url(), "https://pds.example.com:8443");
+
+        network.use_tls = false;
+        assert_eq!(network.pds_url(), "http://pds.example.com");
+        network.ports.http = 8080;
+        assert_eq!(network.pds_url(), "http://pds.example.com:8080");
+        assert_eq!(
+            network.service_url("feed-generator"),
+            "http://feed-generator.example.com:8080"
+        );
+    }
+
+    #[test]
+    fn test_container_user_validation() {
+        let mut config = Config::default();
+        for user in ["1000", "1000:1000", "atc"] {
+            config.containers.user = Some(user.into());
+            assert!(config.validate().is_ok(), "{}", user);
+        }
+        for user in ["", "1000:", ":1000", "1000:1000:1000", "10 00"] {
+            config.containers.user = Some(user.into());
+            assert!(
+                matches!(config.validate(), Err(Error::Config(_))),
+                "{}",
+                user
+            );
+        }
+    }
+
+    #[test]
+    fn test_image_validation() {
+        let mut config = Config::default();
+        config
+            .images
+            .insert("pds".into(), "ghcr.io/bluesky-social/pds:v0.4.0".into());
+        assert!(config.validate().is_ok());
+
+        for image in ["", "ghcr.io/bluesky-social/pds: v0.4.0"] {
+            config.images.insert("pds".into(), image.into());
+            assert!(matches!(config.validate(), Err(Error::Config(_))));
+        }
+    }
+
+    #[test]
+    fn test_privileged_port_warnings() {
+        let mut config = Config::default();
+        let warnings = config.privileged_port_warnings(false);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("http port 80"));
+        assert!(warnings[1].contains("https port 443"));
+        assert!(config.privileged_port_warnings(true).is_empty());
+
+        config.network.ports.http = 8080;
+        config.network.ports.https = 8443;
+        assert!(config.privileged_port_warnings(false).is_empty());
+    }
+
+    #[test_case("https://Example.com/", "example.com" ; "scheme and trailing slash")]
+    #[test_case("  example.com.  ", "example.com" ; "whitespace and trailing dot")]
+    #[test_case("http://bsky.example.com/xrpc?x=1", "bsky.example.com" ; "path and query")]
+    #[test_case("127.0.0.1", "127.0.0.1" ; "ip address")]
+    fn test_normalize_domain(input: &str, expected: &str) {
+        assert_eq!(normalize_domain(input).unwrap(), expected);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("https:///" ; "scheme only")]
+    #[test_case("exa mple.com" ; "embedded space")]
+    #[test_case("example.com:http" ; "bad port")]
+    #[test_case("https://example.com:8443/" ; "port")]
+    fn test_normalize_domain_rejects(input: &str) {
+        assert!(matches!(normalize_domain(input), Err(Error::Config(_))));
+    }
+
+    #[test_case("1048576", 1_048_576 ; "plain bytes")]
+    #[test_case("500kb", 500_000 ; "decimal kilobytes")]
+    #[test_case("50MiB", 50 * 1024 * 1024 ; "binary megabytes")]
+    #[test_case(" 2 GB ", 2_000_000_000 ; "spaced gigabytes")]
+    fn test_parse_byte_size(input: &str, expected: u64) {
+        assert_eq!(parse_byte_size(input).unwrap(), expected);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("MiB" ; "unit only")]
+    #[test_case("5 parsecs" ; "unknown unit")]
+    #[test_case("-5mb" ; "negative")]
+    fn test_parse_byte_size_rejects(input: &str) {
+        assert!(matches!(parse_byte_size(input), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_load_normalizes_domain() -> Result<()> {
+        let dir = tempdir()?;
+        let config_path = dir.path().join("config.toml");
+
+        let mut config = Config::default();
+        config.network.domain = "https://Example.com/".into();
+        config.save(&config_path)?;
+
+        assert_eq!(Config::load(&config_path)?.network.domain, "example.com");
+        Ok(())
+    }
+
+    #[test]
+    fn test_test_endpoint_defaults() {
+        let mut network = NetworkConfig::default();
+        assert!(network.test_endpoint_enabled());
+
+        network.domain = "example.com".into();
+        assert!(!network.test_endpoint_enabled());
+
+        network.test_endpoint = Some(true);
+        assert!(network.test_endpoint_enabled());
+    }
+
+    #[test]
+    fn test_env_overrides() -> Result<()> {
+        let env = HashMap::from([
+            ("ATC_DOMAIN", "Override.com"),
+            ("ATC_USE_TLS", "false"),
+            ("ATC_BIND_ADDRESS", ""),
+            ("ATC_CERT_EMAIL", "certs@override.com"),
+        ]);
+        let lookup = |key: &str| env.get(key).map(|value| value.to_string());
+
+        let mut config = Config::default();
+        config.apply_env_overrides(lookup)?;
+        assert_eq!(config.network.domain, "override.com");
+        assert!(!config.network.use_tls);
+        assert_eq!(
+            config.network.bind_address,
+            Config::default().network.bind_address
+        );
+        assert_eq!(config.email.cert_email, "certs@override.com");
+
+        let result =
+            config.apply_env_overrides(|key| (key == "ATC_USE_TLS").then(|| "yes".to_string()));
+        assert!(matches!(result, Err(Error::Config(msg)) if msg.contains("ATC_USE_TLS")));
+        Ok(())
+    }
+
     #[test]
     fn test_config_save_and_load() -> Result<()> {
         let dir = tempdir()?;
@@ -173,12 +929,15 @@ mod tests {
                 domain: "test.com".into(),
                 bind_address: "127.0.0.1".into(),
                 use_tls: false,
+                ipv6: true,
                 ports: Ports {
                     http: 8080,
                     https: 8443,
                     pds: 3000,
                     plc: 3001,
                 },
+                test_endpoint: Some(false),
+                dns_provider: Some(DnsProvider::Cloudflare),
             },
             storage: StorageConfig {
                 data_dir: PathBuf::from("/tmp/data"),
@@ -190,6 +949,57 @@ mod tests {
                 cert_email: "cert@test.com".into(),
                 admin_email: "admin@test.com".into(),
             },
+            ozone: OzoneConfig {
+                server_did: Some("did:plc:server".into()),
+                admin_dids: vec!["did:plc:admin".into()],
+            },
+            containers: ContainerConfig {
+                harden: true,
+                logging: LoggingConfig::Journald,
+                resources: HashMap::from([(
+                    "appview".to_string(),
+                    ResourceLimit {
+                        cpus: Some(1.5),
+                        memory: Some("2g".into()),
+                    },
+                )]),
+                user: Some("1000:1000".into()),
+            },
+            pds: PdsConfig {
+                blob_upload_limit: "100MB".into(),
+            },
+            notifications: NotificationConfig {
+                webhook_url: Some("https://hooks.test.com/atc".into()),
+                debounce_secs: 5,
+            },
+            feed: FeedConfig {
+                subscription: FeedSubscription::Jetstream,
+            },
+            dns: DnsConfig {
+                timeout_secs: 5,
+                retries: 2,
+            },
+            health: HashMap::from([(
+                "pds".to_string(),
+                HealthEndpoint {
+                    path: "/ready".into(),
+                    expected_status: 204,
+                },
+            )]),
+            database: DatabaseConfig {
+                postgres_tag: "16.2-alpine".into(),
+                user: "bsky".into(),
+                password: Some("s3cret-pass".into()),
+                name: "bsky_appview".into(),
+            },
+            requirements: VersionRequirements {
+                docker: "24.0.0".parse()?,
+                compose: "2.20.0".parse()?,
+            },
+            images: HashMap::from([(
+                "pds".to_string(),
+                "ghcr.io/bluesky-social/pds:v0.4.0".to_string(),
+            )]),
         };
 
         let dir = tempdir()?;
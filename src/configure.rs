@@ -0,0 +1,340 @@
+use crate::compose::FeedSubscription;
+use crate::config::{normalize_domain, Config};
+use crate::error::{Error, Result};
+use dialoguer::{Confirm, Input};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Source of answers for the configure form.
+pub trait Prompter {
+    fn input(&mut self, prompt: &str, default: &str) -> Result<String>;
+    fn confirm(&mut self, prompt: &str, default: bool) -> Result<bool>;
+    fn warn(&mut self, message: &str);
+}
+
+/// Prompts on the terminal.
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn input(&mut self, prompt: &str, default: &str) -> Result<String> {
+        Input::<String>::new()
+            .with_prompt(prompt)
+            .default(default.to_string())
+            .allow_empty(true)
+            .interact_text()
+            .map_err(|e| Error::Config(format!("Failed to read input: {}", e)))
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> Result<bool> {
+        Confirm::new()
+            .with_prompt(prompt)
+            .default(default)
+            .interact()
+            .map_err(|e| Error::Config(format!("Failed to read input: {}", e)))
+    }
+
+    fn warn(&mut self, message: &str) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Walks the common config fields, pre-filled from `current`, and returns
+/// the edited config once it validates.
+pub fn edit_config(current: &Config, prompter: &mut impl Prompter) -> Result<Config> {
+    let mut config = current.clone();
+
+    config.network.domain = loop {
+        let input = prompter.input("Domain", &config.network.domain)?;
+        match normalize_domain(&input) {
+            Ok(domain) => break domain,
+            Err(e) => prompter.warn(&e.to_string()),
+        }
+    };
+    config.email.cert_email = prompter.input("Certificate email", &config.email.cert_email)?;
+    config.email.admin_email = prompter.input("Admin email", &config.email.admin_email)?;
+    config.email.smtp_url = prompter.input("SMTP URL", &config.email.smtp_url)?;
+
+    let ports = &mut config.network.ports;
+    ports.http = prompt_parsed(prompter, "HTTP port", ports.http)?;
+    ports.https = prompt_parsed(prompter, "HTTPS port", ports.https)?;
+    ports.pds = prompt_parsed(prompter, "PDS port", ports.pds)?;
+    ports.plc = prompt_parsed(prompter, "PLC port", ports.plc)?;
+
+    config.network.use_tls = prompter.confirm("Use TLS?", config.network.use_tls)?;
+    config.network.ipv6 = prompter.confirm("Also listen on IPv6?", config.network.ipv6)?;
+    config.containers.harden = prompter.confirm("Harden containers?", config.containers.harden)?;
+
+    // Optional services; declining Ozone clears its settings
+    if prompter.confirm("Set up Ozone?", config.ozone.server_did.is_some())? {
+        let current = config.ozone.server_did.clone().unwrap_or_default();
+        config.ozone.server_did = Some(loop {
+            let did = prompter.input("Ozone server DID", &current)?;
+            if did.starts_with("did:") {
+                break did;
+            }
+            prompter.warn(&format!(
+                "Expected a DID such as did:plc:..., got {:?}",
+                did
+            ));
+        });
+        let admins = prompter.input(
+            "Ozone admin DIDs (comma-separated)",
+            &config.ozone.admin_dids.join(","),
+        )?;
+        config.ozone.admin_dids = admins
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+    } else {
+        config.ozone = Default::default();
+    }
+
+    let jetstream = prompter.confirm(
+        "Feed generator subscribes through Jetstream?",
+        config.feed.subscription == FeedSubscription::Jetstream,
+    )?;
+    config.feed.subscription = if jetstream {
+        FeedSubscription::Jetstream
+    } else {
+        FeedSubscription::Bgs
+    };
+
+    config.validate()?;
+    Ok(config)
+}
+
+/// Opens a file for the user to edit, returning once they are done.
+pub trait Editor {
+    fn edit(&mut self, path: &Path) -> Result<()>;
+}
+
+/// Runs `$EDITOR`, which may include arguments such as `code --wait`.
+pub struct EnvEditor {
+    command: String,
+}
+
+impl EnvEditor {
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("EDITOR") {
+            Ok(command) if !command.trim().is_empty() => Ok(Self { command }),
+            _ => Err(Error::Config(
+                "EDITOR is not set; set it or use configure instead".into(),
+            )),
+        }
+    }
+}
+
+impl Editor for EnvEditor {
+    fn edit(&mut self, path: &Path) -> Result<()> {
+        let mut parts = self.command.split_whitespace();
+        let program = parts.next().unwrap_or_default();
+        let status = Command::new(program)
+            .args(parts)
+            .arg(path)
+            .status()
+            .map_err(|e| Error::Config(format!("Failed to run {}: {}", program, e)))?;
+        if !status.success() {
+            return Err(Error::Config(format!("{} exited with {}", program, status)));
+        }
+        Ok(())
+    }
+}
+
+/// Lets the user edit the config file at `path` in place.
+///
+/// The original is kept as a `.bak` file while editing. If the result fails
+/// to parse or validate, the error is shown and the user can edit again;
+/// declining restores the original.
+pub fn edit_config_file(
+    path: &Path,
+    editor: &mut impl Editor,
+    prompter: &mut impl Prompter,
+) -> Result<Config> {
+    let backup = backup_path(path);
+    std::fs::copy(path, &backup)?;
+
+    let restore = |error: Error| -> Result<Config> {
+        std::fs::rename(&backup, path)?;
+        Err(error)
+    };
+
+    loop {
+        if let Err(e) = editor.edit(path) {
+            return restore(e);
+        }
+
+        // Raw load: the user is checking the file they just edited
+        match Config::load(path).and_then(|config| config.validate().map(|()| config)) {
+            Ok(config) => {
+                std::fs::remove_file(&backup)?;
+                return Ok(config);
+            }
+            Err(e) => {
+                prompter.warn(&e.to_string());
+                if !prompter.confirm("Edit again?", true)? {
+                    return restore(e);
+                }
+            }
+        }
+    }
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+fn prompt_parsed<T>(prompter: &mut impl Prompter, prompt: &str, default: T) -> Result<T>
+where
+    T: FromStr + ToString,
+{
+    loop {
+        let input = prompter.input(prompt, &default.to_string())?;
+        match input.trim().parse() {
+            Ok(value) => return Ok(value),
+            Err(_) => prompter.warn(&format!("Invalid value for {}: {:?}", prompt, input)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Replays answers in order; an empty answer accepts the default.
+    struct ScriptedPrompter {
+        answers: VecDeque<&'static str>,
+        warnings: usize,
+    }
+
+    impl ScriptedPrompter {
+        fn new(answers: &[&'static str]) -> Self {
+            Self {
+                answers: answers.iter().copied().collect(),
+                warnings: 0,
+            }
+        }
+
+        fn next(&mut self, prompt: &str) -> Result<&'static str> {
+            self.answers
+                .pop_front()
+                .ok_or_else(|| Error::Config(format!("No scripted answer for {}", prompt)))
+        }
+    }
+
+    impl Prompter for ScriptedPrompter {
+        fn input(&mut self, prompt: &str, default: &str) -> Result<String> {
+            match self.next(prompt)? {
+                "" => Ok(default.to_string()),
+                answer => Ok(answer.to_string()),
+            }
+        }
+
+        fn confirm(&mut self, prompt: &str, default: bool) -> Result<bool> {
+            match self.next(prompt)? {
+                "" => Ok(default),
+                answer => Ok(answer == "y"),
+            }
+        }
+
+        fn warn(&mut self, _message: &str) {
+            self.warnings += 1;
+        }
+    }
+
+    #[test]
+    fn test_scripted_configure() -> Result<()> {
+        let mut prompter = ScriptedPrompter::new(&[
+            "https://Bsky.Example.com/",
+            "certs@example.com",
+            "",
+            "smtp://mail.example.com:587",
+            "8080",
+            "not-a-port",
+            "8443",
+            "",
+            "",
+            "n",
+            "y",
+            "",
+            "y",
+            "plc:ozone",
+            "did:plc:ozone",
+            "did:plc:alice, did:plc:bob",
+            "y",
+        ]);
+
+        let config = edit_config(&Config::default(), &mut prompter)?;
+
+        assert_eq!(config.network.domain, "bsky.example.com");
+        assert_eq!(config.email.cert_email, "certs@example.com");
+        assert_eq!(config.email.admin_email, "admin@localhost");
+        assert_eq!(config.email.smtp_url, "smtp://mail.example.com:587");
+        assert_eq!(config.network.ports.http, 8080);
+        assert_eq!(config.network.ports.https, 8443);
+        assert_eq!(config.network.ports.pds, 2583);
+        assert!(!config.network.use_tls);
+        assert!(config.network.ipv6);
+        assert!(!config.containers.harden);
+        assert_eq!(config.ozone.server_did.as_deref(), Some("did:plc:ozone"));
+        assert_eq!(
+            config.ozone.admin_dids,
+            vec!["did:plc:alice", "did:plc:bob"]
+        );
+        assert_eq!(config.feed.subscription, FeedSubscription::Jetstream);
+        assert_eq!(prompter.warnings, 2);
+        Ok(())
+    }
+
+    /// Overwrites the file with each scripted content in turn.
+    struct ScriptedEditor {
+        contents: VecDeque<&'static str>,
+    }
+
+    impl Editor for ScriptedEditor {
+        fn edit(&mut self, path: &Path) -> Result<()> {
+            let content = self
+                .contents
+                .pop_front()
+                .ok_or_else(|| Error::Config("Editor opened too many times".into()))?;
+            std::fs::write(path, content)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_invalid_edit_restores_original() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        Config::default().save(&path)?;
+        let original = std::fs::read_to_string(&path)?;
+
+        let mut editor = ScriptedEditor {
+            contents: VecDeque::from(["[network\ndomain = ", "still = [broken"]),
+        };
+        let mut prompter = ScriptedPrompter::new(&["y", "n"]);
+
+        assert!(matches!(
+            edit_config_file(&path, &mut editor, &mut prompter),
+            Err(Error::Config(_))
+        ));
+        assert_eq!(prompter.warnings, 2);
+        assert_eq!(std::fs::read_to_string(&path)?, original);
+        assert!(!backup_path(&path).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_configure_rejects_duplicate_ports() {
+        let mut prompter =
+            ScriptedPrompter::new(&["", "", "", "", "443", "", "", "", "", "", "", "", ""]);
+        assert!(matches!(
+            edit_config(&Config::default(), &mut prompter),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+/// Unified diff of `old` against `new`, labelled with `path`, or `None`
+/// when they are the same.
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let label = path.display().to_string();
+    Some(
+        similar::TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(3)
+            .header(&format!("a/{}", label), &format!("b/{}", label))
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff(Path::new("Caddyfile"), "a\nb\nc\n", "a\nx\nc\n").unwrap();
+        assert!(diff.contains("--- a/Caddyfile"));
+        assert!(diff.contains("-b\n"));
+        assert!(diff.contains("+x\n"));
+        assert_eq!(
+            unified_diff(Path::new("Caddyfile"), "same\n", "same\n"),
+            None
+        );
+    }
+}
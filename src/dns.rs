@@ -1,16 +1,114 @@
 use crate::error::{Error, Result};
+use crate::retry::{poll_until, Backoff};
+use async_trait::async_trait;
 use std::process::Stdio;
+use std::time::Duration;
 use tokio::process::Command;
 use tracing::{debug, instrument};
 
+/// Subdomains Caddy serves, each of which needs its own record.
+pub const SUBDOMAINS: &[&str] = &["pds", "bgs", "appview", "plc"];
+
+/// `dig`'s exit status when no server replied.
+const DIG_NO_REPLY: i32 = 9;
+
+/// How long `check_port` waits for a TCP connection.
+const PORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+}
+
+impl RecordType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::Aaaa => "AAAA",
+        }
+    }
+}
+
+/// Per-query timeout and how many times a timed-out query is retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DnsOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl Default for DnsOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            retries: 0,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Resolver: Sync {
+    /// Makes a single query, returning whether any address came back. A
+    /// query that got no reply fails with an `Io` error of kind `TimedOut`.
+    async fn query(&self, name: &str, record: RecordType) -> Result<bool>;
+}
+
+/// Resolves names by shelling out to `dig`.
+pub struct DigResolver {
+    timeout: Duration,
+}
+
+impl DigResolver {
+    pub fn new(options: DnsOptions) -> Self {
+        Self {
+            timeout: options.timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for DigResolver {
+    async fn query(&self, name: &str, record: RecordType) -> Result<bool> {
+        DnsChecker::dig_check(name, record, self.timeout).await
+    }
+}
+
+/// Whether `name` has an A or AAAA record. Each query is bounded by
+/// `options.timeout` and retried up to `options.retries` times if it times out.
+pub async fn resolves(resolver: &impl Resolver, name: &str, options: DnsOptions) -> Result<bool> {
+    for record in [RecordType::A, RecordType::Aaaa] {
+        for attempt in 0..=options.retries {
+            match tokio::time::timeout(options.timeout, resolver.query(name, record)).await {
+                Ok(Ok(true)) => return Ok(true),
+                Ok(Ok(false)) => break,
+                Ok(Err(Error::Io(e))) if e.kind() == std::io::ErrorKind::TimedOut => debug!(
+                    "{} query for {} got no reply (attempt {})",
+                    record.as_str(),
+                    name,
+                    attempt + 1
+                ),
+                Ok(Err(e)) => return Err(e),
+                Err(_) => debug!(
+                    "{} query for {} timed out after {:?} (attempt {})",
+                    record.as_str(),
+                    name,
+                    options.timeout,
+                    attempt + 1
+                ),
+            }
+        }
+    }
+    Ok(false)
+}
+
 pub struct DnsChecker;
 
 impl DnsChecker {
     #[instrument]
-    pub async fn check_domain(domain: &str) -> Result<bool> {
+    pub async fn check_domain(domain: &str, options: DnsOptions) -> Result<bool> {
         debug!("Checking DNS for domain: {}", domain);
 
-        match Self::dig_check(domain).await {
+        match resolves(&DigResolver::new(options), domain, options).await {
             Ok(true) => {
                 debug!("Main domain resolves successfully");
                 Ok(true)
@@ -26,34 +124,75 @@ impl DnsChecker {
         }
     }
 
+    /// Subdomains of `domain` that don't resolve yet.
+    #[instrument]
+    pub async fn check_subdomains(domain: &str, options: DnsOptions) -> Result<Vec<String>> {
+        let mut unresolved = unresolved_names(&DigResolver::new(options), domain, options).await?;
+        unresolved.retain(|name| name != domain);
+        Ok(unresolved)
+    }
+
     #[instrument]
-    async fn dig_check(domain: &str) -> Result<bool> {
+    async fn dig_check(domain: &str, record: RecordType, timeout: Duration) -> Result<bool> {
         let output = Command::new("dig")
             .arg("+short")
-            .arg("+time=2") // 2 second timeout
-            .arg("+tries=1") // Only try once
-            .arg("A")
+            .arg(format!("+time={}", timeout.as_secs().max(1)))
+            .arg("+tries=1") // Retries are handled by `resolves`
+            .arg(record.as_str())
             .arg(domain)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            // `resolves` may give up on the query first
+            .kill_on_drop(true)
             .output()
             .await
             .map_err(|e| Error::Network(format!("Failed to run dig: {}", e)))?;
 
+        match output.status.code() {
+            Some(0) => {}
+            Some(DIG_NO_REPLY) => {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("no reply to {} query for {}", record.as_str(), domain),
+                )))
+            }
+            _ => {
+                return Err(Error::Network(format!(
+                    "dig failed for {}: {}",
+                    domain,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )))
+            }
+        }
+
         let output_str = String::from_utf8_lossy(&output.stdout);
 
-        // Check if we got any IP addresses back
-        let has_ip = !output_str.trim().is_empty()
-            && output_str.lines().any(|line| {
-                line.split('.')
-                    .filter(|octet| octet.parse::<u8>().is_ok())
-                    .count()
-                    == 4
-            });
+        // Check if we got any addresses back (not CNAME targets)
+        let has_ip = output_str
+            .lines()
+            .any(|line| line.trim().parse::<std::net::IpAddr>().is_ok());
 
         Ok(has_ip)
     }
 
+    /// Whether a TCP connection to `host:port` succeeds. Refused, unroutable
+    /// and timed-out connections all count as unreachable.
+    #[instrument]
+    pub async fn check_port(host: &str, port: u16) -> Result<bool> {
+        let connect = tokio::net::TcpStream::connect((host, port));
+        match tokio::time::timeout(PORT_TIMEOUT, connect).await {
+            Ok(Ok(_)) => Ok(true),
+            Ok(Err(e)) => {
+                debug!("Connecting to {}:{} failed: {}", host, port, e);
+                Ok(false)
+            }
+            Err(_) => {
+                debug!("Connecting to {}:{} timed out", host, port);
+                Ok(false)
+            }
+        }
+    }
+
     #[instrument]
     pub async fn check_ssl_test_endpoint(domain: &str) -> Result<bool> {
         debug!("Testing HTTPS endpoint");
@@ -89,19 +228,99 @@ impl DnsChecker {
 
         Ok(output.status.success())
     }
+}
+
+/// The domain and its subdomains that don't resolve with `resolver`.
+pub async fn unresolved_names(
+    resolver: &impl Resolver,
+    domain: &str,
+    options: DnsOptions,
+) -> Result<Vec<String>> {
+    let names = std::iter::once(domain.to_string())
+        .chain(SUBDOMAINS.iter().map(|sub| format!("{}.{}", sub, domain)));
 
-    #[cfg(test)]
-    async fn dig_check_mock(domain: &str) -> Result<bool> {
-        Ok(matches!(
-            domain,
-            "valid.example.com" | "any.valid.example.com"
-        ))
+    let mut unresolved = Vec::new();
+    for name in names {
+        if !resolves(resolver, &name, options).await? {
+            unresolved.push(name);
+        }
     }
+    Ok(unresolved)
+}
+
+/// Polls until the domain and all its subdomains resolve, calling
+/// `on_progress` with the names still pending after each attempt.
+///
+/// Returns `Ok(false)` if `timeout` elapses first.
+pub async fn wait_for_dns(
+    resolver: &impl Resolver,
+    domain: &str,
+    options: DnsOptions,
+    timeout: Duration,
+    backoff: Backoff,
+    on_progress: impl Fn(&[String]),
+) -> Result<bool> {
+    poll_until(timeout, backoff, || async {
+        let unresolved = unresolved_names(resolver, domain, options).await?;
+        if !unresolved.is_empty() {
+            on_progress(&unresolved);
+        }
+        Ok(unresolved.is_empty())
+    })
+    .await
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Resolves nothing until `resolved_after` queries have been made.
+    struct StubResolver {
+        lookups: AtomicUsize,
+        resolved_after: usize,
+    }
+
+    #[async_trait]
+    impl Resolver for StubResolver {
+        async fn query(&self, _name: &str, _record: RecordType) -> Result<bool> {
+            Ok(self.lookups.fetch_add(1, Ordering::SeqCst) >= self.resolved_after)
+        }
+    }
+
+    /// Never answers within any reasonable timeout.
+    struct HangingResolver {
+        queries: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Resolver for HangingResolver {
+        async fn query(&self, _name: &str, _record: RecordType) -> Result<bool> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(true)
+        }
+    }
+
+    /// Fails its first `no_reply` queries the way `dig` does when no server
+    /// answers, then resolves.
+    struct FlakyResolver {
+        queries: AtomicUsize,
+        no_reply: usize,
+    }
+
+    #[async_trait]
+    impl Resolver for FlakyResolver {
+        async fn query(&self, name: &str, _record: RecordType) -> Result<bool> {
+            if self.queries.fetch_add(1, Ordering::SeqCst) < self.no_reply {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("no reply for {}", name),
+                )));
+            }
+            Ok(true)
+        }
+    }
 
     async fn mock_dig_response(domain: &str) -> String {
         match domain {
@@ -119,6 +338,21 @@ pub mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_check_port() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let open = listener.local_addr()?.port();
+        assert!(DnsChecker::check_port("127.0.0.1", open).await?);
+
+        // Nothing listens on a port once its listener is dropped
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await?
+            .local_addr()?
+            .port();
+        assert!(!DnsChecker::check_port("127.0.0.1", closed).await?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_dns_checker() {
         let result = DnsChecker::dig_check_test("google.com").await.unwrap();
@@ -140,4 +374,87 @@ pub mod tests {
             .unwrap();
         assert!(!result, "Should not find IP for invalid domain");
     }
+
+    #[tokio::test]
+    async fn test_wait_for_dns_until_propagated() -> Result<()> {
+        let resolver = StubResolver {
+            lookups: AtomicUsize::new(0),
+            // Every name fails both its A and AAAA query once
+            resolved_after: 2 * (1 + SUBDOMAINS.len()),
+        };
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(5));
+        let progress = AtomicUsize::new(0);
+
+        let resolved = wait_for_dns(
+            &resolver,
+            "example.com",
+            DnsOptions::default(),
+            Duration::from_secs(5),
+            backoff,
+            |pending| {
+                assert!(pending.contains(&"pds.example.com".to_string()));
+                progress.fetch_add(1, Ordering::SeqCst);
+            },
+        )
+        .await?;
+
+        assert!(resolved);
+        assert_eq!(progress.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_dns_times_out() -> Result<()> {
+        let resolver = StubResolver {
+            lookups: AtomicUsize::new(0),
+            resolved_after: usize::MAX,
+        };
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(5));
+
+        let resolved = wait_for_dns(
+            &resolver,
+            "example.com",
+            DnsOptions::default(),
+            Duration::from_millis(20),
+            backoff,
+            |_| {},
+        )
+        .await?;
+        assert!(!resolved);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout_is_honored() -> Result<()> {
+        let resolver = HangingResolver {
+            queries: AtomicUsize::new(0),
+        };
+        let options = DnsOptions {
+            timeout: Duration::from_millis(20),
+            retries: 2,
+        };
+
+        let start = std::time::Instant::now();
+        assert!(!resolves(&resolver, "slow.example.com", options).await?);
+
+        // Three attempts each for A and AAAA, all cut off at the timeout
+        assert_eq!(resolver.queries.load(Ordering::SeqCst), 6);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_reply_is_retried() -> Result<()> {
+        let resolver = FlakyResolver {
+            queries: AtomicUsize::new(0),
+            no_reply: 2,
+        };
+        let options = DnsOptions {
+            timeout: Duration::from_secs(1),
+            retries: 2,
+        };
+        assert!(resolves(&resolver, "pds.example.com", options).await?);
+        assert_eq!(resolver.queries.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
 }
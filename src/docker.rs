@@ -1,21 +1,56 @@
 use crate::error::{Error, Result};
+use crate::logs::{LogStyle, LogWindow};
 use crate::secrets::Secrets;
+use crate::version::Version;
 use async_trait::async_trait;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
 use std::process::Stdio;
-use std::{collections::HashMap, path::Path};
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
-use tracing::{debug, instrument};
+use tokio::sync::OnceCell;
+use tracing::{debug, info, instrument, warn};
+
+/// The Compose invocation found on this machine, probed once per process.
+static COMPOSE: OnceCell<Option<ComposeCommand>> = OnceCell::const_new();
 
 #[async_trait]
-pub trait DockerServiceTrait {
+pub trait DockerServiceTrait: Sync {
     async fn get_service_status(&self) -> Result<HashMap<String, ServiceStatus>>;
+
+    /// Checks that docker and Compose are installed and recent enough.
+    async fn check_dependencies(&self) -> Result<DockerEnvironment>;
+
+    /// Names of the variables set in a running service's environment.
+    async fn env_keys(&self, service: &str) -> Result<Vec<String>>;
+
+    /// Returns which of `keys` are missing from `service`'s running
+    /// environment. Only presence is checked; values are never reported.
+    async fn verify_env(&self, service: &str, keys: &[String]) -> Result<Vec<String>> {
+        let present = self.env_keys(service).await?;
+        Ok(keys
+            .iter()
+            .filter(|key| !present.contains(key))
+            .cloned()
+            .collect())
+    }
 }
 
 #[derive(Debug)]
 pub struct DockerService {
     compose_file: String,
     env_vars: HashMap<String, String>,
+    remove_orphans: bool,
+    force_recreate: bool,
+    log_style: LogStyle,
+    log_window: LogWindow,
+    runner: Arc<dyn CommandRunner>,
+    requirements: VersionRequirements,
 }
 
 #[derive(Debug, Clone)]
@@ -23,13 +58,469 @@ pub struct ServiceStatus {
     pub running: bool,
     pub state: String,
     pub ports: Vec<String>,
+    /// Docker healthcheck state (`healthy`, `unhealthy` or `starting`), or
+    /// `None` if the service defines no healthcheck
+    pub health: Option<String>,
+}
+
+/// Outcome of pulling images for a set of services.
+#[derive(Debug, Default)]
+pub struct PullReport {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Why a `docker-compose pull` failed, judged from its output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PullFailure {
+    RateLimited { retry_after: Option<u64> },
+    Unauthorized,
+    Network,
+    Other,
+}
+
+impl PullFailure {
+    const DEFAULT_RETRY_AFTER: u64 = 30;
+    const MAX_RETRY_AFTER: u64 = 120;
+
+    pub fn classify(output: &str) -> Self {
+        let output = output.to_ascii_lowercase();
+
+        // Not a bare "429", which also turns up in digests, sizes and ports
+        if output.contains("toomanyrequests") || output.contains("too many requests") {
+            return Self::RateLimited {
+                retry_after: parse_retry_after(&output),
+            };
+        }
+
+        if ["unauthorized", "denied", "authentication required"]
+            .iter()
+            .any(|needle| output.contains(needle))
+        {
+            return Self::Unauthorized;
+        }
+
+        if [
+            "timeout",
+            "connection refused",
+            "no such host",
+            "network is unreachable",
+            "tls handshake",
+        ]
+        .iter()
+        .any(|needle| output.contains(needle))
+        {
+            return Self::Network;
+        }
+
+        Self::Other
+    }
+
+    fn describe(self, services: &str) -> String {
+        match self {
+            Self::RateLimited {
+                retry_after: Some(secs),
+            } => format!(
+                "Registry rate limit reached while pulling {}; retry after {}s",
+                services, secs
+            ),
+            Self::RateLimited { retry_after: None } => format!(
+                "Registry rate limit reached while pulling {}; wait before retrying or run `docker login` to raise the limit",
+                services
+            ),
+            Self::Unauthorized => format!(
+                "Registry denied access while pulling {}; check your `docker login` credentials",
+                services
+            ),
+            Self::Network => format!(
+                "Network error while pulling {}; check connectivity to the registry",
+                services
+            ),
+            Self::Other => format!("Failed to pull images for: {}", services),
+        }
+    }
+}
+
+/// Finds a `Retry-After`-style delay in seconds in lowercased pull output.
+fn parse_retry_after(output: &str) -> Option<u64> {
+    ["retry-after:", "retry after"].iter().find_map(|marker| {
+        let rest = &output[output.find(marker)? + marker.len()..];
+        let digits: String = rest
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    })
+}
+
+impl PullReport {
+    /// Summarises failed pulls as one error, calling out rate limits first,
+    /// then auth and network problems.
+    pub fn error(&self) -> Option<Error> {
+        let services = self
+            .failed
+            .iter()
+            .map(|(service, _)| service.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let failure = self
+            .failed
+            .iter()
+            .map(|(_, output)| PullFailure::classify(output))
+            .min_by_key(|failure| match failure {
+                PullFailure::RateLimited { .. } => 0,
+                PullFailure::Unauthorized => 1,
+                PullFailure::Network => 2,
+                PullFailure::Other => 3,
+            })?;
+
+        Some(Error::Docker(failure.describe(&services)))
+    }
+}
+
+/// Minimum tool versions enforced by `check_dependencies_with`, set in
+/// the `[requirements]` config section.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VersionRequirements {
+    pub docker: Version,
+    pub compose: Version,
+}
+
+impl Default for VersionRequirements {
+    /// Compose v2 is required: v1 has no `ps --format json` with the
+    /// `Service`/`State`/`Publishers`/`Health` keys, nor `logs --since/--until`.
+    fn default() -> Self {
+        Self {
+            docker: Version::new(20, 10, 0),
+            compose: Version::new(2, 0, 0),
+        }
+    }
+}
+
+/// How Compose is invoked: the standalone `docker-compose` binary or the
+/// `docker compose` CLI plugin that newer installs ship instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ComposeCommand {
+    #[default]
+    Standalone,
+    Plugin,
+}
+
+impl ComposeCommand {
+    /// Probes for `docker-compose` first, then `docker compose`. The result
+    /// is cached; `None` means neither is installed.
+    pub async fn detect() -> Option<Self> {
+        *COMPOSE
+            .get_or_init(|| async {
+                for candidate in [Self::Standalone, Self::Plugin] {
+                    let probe = candidate.command().arg("version").output().await;
+                    if probe.is_ok_and(|output| output.status.success()) {
+                        debug!("Using {} for Compose", candidate);
+                        return Some(candidate);
+                    }
+                }
+                None
+            })
+            .await
+    }
+
+    /// A command running this Compose, ready for its arguments.
+    pub fn command(self) -> Command {
+        match self {
+            Self::Standalone => Command::new("docker-compose"),
+            Self::Plugin => {
+                let mut cmd = Command::new("docker");
+                cmd.arg("compose");
+                cmd
+            }
+        }
+    }
+}
+
+impl fmt::Display for ComposeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standalone => f.write_str("docker-compose"),
+            Self::Plugin => f.write_str("docker compose"),
+        }
+    }
+}
+
+/// A process for a `CommandRunner` to run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Added to the inherited environment
+    pub envs: HashMap<String, String>,
+}
+
+impl Invocation {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            envs: HashMap::new(),
+        }
+    }
+
+    /// Runs `args` through `compose`.
+    pub fn compose(compose: ComposeCommand, args: Vec<String>) -> Self {
+        match compose {
+            ComposeCommand::Standalone => Self::new("docker-compose", args),
+            ComposeCommand::Plugin => Self::new(
+                "docker",
+                std::iter::once("compose".to_string()).chain(args).collect(),
+            ),
+        }
+    }
+
+    pub fn with_envs(mut self, envs: HashMap<String, String>) -> Self {
+        self.envs = envs;
+        self
+    }
 }
 
+/// What a captured process produced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs the processes `DockerService` shells out to, so tests can swap in
+/// `mock::MockRunner` instead of a real Docker.
+#[async_trait]
+pub trait CommandRunner: fmt::Debug + Send + Sync {
+    /// The installed Compose, or `None` if there is none.
+    async fn compose(&self) -> Option<ComposeCommand> {
+        ComposeCommand::detect().await
+    }
+
+    /// Runs with stdout and stderr inherited, returning whether it succeeded.
+    async fn status(&self, invocation: &Invocation) -> Result<bool>;
+
+    /// Runs with stdout and stderr captured.
+    async fn output(&self, invocation: &Invocation) -> Result<CommandOutput>;
+
+    /// Runs with stdout inherited and stderr both passed through and
+    /// captured, so errors can be inspected after the user has seen them.
+    async fn output_teed(&self, invocation: &Invocation) -> Result<CommandOutput>;
+
+    /// Runs with stderr inherited, handing each stdout line to `on_line` as
+    /// it arrives. Returns whether it succeeded.
+    async fn stream_lines(
+        &self,
+        invocation: &Invocation,
+        on_line: &mut (dyn for<'l> FnMut(&'l str) + Send),
+    ) -> Result<bool>;
+}
+
+/// Runs processes for real.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl ProcessRunner {
+    fn command(invocation: &Invocation) -> Command {
+        let mut cmd = Command::new(&invocation.program);
+        cmd.args(&invocation.args).envs(&invocation.envs);
+        cmd
+    }
+}
+
+#[async_trait]
+impl CommandRunner for ProcessRunner {
+    async fn status(&self, invocation: &Invocation) -> Result<bool> {
+        let mut cmd = Self::command(invocation);
+        cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+
+        debug!("Running command: {:?}", cmd);
+        Ok(cmd.status().await?.success())
+    }
+
+    async fn output(&self, invocation: &Invocation) -> Result<CommandOutput> {
+        let mut cmd = Self::command(invocation);
+
+        debug!("Running command: {:?}", cmd);
+        let output = cmd.output().await?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    async fn output_teed(&self, invocation: &Invocation) -> Result<CommandOutput> {
+        let mut cmd = Self::command(invocation);
+        cmd.stdout(Stdio::inherit()).stderr(Stdio::piped());
+
+        debug!("Running command: {:?}", cmd);
+        let mut child = cmd.spawn()?;
+        let mut stderr = String::new();
+        if let Some(pipe) = child.stderr.take() {
+            let mut lines = BufReader::new(pipe).lines();
+            while let Some(line) = lines.next_line().await? {
+                eprintln!("{}", line);
+                stderr.push_str(&line);
+                stderr.push('\n');
+            }
+        }
+
+        Ok(CommandOutput {
+            success: child.wait().await?.success(),
+            stdout: String::new(),
+            stderr,
+        })
+    }
+
+    async fn stream_lines(
+        &self,
+        invocation: &Invocation,
+        on_line: &mut (dyn for<'l> FnMut(&'l str) + Send),
+    ) -> Result<bool> {
+        let mut cmd = Self::command(invocation);
+        cmd.stdout(Stdio::piped()).stderr(Stdio::inherit());
+
+        debug!("Running command: {:?}", cmd);
+        let mut child = cmd.spawn()?;
+        if let Some(pipe) = child.stdout.take() {
+            let mut lines = BufReader::new(pipe).lines();
+            while let Some(line) = lines.next_line().await? {
+                on_line(&line);
+            }
+        }
+        Ok(child.wait().await?.success())
+    }
+}
+
+/// What `check_dependencies` found out about the local Docker install.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DockerEnvironment {
+    /// The daemon runs as an unprivileged user
+    pub rootless: bool,
+    pub compose: ComposeCommand,
+}
+
+impl DockerEnvironment {
+    /// Where a rootless daemon listens: `DOCKER_HOST` if set, otherwise
+    /// `$XDG_RUNTIME_DIR/docker.sock`.
+    pub fn rootless_socket() -> Option<String> {
+        if let Ok(host) = std::env::var("DOCKER_HOST") {
+            return Some(host);
+        }
+        std::env::var("XDG_RUNTIME_DIR")
+            .ok()
+            .map(|dir| format!("unix://{}/docker.sock", dir))
+    }
+
+    /// Caveats for running with this install, given the host ports the
+    /// deployment publishes.
+    pub fn warnings(&self, ports: &[u16]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.rootless {
+            return warnings;
+        }
+
+        let privileged: Vec<String> = ports
+            .iter()
+            .filter(|port| **port < 1024)
+            .map(u16::to_string)
+            .collect();
+        if !privileged.is_empty() {
+            warnings.push(format!(
+                "Rootless Docker cannot publish privileged ports ({}); set \
+                 net.ipv4.ip_unprivileged_port_start={} or use ports above 1023",
+                privileged.join(", "),
+                ports.iter().min().copied().unwrap_or_default()
+            ));
+        }
+        if let Some(socket) = Self::rootless_socket() {
+            warnings.push(format!(
+                "Rootless Docker socket is {}; keep DOCKER_HOST pointing there",
+                socket
+            ));
+        }
+        warnings
+    }
+}
+
+/// Resource usage of one running service container.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContainerStats {
+    pub service: String,
+    pub container: String,
+    pub cpu_percent: f64,
+    pub memory_usage: String,
+    pub memory_limit: String,
+    pub memory_percent: f64,
+}
+
+/// A line of `docker stats --format json`.
 #[derive(Debug, Deserialize)]
-struct DockerComposeService {
+struct DockerStatsLine {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "CPUPerc")]
+    cpu_perc: String,
+    #[serde(rename = "MemUsage")]
+    mem_usage: String,
+    #[serde(rename = "MemPerc")]
+    mem_perc: String,
+}
+
+/// A line of `docker compose ps --format json`.
+#[derive(Debug, Deserialize)]
+struct ComposeContainer {
+    #[serde(rename = "Name")]
     name: String,
+    #[serde(rename = "Service")]
+    service: String,
+}
+
+/// A line of `docker compose ps --format json`, as far as status needs it.
+#[derive(Debug, Deserialize)]
+struct DockerComposeService {
+    #[serde(rename = "Service")]
+    service: String,
+    #[serde(rename = "State")]
     state: String,
-    ports: Vec<String>,
+    /// `null` rather than empty for containers publishing nothing
+    #[serde(rename = "Publishers", default)]
+    publishers: Option<Vec<Publisher>>,
+    #[serde(rename = "Health", default)]
+    health: String,
+}
+
+/// A port a container publishes, from the `Publishers` field of `ps`.
+#[derive(Debug, Deserialize)]
+struct Publisher {
+    #[serde(rename = "URL", default)]
+    url: String,
+    #[serde(rename = "TargetPort")]
+    target_port: u16,
+    #[serde(rename = "PublishedPort", default)]
+    published_port: u16,
+    #[serde(rename = "Protocol")]
+    protocol: String,
+}
+
+impl Publisher {
+    /// In `docker ps` form, e.g. `0.0.0.0:443->443/tcp`, or just the target
+    /// port for an exposed but unpublished one.
+    fn describe(&self) -> String {
+        if self.published_port == 0 {
+            format!("{}/{}", self.target_port, self.protocol)
+        } else {
+            format!(
+                "{}:{}->{}/{}",
+                self.url, self.published_port, self.target_port, self.protocol
+            )
+        }
+    }
 }
 
 impl DockerService {
@@ -37,68 +528,483 @@ impl DockerService {
         Self {
             compose_file: compose_file.into(),
             env_vars: HashMap::new(),
+            remove_orphans: false,
+            force_recreate: false,
+            log_style: LogStyle::default(),
+            log_window: LogWindow::default(),
+            runner: Arc::new(ProcessRunner),
+            requirements: VersionRequirements::default(),
         }
     }
 
+    /// Minimum versions `check_dependencies` enforces.
+    pub fn with_requirements(mut self, requirements: VersionRequirements) -> Self {
+        self.requirements = requirements;
+        self
+    }
+
     pub fn with_env_vars(mut self, env_vars: HashMap<String, String>) -> Self {
         self.env_vars = env_vars;
         self
     }
 
-    #[instrument]
-    pub async fn start_services(&self, services: Option<&[String]>) -> Result<()> {
-        // Load secrets if they exist
-        let mut env_vars = self.env_vars.clone();
+    /// Passes `--remove-orphans` to `up` and `down`, removing containers for
+    /// services no longer in the compose file.
+    pub fn with_remove_orphans(mut self, remove_orphans: bool) -> Self {
+        self.remove_orphans = remove_orphans;
+        self
+    }
+
+    /// Passes `--force-recreate` to `up` so changed config or images take
+    /// effect even when compose thinks the container is current.
+    pub fn with_force_recreate(mut self, force_recreate: bool) -> Self {
+        self.force_recreate = force_recreate;
+        self
+    }
+
+    pub fn with_log_style(mut self, log_style: LogStyle) -> Self {
+        self.log_style = log_style;
+        self
+    }
+
+    /// Limits `logs` to the given time window.
+    pub fn with_log_window(mut self, log_window: LogWindow) -> Self {
+        self.log_window = log_window;
+        self
+    }
+
+    /// Runs this service's commands through `runner` instead of spawning
+    /// processes directly.
+    #[cfg(test)]
+    pub fn with_runner(mut self, runner: impl CommandRunner + 'static) -> Self {
+        self.runner = Arc::new(runner);
+        self
+    }
+
+    /// A Compose invocation of `args` through this service's runner.
+    async fn compose_invocation(&self, args: Vec<String>) -> Result<Invocation> {
+        self.runner
+            .compose()
+            .await
+            .map(|compose| Invocation::compose(compose, args))
+            .ok_or_else(|| Error::Docker("Docker Compose is not installed".into()))
+    }
+
+    fn up_args(&self, services: Option<&[String]>) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "up".to_string(),
+            "-d".to_string(),
+        ];
+        if self.remove_orphans {
+            args.push("--remove-orphans".to_string());
+        }
+        if self.force_recreate {
+            args.push("--force-recreate".to_string());
+        }
+        if let Some(services) = services {
+            args.extend(services.iter().cloned());
+        }
+        args
+    }
+
+    fn ps_args(&self) -> Vec<String> {
+        ["-f", &self.compose_file, "ps", "--format", "json"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn down_args(&self, clean: bool) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "down".to_string(),
+        ];
+        if clean {
+            args.push("-v".to_string()); // Remove volumes
+        }
+        if self.remove_orphans {
+            args.push("--remove-orphans".to_string());
+        }
+        args
+    }
+
+    fn logs_args(&self, service: Option<&str>, follow: bool, tail: Option<usize>) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "logs".to_string(),
+            // Prefixes are re-colored by service instead
+            "--no-color".to_string(),
+        ];
+        if !self.log_style.prefix {
+            args.push("--no-log-prefix".to_string());
+        }
+        if follow {
+            args.push("--follow".to_string());
+        }
+        if let Some(tail) = tail {
+            args.push(format!("--tail={}", tail));
+        }
+        if let Some(since) = &self.log_window.since {
+            args.push(format!("--since={}", since));
+        }
+        if let Some(until) = &self.log_window.until {
+            args.push(format!("--until={}", until));
+        }
+        if let Some(service) = service {
+            args.push(service.to_string());
+        }
+        args
+    }
+
+    fn build_args(&self, services: &[String], no_cache: bool) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "build".to_string(),
+        ];
+        if no_cache {
+            args.push("--no-cache".to_string());
+        }
+        args.extend(services.iter().cloned());
+        args
+    }
+
+    fn pull_args(&self, services: Option<&[String]>) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "pull".to_string(),
+        ];
+        if let Some(services) = services {
+            args.extend(services.iter().cloned());
+        }
+        args
+    }
+
+    fn pull_one_args(&self, service: &str) -> Vec<String> {
+        ["-f", &self.compose_file, "pull", "--quiet", service]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn recreate_args(&self, services: Option<&[String]>) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "up".to_string(),
+            "-d".to_string(),
+            "--no-deps".to_string(),
+        ];
+        if let Some(services) = services {
+            args.extend(services.iter().cloned());
+        }
+        args
+    }
+
+    fn restart_args(&self, services: Option<&[String]>) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.compose_file.clone(),
+            "restart".to_string(),
+        ];
+        if let Some(services) = services {
+            args.extend(services.iter().cloned());
+        }
+        args
+    }
+
+    /// Environment for compose: secrets if they exist, with explicitly
+    /// provided vars taking precedence.
+    fn compose_env(&self) -> Result<HashMap<String, String>> {
+        let mut env_vars = HashMap::new();
         if Path::new("config/secrets.toml").exists() {
             let secrets = Secrets::load("config/secrets.toml")?;
-            env_vars.extend(secrets.as_env_vars().into_iter());
+            env_vars.extend(secrets.as_env_vars());
         }
+        env_vars.extend(self.env_vars.clone());
+        Ok(env_vars)
+    }
 
-        let mut cmd = Command::new("docker-compose");
-        cmd.arg("-f")
-            .arg(&self.compose_file)
-            .arg("up")
-            .arg("-d")
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+    #[instrument]
+    pub async fn start_services(&self, services: Option<&[String]>) -> Result<()> {
+        let invocation = self
+            .compose_invocation(self.up_args(services))
+            .await?
+            .with_envs(self.compose_env()?);
+        let output = self.runner.output_teed(&invocation).await?;
+        if output.success {
+            return Ok(());
+        }
+
+        let mut known: Vec<KnownService> = crate::compose::ComposeConfig::load(&self.compose_file)
+            .map(|compose| {
+                compose
+                    .services
+                    .into_iter()
+                    .map(|(name, service)| KnownService {
+                        name,
+                        container: service.container_name,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        known.extend(
+            services
+                .unwrap_or_default()
+                .iter()
+                .map(|name| KnownService {
+                    name: name.clone(),
+                    container: None,
+                }),
+        );
+        Err(Error::Docker(
+            match UpFailure::parse(&output.stderr, &known) {
+                Some(UpFailure {
+                    service: Some(service),
+                    reason,
+                }) => format!("Failed to start {}: {}", service, reason),
+                Some(UpFailure { reason, .. }) => format!("Failed to start services: {}", reason),
+                None => "Failed to start services".into(),
+            },
+        ))
+    }
 
-        // Add environment variables including secrets
-        for (key, value) in env_vars {
-            cmd.env(key, value);
+    /// Builds images for `services`, which should all declare a `build`
+    /// section.
+    #[instrument(skip(self))]
+    pub async fn build_services(&self, services: &[String], no_cache: bool) -> Result<()> {
+        let invocation = self
+            .compose_invocation(self.build_args(services, no_cache))
+            .await?
+            .with_envs(self.compose_env()?);
+        if !self.runner.status(&invocation).await? {
+            return Err(Error::Docker("Failed to build services".into()));
         }
 
-        // Add specific services if requested
-        if let Some(services) = services {
-            cmd.args(services);
+        Ok(())
+    }
+
+    /// Restarts `services`, or every service, with the same environment
+    /// `start_services` uses. Compose restarts containers as they are, so
+    /// config changes that need a new container call for `--recreate`.
+    #[instrument(skip(self))]
+    pub async fn restart_services(&self, services: Option<&[String]>) -> Result<()> {
+        let invocation = self
+            .compose_invocation(self.restart_args(services))
+            .await?
+            .with_envs(self.compose_env()?);
+        if !self.runner.status(&invocation).await? {
+            return Err(Error::Docker("Failed to restart services".into()));
+        }
+
+        Ok(())
+    }
+
+    /// Pulls newer images for `services` (or all), then recreates their
+    /// containers. A failed pull leaves running containers untouched. With
+    /// `prune_after`, dangling images are removed once the update succeeds.
+    #[instrument(skip(self))]
+    pub async fn pull_and_recreate(
+        &self,
+        services: Option<&[String]>,
+        prune_after: bool,
+    ) -> Result<()> {
+        let invocation = self
+            .compose_invocation(self.pull_args(services))
+            .await?
+            .with_envs(self.compose_env()?);
+        if !self.runner.status(&invocation).await? {
+            return Err(Error::Docker(
+                "Failed to pull images; running containers were left as they are".into(),
+            ));
         }
 
-        debug!("Running docker-compose command: {:?}", cmd);
-        let status = cmd.status().await?;
+        let invocation = self
+            .compose_invocation(self.recreate_args(services))
+            .await?
+            .with_envs(self.compose_env()?);
+        let updated = self.runner.status(&invocation).await?;
 
-        if !status.success() {
-            return Err(Error::Docker("Failed to start services".into()));
+        if let Some(args) = prune_args(prune_after, updated) {
+            let output = self.runner.output(&Invocation::new("docker", args)).await?;
+            if !output.success {
+                warn!("Failed to prune dangling images: {}", output.stderr.trim());
+            }
         }
 
+        if !updated {
+            return Err(Error::Docker("Failed to recreate services".into()));
+        }
         Ok(())
     }
 
+    /// The local digest of each service's image, or `None` if it has not
+    /// been pulled.
     #[instrument(skip(self))]
-    pub async fn stop_services(&self, clean: bool) -> Result<()> {
-        let mut cmd = Command::new("docker-compose");
-        cmd.arg("-f")
-            .arg(&self.compose_file)
-            .arg("down")
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+    pub async fn image_digests(
+        &self,
+        services: Option<&[String]>,
+    ) -> Result<BTreeMap<String, Option<String>>> {
+        let compose = crate::compose::ComposeConfig::load(&self.compose_file)?;
+        let mut digests = BTreeMap::new();
 
-        if clean {
-            cmd.arg("-v"); // Remove volumes
+        for (name, service) in &compose.services {
+            if service.image.is_empty() || services.is_some_and(|only| !only.contains(name)) {
+                continue;
+            }
+            let args = [
+                "image",
+                "inspect",
+                "--format",
+                "{{if .RepoDigests}}{{index .RepoDigests 0}}{{else}}{{.Id}}{{end}}",
+                &service.image,
+            ]
+            .map(String::from)
+            .to_vec();
+            let output = self.runner.output(&Invocation::new("docker", args)).await?;
+            let digest = output.stdout.trim().to_string();
+            digests.insert(
+                name.clone(),
+                (output.success && !digest.is_empty()).then_some(digest),
+            );
         }
 
-        debug!("Running docker-compose command: {:?}", cmd);
-        let status = cmd.status().await?;
+        Ok(digests)
+    }
+
+    /// Pulls images for `services`, at most `max_concurrent` at a time.
+    /// Pulls hitting a registry rate limit are retried up to `rate_limit_retries`
+    /// times after the advertised (or a default) delay.
+    #[instrument(skip(self))]
+    pub async fn pull_parallel(
+        &self,
+        services: &[String],
+        max_concurrent: usize,
+        rate_limit_retries: u32,
+    ) -> Result<PullReport> {
+        let mut report = PullReport::default();
+        let permits = Arc::new(tokio::sync::Semaphore::new(max_concurrent.max(1)));
+        let env_vars = self.compose_env()?;
+        let mut pulls = tokio::task::JoinSet::new();
+
+        for service in unique_services(services) {
+            let runner = Arc::clone(&self.runner);
+            let permits = Arc::clone(&permits);
+            let invocation = self
+                .compose_invocation(self.pull_one_args(&service))
+                .await?
+                .with_envs(env_vars.clone());
+            pulls.spawn(async move {
+                // Held for the whole pull, retries included, so a rate-limited
+                // service doesn't let another pull start while it waits.
+                let _permit = permits
+                    .acquire_owned()
+                    .await
+                    .expect("pull semaphore is never closed");
+                let mut attempt = 0;
+                loop {
+                    let result = pull_service(runner.as_ref(), &invocation).await;
+                    let retry_after = match &result {
+                        Err(stderr) => match PullFailure::classify(stderr) {
+                            PullFailure::RateLimited { retry_after } => retry_after,
+                            _ => break (service, result),
+                        },
+                        Ok(()) => break (service, result),
+                    };
+
+                    if attempt >= rate_limit_retries {
+                        break (service, result);
+                    }
+                    attempt += 1;
 
-        if !status.success() {
+                    let delay = retry_after
+                        .unwrap_or(PullFailure::DEFAULT_RETRY_AFTER)
+                        .min(PullFailure::MAX_RETRY_AFTER);
+                    warn!("Rate limited pulling {}, retrying in {}s", service, delay);
+                    tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+                }
+            });
+        }
+
+        while let Some(joined) = pulls.join_next().await {
+            let (service, result) =
+                joined.map_err(|e| Error::Docker(format!("Pull task failed: {}", e)))?;
+            match result {
+                Ok(()) => report.succeeded.push(service),
+                Err(stderr) => report.failed.push((service, stderr)),
+            }
+        }
+
+        report.succeeded.sort();
+        report.failed.sort();
+        Ok(report)
+    }
+
+    /// Returns `docker-compose config` output, or `None` if Compose isn't installed.
+    #[instrument(skip(self))]
+    pub async fn render_config(&self) -> Result<Option<String>> {
+        let Some(compose) = self.runner.compose().await else {
+            return Ok(None);
+        };
+        let args = ["-f", &self.compose_file, "config"]
+            .map(String::from)
+            .to_vec();
+        let invocation = Invocation::compose(compose, args).with_envs(self.env_vars.clone());
+
+        let output = match self.runner.output(&invocation).await {
+            Ok(output) => output,
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        if !output.success {
+            return Err(Error::Docker(format!(
+                "Failed to render compose config: {}",
+                output.stderr.trim()
+            )));
+        }
+
+        Ok(Some(output.stdout))
+    }
+
+    /// Prints the logs of `service`, or of every service, until they end or
+    /// the user interrupts when following.
+    #[instrument(skip(self))]
+    pub async fn logs(
+        &self,
+        service: Option<&str>,
+        follow: bool,
+        tail: Option<usize>,
+    ) -> Result<()> {
+        let invocation = self
+            .compose_invocation(self.logs_args(service, follow, tail))
+            .await?;
+
+        let success = if self.log_style.recolors() {
+            let log_style = self.log_style;
+            self.runner
+                .stream_lines(&invocation, &mut |line| {
+                    println!("{}", log_style.format_line(line))
+                })
+                .await?
+        } else {
+            self.runner.status(&invocation).await?
+        };
+
+        if !success {
+            return Err(Error::Docker("Failed to read service logs".into()));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn stop_services(&self, clean: bool) -> Result<()> {
+        let invocation = self.compose_invocation(self.down_args(clean)).await?;
+        if !self.runner.status(&invocation).await? {
             return Err(Error::Docker("Failed to stop services".into()));
         }
 
@@ -107,33 +1013,29 @@ impl DockerService {
 
     #[instrument(skip(self))]
     pub async fn get_service_status(&self) -> Result<HashMap<String, ServiceStatus>> {
-        let output = Command::new("docker-compose")
-            .arg("-f")
-            .arg(&self.compose_file)
-            .arg("ps")
-            .arg("--format")
-            .arg("json")
-            .output()
-            .await?;
+        let invocation = self.compose_invocation(self.ps_args()).await?;
+        let output = self.runner.output(&invocation).await?;
 
-        if !output.status.success() {
+        if !output.success {
             return Err(Error::Docker("Failed to get service status".into()));
         }
 
-        let output = String::from_utf8_lossy(&output.stdout);
-        let services: Vec<DockerComposeService> = output
-            .lines()
-            .filter_map(|line| serde_json::from_str(line).ok())
-            .collect();
+        let services: Vec<DockerComposeService> = parse_compose_ps(&output.stdout);
 
         let mut statuses = HashMap::new();
         for service in services {
             statuses.insert(
-                service.name,
+                service.service,
                 ServiceStatus {
                     running: service.state == "running",
                     state: service.state,
-                    ports: service.ports,
+                    ports: service
+                        .publishers
+                        .unwrap_or_default()
+                        .iter()
+                        .map(Publisher::describe)
+                        .collect(),
+                    health: Some(service.health).filter(|health| !health.is_empty()),
                 },
             );
         }
@@ -141,27 +1043,409 @@ impl DockerService {
         Ok(statuses)
     }
 
-    #[instrument]
-    pub async fn check_dependencies() -> Result<()> {
+    #[instrument(skip(self))]
+    pub async fn env_keys(&self, service: &str) -> Result<Vec<String>> {
+        let args = ["-f", &self.compose_file, "exec", "-T", service, "printenv"]
+            .map(String::from)
+            .to_vec();
+        let output = self
+            .runner
+            .output(&self.compose_invocation(args).await?)
+            .await?;
+
+        if !output.success {
+            return Err(Error::Docker(format!(
+                "Failed to read environment of {}: {}",
+                service,
+                output.stderr.trim()
+            )));
+        }
+
+        Ok(parse_env_keys(&output.stdout))
+    }
+
+    /// CPU and memory usage of this project's running containers, keyed by
+    /// compose service. Returns an empty list when nothing is running.
+    #[instrument(skip(self))]
+    pub async fn stats(&self) -> Result<Vec<ContainerStats>> {
+        let invocation = self.compose_invocation(self.ps_args()).await?;
+        let output = self.runner.output(&invocation).await?;
+
+        if !output.success {
+            return Err(Error::Docker(format!(
+                "Failed to list containers: {}",
+                output.stderr.trim()
+            )));
+        }
+
+        let containers = parse_compose_containers(&output.stdout);
+        if containers.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let args = ["stats", "--no-stream", "--format", "json"]
+            .into_iter()
+            .map(String::from)
+            .chain(containers.keys().cloned())
+            .collect();
+        let output = self.runner.output(&Invocation::new("docker", args)).await?;
+
+        if !output.success {
+            return Err(Error::Docker(format!(
+                "Failed to get container stats: {}",
+                output.stderr.trim()
+            )));
+        }
+
+        parse_stats(&output.stdout, &containers)
+    }
+
+    /// Lists the compose service labels of all containers in `project`,
+    /// including ones whose service has since left the compose file.
+    #[instrument(skip(self))]
+    pub async fn project_services(&self, project: &str) -> Result<Vec<String>> {
+        let args = vec![
+            "ps".to_string(),
+            "-a".to_string(),
+            "--filter".to_string(),
+            format!("label=com.docker.compose.project={}", project),
+            "--format".to_string(),
+            "{{.Label \"com.docker.compose.service\"}}".to_string(),
+        ];
+        let output = self.runner.output(&Invocation::new("docker", args)).await?;
+
+        if !output.success {
+            return Err(Error::Docker(format!(
+                "Failed to list project containers: {}",
+                output.stderr.trim()
+            )));
+        }
+
+        Ok(output
+            .stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn check_dependencies(&self) -> Result<DockerEnvironment> {
+        self.check_dependencies_with(self.requirements).await
+    }
+
+    pub async fn check_dependencies_with(
+        &self,
+        required: VersionRequirements,
+    ) -> Result<DockerEnvironment> {
         // Check docker
-        let docker_version = Command::new("docker").arg("--version").output().await?;
+        let docker_version = self
+            .runner
+            .output(&Invocation::new("docker", vec!["--version".to_string()]))
+            .await?;
 
-        if !docker_version.status.success() {
+        if !docker_version.success {
             return Err(Error::Docker("Docker is not installed".into()));
         }
 
-        // Check docker-compose
-        let compose_version = Command::new("docker-compose")
-            .arg("--version")
-            .output()
+        let found = parse_tool_version(&docker_version.stdout)?;
+        ensure_min_version("Docker", found, required.docker)?;
+
+        // Check docker-compose, or the compose plugin
+        let Some(compose) = self.runner.compose().await else {
+            return Err(Error::Docker(
+                "Docker Compose is not installed (tried docker-compose and docker compose)".into(),
+            ));
+        };
+        let compose_version = self
+            .runner
+            .output(&Invocation::compose(compose, vec!["version".to_string()]))
             .await?;
 
-        if !compose_version.status.success() {
-            return Err(Error::Docker("Docker Compose is not installed".into()));
+        let found = parse_tool_version(&compose_version.stdout)?;
+        ensure_min_version("Docker Compose", found, required.compose)?;
+        info!("Using {} {}", compose, found);
+
+        Ok(DockerEnvironment {
+            compose,
+            ..self.detect_environment().await
+        })
+    }
+
+    /// Asks the daemon how it runs. A daemon that can't be reached is not a
+    /// dependency problem, but a rootless socket nobody points at is worth
+    /// a hint since it explains the failures that follow.
+    async fn detect_environment(&self) -> DockerEnvironment {
+        let args = ["info", "--format", "{{json .SecurityOptions}}"]
+            .map(String::from)
+            .to_vec();
+        let info = self.runner.output(&Invocation::new("docker", args)).await;
+
+        match info {
+            Ok(output) if output.success => {
+                let rootless = parse_rootless(&output.stdout);
+                if rootless {
+                    warn!("Docker is running in rootless mode");
+                }
+                DockerEnvironment {
+                    rootless,
+                    ..Default::default()
+                }
+            }
+            _ => {
+                if let Some(socket) = DockerEnvironment::rootless_socket() {
+                    let path = socket.trim_start_matches("unix://");
+                    if std::env::var("DOCKER_HOST").is_err() && Path::new(path).exists() {
+                        warn!(
+                            "Docker daemon unreachable, but a rootless socket exists; \
+                             try DOCKER_HOST={}",
+                            socket
+                        );
+                    }
+                }
+                DockerEnvironment::default()
+            }
         }
+    }
+}
 
-        Ok(())
+/// Whether `docker info --format '{{json .SecurityOptions}}'` lists rootless.
+fn parse_rootless(output: &str) -> bool {
+    serde_json::from_str::<Vec<String>>(output.trim())
+        .unwrap_or_default()
+        .iter()
+        .any(|option| option.split(',').any(|field| field == "name=rootless"))
+}
+
+/// Extracts the version from output such as `Docker version 24.0.7, build afdd53b`
+/// or `Docker Compose version v2.23.0`.
+fn parse_tool_version(output: &str) -> Result<Version> {
+    output
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| {
+            token
+                .trim_start_matches('v')
+                .starts_with(|c: char| c.is_ascii_digit())
+        })
+        .find_map(|token| token.parse::<Version>().ok())
+        .ok_or_else(|| Error::Docker(format!("Could not parse version from: {}", output.trim())))
+}
+
+fn ensure_min_version(tool: &str, found: Version, required: Version) -> Result<()> {
+    if found < required {
+        return Err(Error::Docker(format!(
+            "{} {} is too old, {} or newer is required",
+            tool, found, required
+        )));
+    }
+    Ok(())
+}
+
+/// Runs a quiet pull for one service, returning its stderr on failure.
+async fn pull_service(
+    runner: &dyn CommandRunner,
+    invocation: &Invocation,
+) -> std::result::Result<(), String> {
+    match runner.output(invocation).await {
+        Ok(output) if output.success => Ok(()),
+        Ok(output) => Err(output.stderr.trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Entries of `docker compose ps --format json`, which prints one object
+/// per line since compose 2.21 and a single JSON array before that.
+/// Entries that don't parse are skipped.
+fn parse_compose_ps<T: DeserializeOwned>(output: &str) -> Vec<T> {
+    let output = output.trim_start();
+    if output.starts_with('[') {
+        return serde_json::from_str::<Vec<serde_json::Value>>(output)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|entry| serde_json::from_value(entry).ok())
+            .collect();
+    }
+
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Maps container names to their compose service.
+fn parse_compose_containers(output: &str) -> HashMap<String, String> {
+    parse_compose_ps::<ComposeContainer>(output)
+        .into_iter()
+        .map(|container| (container.name, container.service))
+        .collect()
+}
+
+/// Parses `docker stats` JSON lines, keeping only containers in `containers`.
+fn parse_stats(output: &str, containers: &HashMap<String, String>) -> Result<Vec<ContainerStats>> {
+    let mut stats = Vec::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let line: DockerStatsLine = serde_json::from_str(line)
+            .map_err(|e| Error::Docker(format!("Failed to parse docker stats: {}", e)))?;
+        let Some(service) = containers.get(&line.name) else {
+            continue;
+        };
+
+        let (memory_usage, memory_limit) = line
+            .mem_usage
+            .split_once(" / ")
+            .unwrap_or((line.mem_usage.as_str(), ""));
+        stats.push(ContainerStats {
+            service: service.clone(),
+            container: line.name.clone(),
+            cpu_percent: parse_percent(&line.cpu_perc),
+            memory_usage: memory_usage.trim().to_string(),
+            memory_limit: memory_limit.trim().to_string(),
+            memory_percent: parse_percent(&line.mem_perc),
+        });
     }
+
+    stats.sort_by(|a, b| a.service.cmp(&b.service));
+    Ok(stats)
+}
+
+fn parse_percent(value: &str) -> f64 {
+    value.trim().trim_end_matches('%').parse().unwrap_or(0.0)
+}
+
+/// Variable names from `printenv` output, dropping the values.
+fn parse_env_keys(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, _)| key.to_string())
+        .collect()
+}
+
+/// `docker image prune` for dangling images, only after a successful update
+/// that asked for it.
+fn prune_args(prune_after: bool, updated: bool) -> Option<Vec<String>> {
+    (prune_after && updated).then(|| {
+        ["image", "prune", "-f", "--filter", "dangling=true"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    })
+}
+
+/// Compose's default project name for a directory: its lowercased basename
+/// with characters other than `[a-z0-9_-]` dropped.
+pub fn default_project_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect()
+}
+
+/// A compose service, and the fixed `container_name` it runs under if any.
+#[derive(Debug, Clone, PartialEq)]
+struct KnownService {
+    name: String,
+    container: Option<String>,
+}
+
+/// Why `docker compose up` failed, as far as its stderr tells.
+#[derive(Debug, PartialEq)]
+struct UpFailure {
+    /// The service whose container or definition the error names
+    service: Option<String>,
+    reason: String,
+}
+
+impl UpFailure {
+    /// Takes the last error line as the reason, and the service from either
+    /// `service "name"`, a `container_name` in `known`, or a generated
+    /// container name such as `atc-pds-1`. Names in `known` are preferred,
+    /// so hyphenated services are matched whole.
+    fn parse(stderr: &str, known: &[KnownService]) -> Option<Self> {
+        let lines: Vec<&str> = stderr
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let reason = lines
+            .iter()
+            .rev()
+            .find(|line| line.to_lowercase().contains("error"))
+            .or(lines.last())?
+            .to_string();
+
+        let service = std::iter::once(reason.as_str())
+            .chain(lines.iter().rev().copied())
+            .find_map(|line| failed_service(line, known));
+        Some(Self { service, reason })
+    }
+}
+
+/// The service named in one line of compose output, if any.
+fn failed_service(line: &str, known: &[KnownService]) -> Option<String> {
+    if let Some((_, rest)) = line.split_once("service \"") {
+        return rest.split_once('"').map(|(name, _)| name.to_string());
+    }
+
+    for word in line.split(|c: char| c.is_whitespace() || c == '"' || c == ':' || c == ',') {
+        if let Some(service) = known
+            .iter()
+            .find(|service| !word.is_empty() && service.container.as_deref() == Some(word))
+        {
+            return Some(service.name.clone());
+        }
+
+        let Some((name, index)) = word.rsplit_once(['-', '_']) else {
+            continue;
+        };
+        if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let matched = known
+            .iter()
+            .filter(|service| {
+                name.strip_suffix(service.name.as_str())
+                    .is_some_and(|project| project.ends_with(['-', '_']))
+            })
+            .max_by_key(|service| service.name.len());
+        if let Some(service) = matched {
+            return Some(service.name.clone());
+        }
+        if let Some((_, service)) = name.rsplit_once(['-', '_']) {
+            return Some(service.to_string());
+        }
+    }
+    None
+}
+
+/// Services with containers in the project that the compose file no longer defines.
+pub fn find_orphans<'a>(
+    containers: &'a [String],
+    defined: impl IntoIterator<Item = &'a String>,
+) -> Vec<String> {
+    let defined: std::collections::HashSet<_> = defined.into_iter().collect();
+    let mut orphans: Vec<String> = containers
+        .iter()
+        .filter(|service| !defined.contains(service))
+        .cloned()
+        .collect();
+    orphans.sort();
+    orphans.dedup();
+    orphans
+}
+
+/// `services` in order, without duplicates.
+fn unique_services(services: &[String]) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(services.len());
+    for service in services {
+        if !unique.contains(service) {
+            unique.push(service.clone());
+        }
+    }
+    unique
 }
 
 #[async_trait]
@@ -169,6 +1453,14 @@ impl DockerServiceTrait for DockerService {
     async fn get_service_status(&self) -> Result<HashMap<String, ServiceStatus>> {
         self.get_service_status().await
     }
+
+    async fn check_dependencies(&self) -> Result<DockerEnvironment> {
+        self.check_dependencies().await
+    }
+
+    async fn env_keys(&self, service: &str) -> Result<Vec<String>> {
+        self.env_keys(service).await
+    }
 }
 
 #[cfg(test)]
@@ -180,19 +1472,30 @@ pub mod mock {
     #[derive(Debug, Clone, Default)]
     pub struct MockDockerService {
         services: Arc<Mutex<HashMap<String, ServiceStatus>>>,
+        env: Arc<Mutex<HashMap<String, String>>>,
+        missing_dependency: Arc<Mutex<Option<String>>>,
     }
 
     impl MockDockerService {
         pub fn new() -> Self {
-            Self {
-                services: Arc::new(Mutex::new(HashMap::new())),
-            }
+            Self::default()
+        }
+
+        /// Sets the simulated `printenv` output for `service`.
+        pub async fn set_env_output(&self, service: &str, output: &str) {
+            let mut env = self.env.lock().await;
+            env.insert(service.to_string(), output.to_string());
         }
 
         pub async fn set_service_status(&self, name: &str, status: ServiceStatus) {
             let mut services = self.services.lock().await;
             services.insert(name.to_string(), status);
         }
+
+        /// Makes `check_dependencies` fail with `message`.
+        pub async fn set_missing_dependency(&self, message: &str) {
+            *self.missing_dependency.lock().await = Some(message.to_string());
+        }
     }
 
     #[async_trait]
@@ -201,6 +1504,102 @@ pub mod mock {
             let services = self.services.lock().await;
             Ok(services.clone())
         }
+
+        async fn check_dependencies(&self) -> Result<DockerEnvironment> {
+            match &*self.missing_dependency.lock().await {
+                Some(message) => Err(Error::Docker(message.clone())),
+                None => Ok(DockerEnvironment::default()),
+            }
+        }
+
+        async fn env_keys(&self, service: &str) -> Result<Vec<String>> {
+            let env = self.env.lock().await;
+            env.get(service)
+                .map(|output| parse_env_keys(output))
+                .ok_or_else(|| Error::Docker(format!("service {} is not running", service)))
+        }
+    }
+
+    /// Records every invocation and answers with scripted outputs, in order.
+    /// Once the script runs out, commands succeed with no output.
+    #[derive(Debug, Clone, Default)]
+    pub struct MockRunner {
+        invocations: Arc<std::sync::Mutex<Vec<Invocation>>>,
+        outputs: Arc<std::sync::Mutex<std::collections::VecDeque<CommandOutput>>>,
+        without_compose: bool,
+    }
+
+    impl MockRunner {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// A runner on a machine where neither Compose variant is installed.
+        pub fn without_compose() -> Self {
+            Self {
+                without_compose: true,
+                ..Self::default()
+            }
+        }
+
+        /// Queues the output of the next command run.
+        pub fn push_output(&self, output: CommandOutput) {
+            self.outputs.lock().unwrap().push_back(output);
+        }
+
+        /// Queues a command that exits non-zero with `stderr`.
+        pub fn push_failure(&self, stderr: &str) {
+            self.push_output(CommandOutput {
+                success: false,
+                stderr: stderr.to_string(),
+                ..Default::default()
+            });
+        }
+
+        pub fn invocations(&self) -> Vec<Invocation> {
+            self.invocations.lock().unwrap().clone()
+        }
+
+        fn next(&self, invocation: &Invocation) -> CommandOutput {
+            self.invocations.lock().unwrap().push(invocation.clone());
+            self.outputs
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(CommandOutput {
+                    success: true,
+                    ..Default::default()
+                })
+        }
+    }
+
+    #[async_trait]
+    impl CommandRunner for MockRunner {
+        async fn compose(&self) -> Option<ComposeCommand> {
+            (!self.without_compose).then_some(ComposeCommand::Plugin)
+        }
+
+        async fn status(&self, invocation: &Invocation) -> Result<bool> {
+            Ok(self.next(invocation).success)
+        }
+
+        async fn output(&self, invocation: &Invocation) -> Result<CommandOutput> {
+            Ok(self.next(invocation))
+        }
+
+        async fn output_teed(&self, invocation: &Invocation) -> Result<CommandOutput> {
+            Ok(self.next(invocation))
+        }
+
+        async fn stream_lines(
+            &self,
+            invocation: &Invocation,
+            on_line: &mut (dyn for<'l> FnMut(&'l str) + Send),
+        ) -> Result<bool> {
+            let output = self.next(invocation);
+            output.stdout.lines().for_each(on_line);
+            Ok(output.success)
+        }
     }
 }
 
@@ -224,6 +1623,398 @@ mod tests {
         assert_eq!(docker.env_vars.get("TEST_VAR").unwrap(), "test_value");
     }
 
+    #[tokio::test]
+    async fn test_start_services_runs_compose_up() {
+        let runner = mock::MockRunner::new();
+        let mut env_vars = HashMap::new();
+        env_vars.insert("PDS_HOSTNAME".to_string(), "example.com".to_string());
+        let docker = DockerService::new("docker-compose.yml")
+            .with_env_vars(env_vars)
+            .with_remove_orphans(true)
+            .with_runner(runner.clone());
+
+        docker
+            .start_services(Some(&["pds".to_string()]))
+            .await
+            .unwrap();
+
+        let invocations = runner.invocations();
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(invocations[0].program, "docker");
+        assert_eq!(
+            invocations[0].args,
+            [
+                "compose",
+                "-f",
+                "docker-compose.yml",
+                "up",
+                "-d",
+                "--remove-orphans",
+                "pds"
+            ]
+        );
+        assert_eq!(invocations[0].envs["PDS_HOSTNAME"], "example.com");
+    }
+
+    #[tokio::test]
+    async fn test_pull_parallel_passes_compose_env() {
+        let runner = mock::MockRunner::new();
+        let mut env_vars = HashMap::new();
+        env_vars.insert("PDS_HOSTNAME".to_string(), "example.com".to_string());
+        let docker = DockerService::new("docker-compose.yml")
+            .with_env_vars(env_vars)
+            .with_runner(runner.clone());
+
+        let services: Vec<String> = ["pds", "caddy", "pds"].map(String::from).to_vec();
+        let report = docker.pull_parallel(&services, 1, 0).await.unwrap();
+
+        assert_eq!(report.succeeded, ["caddy", "pds"]);
+        let invocations = runner.invocations();
+        assert_eq!(invocations.len(), 2);
+        assert!(invocations
+            .iter()
+            .all(|invocation| invocation.envs["PDS_HOSTNAME"] == "example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_start_services_fails_on_non_zero_exit() {
+        let runner = mock::MockRunner::new();
+        runner.push_failure("port is already allocated");
+        let docker = DockerService::new("docker-compose.yml").with_runner(runner.clone());
+
+        let err = docker.start_services(None).await.unwrap_err();
+        assert!(matches!(err, Error::Docker(msg)
+            if msg == "Failed to start services: port is already allocated"));
+        assert_eq!(runner.invocations().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_start_services_names_failed_service() {
+        let dir = tempfile::tempdir().unwrap();
+        let compose_file = dir.path().join("docker-compose.yml");
+        std::fs::write(
+            &compose_file,
+            "services:\n  \
+               pds:\n    image: pds\n    container_name: pds\n  \
+               db:\n    image: postgres\n    container_name: db\n",
+        )
+        .unwrap();
+        let runner = mock::MockRunner::new();
+        runner.push_failure(
+            " Container db  Started\n \
+             Container pds  Starting\n\
+             Error response from daemon: driver failed programming external connectivity \
+             on endpoint pds (0d8c5a4e): Bind for 0.0.0.0:3000 failed: port is already allocated\n",
+        );
+        let docker =
+            DockerService::new(compose_file.to_string_lossy().into_owned()).with_runner(runner);
+
+        let err = docker.start_services(None).await.unwrap_err();
+        match err {
+            Error::Docker(msg) => {
+                assert!(msg.starts_with("Failed to start pds: "), "{}", msg);
+                assert!(msg.contains("port is already allocated"));
+            }
+            other => panic!("expected a Docker error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_up_failure() {
+        let known: Vec<KnownService> = ["social-app", "app"]
+            .into_iter()
+            .map(|name| KnownService {
+                name: name.to_string(),
+                container: None,
+            })
+            .collect();
+        let failure = UpFailure::parse(
+            "dependency failed to start: container atc-social-app-1 is unhealthy\n",
+            &known,
+        )
+        .unwrap();
+        assert_eq!(failure.service.as_deref(), Some("social-app"));
+
+        let failure = UpFailure::parse(
+            "service \"pds\" refers to undefined volume pds-data: invalid compose project\n",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(failure.service.as_deref(), Some("pds"));
+
+        let failure = UpFailure::parse("Error: no space left on device\n", &[]).unwrap();
+        assert_eq!(failure.service, None);
+        assert_eq!(failure.reason, "Error: no space left on device");
+        assert_eq!(UpFailure::parse("\n", &[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_dependencies_through_runner() {
+        let runner = mock::MockRunner::new();
+        for stdout in [
+            "Docker version 24.0.7, build afdd53b\n",
+            "Docker Compose version v2.23.0\n",
+            r#"["name=seccomp,profile=builtin","name=rootless"]"#,
+        ] {
+            runner.push_output(CommandOutput {
+                success: true,
+                stdout: stdout.to_string(),
+                ..Default::default()
+            });
+        }
+        let docker = DockerService::new("docker-compose.yml").with_runner(runner.clone());
+
+        let environment = docker.check_dependencies().await.unwrap();
+        assert_eq!(environment.compose, ComposeCommand::Plugin);
+        assert!(environment.rootless);
+        let programs: Vec<_> = runner
+            .invocations()
+            .into_iter()
+            .map(|invocation| format!("{} {}", invocation.program, invocation.args.join(" ")))
+            .collect();
+        assert_eq!(programs[0], "docker --version");
+        assert_eq!(programs[1], "docker compose version");
+
+        runner.push_output(CommandOutput {
+            success: true,
+            stdout: "Docker version 19.03.1, build 74b1e89\n".to_string(),
+            ..Default::default()
+        });
+        assert!(docker.check_dependencies().await.is_err());
+
+        runner.push_output(CommandOutput {
+            success: true,
+            stdout: "Docker version 24.0.7, build afdd53b\n".to_string(),
+            ..Default::default()
+        });
+        let docker = docker.with_requirements(VersionRequirements {
+            docker: Version::new(25, 0, 0),
+            ..Default::default()
+        });
+        assert!(matches!(
+            docker.check_dependencies().await,
+            Err(Error::Docker(msg)) if msg.contains("25.0.0")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_service_status_reports_health() {
+        let runner = mock::MockRunner::new();
+        runner.push_output(CommandOutput {
+            success: true,
+            stdout: [
+                r#"{"Command":"\"node --enable-source-maps index.js\"","CreatedAt":"2024-05-02 10:11:12 +0000 UTC","ExitCode":0,"Health":"healthy","ID":"0f3c2a","Image":"ghcr.io/bluesky-social/pds:0.4","Labels":"com.docker.compose.service=pds","LocalVolumes":"1","Mounts":"atc_pds_data","Name":"pds","Names":"pds","Networks":"atc_bluesky","Ports":"3000/tcp","Project":"atc","Publishers":[{"URL":"","TargetPort":3000,"PublishedPort":0,"Protocol":"tcp"}],"RunningFor":"2 hours ago","Service":"pds","Size":"0B","State":"running","Status":"Up 2 hours (healthy)"}"#,
+                r#"{"Command":"\"docker-entrypoint.s…\"","CreatedAt":"2024-05-02 10:11:10 +0000 UTC","ExitCode":0,"Health":"starting","ID":"7ab1d9","Image":"postgres:15-alpine","Labels":"com.docker.compose.service=db","LocalVolumes":"1","Mounts":"atc_postgres_data","Name":"db","Names":"db","Networks":"atc_bluesky","Ports":"5432/tcp","Project":"atc","Publishers":null,"RunningFor":"2 hours ago","Service":"db","Size":"0B","State":"running","Status":"Up 5 seconds (health: starting)"}"#,
+                r#"{"Command":"\"caddy run --config …\"","CreatedAt":"2024-05-02 10:11:13 +0000 UTC","ExitCode":0,"Health":"","ID":"c41e07","Image":"caddy:2","Labels":"com.docker.compose.service=caddy","LocalVolumes":"2","Mounts":"atc_caddy_data","Name":"caddy","Names":"caddy","Networks":"atc_bluesky","Ports":"0.0.0.0:443->443/tcp","Project":"atc","Publishers":[{"URL":"0.0.0.0","TargetPort":443,"PublishedPort":443,"Protocol":"tcp"}],"RunningFor":"2 hours ago","Service":"caddy","Size":"0B","State":"running","Status":"Up 2 hours"}"#,
+            ]
+            .join("\n"),
+            ..Default::default()
+        });
+        let docker = DockerService::new("docker-compose.yml").with_runner(runner.clone());
+
+        let statuses = docker.get_service_status().await.unwrap();
+        assert_eq!(statuses["pds"].health.as_deref(), Some("healthy"));
+        assert_eq!(statuses["db"].health.as_deref(), Some("starting"));
+        assert_eq!(statuses["caddy"].health, None);
+        assert_eq!(statuses["caddy"].ports, ["0.0.0.0:443->443/tcp"]);
+        assert_eq!(statuses["pds"].ports, ["3000/tcp"]);
+        assert!(statuses["db"].ports.is_empty());
+        assert_eq!(
+            runner.invocations()[0].args,
+            [
+                "compose",
+                "-f",
+                "docker-compose.yml",
+                "ps",
+                "--format",
+                "json"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compose_invocation() {
+        let args = vec!["ps".to_string()];
+        let plugin = Invocation::compose(ComposeCommand::Plugin, args.clone());
+        assert_eq!(
+            (plugin.program.as_str(), plugin.args),
+            ("docker", vec!["compose".into(), "ps".into()])
+        );
+        let standalone = Invocation::compose(ComposeCommand::Standalone, args.clone());
+        assert_eq!(
+            (standalone.program.as_str(), standalone.args),
+            ("docker-compose", args)
+        );
+    }
+
+    #[test]
+    fn test_parse_rootless() {
+        let rootless = r#"["name=seccomp,profile=builtin","name=rootless","name=cgroupns"]"#;
+        assert!(parse_rootless(rootless));
+
+        let rootful = r#"["name=apparmor","name=seccomp,profile=builtin","name=cgroupns"]"#;
+        assert!(!parse_rootless(rootful));
+        assert!(!parse_rootless("null"));
+
+        let env = DockerEnvironment {
+            rootless: true,
+            ..Default::default()
+        };
+        assert!(env.warnings(&[80, 443, 3000])[0].contains("80, 443"));
+        assert!(DockerEnvironment::default().warnings(&[80]).is_empty());
+    }
+
+    #[test]
+    fn test_compose_command_invocation() {
+        let standalone = ComposeCommand::Standalone.command();
+        assert_eq!(standalone.as_std().get_program(), "docker-compose");
+        assert_eq!(standalone.as_std().get_args().count(), 0);
+
+        let plugin = ComposeCommand::Plugin.command();
+        assert_eq!(plugin.as_std().get_program(), "docker");
+        assert_eq!(
+            plugin.as_std().get_args().collect::<Vec<_>>(),
+            vec!["compose"]
+        );
+        assert_eq!(ComposeCommand::Plugin.to_string(), "docker compose");
+    }
+
+    #[test]
+    fn test_restart_args() {
+        let docker = DockerService::new("docker-compose.yml");
+        assert_eq!(
+            docker.restart_args(Some(&["pds".to_string(), "bgs".to_string()])),
+            vec!["-f", "docker-compose.yml", "restart", "pds", "bgs"]
+        );
+        assert_eq!(
+            docker.restart_args(None),
+            vec!["-f", "docker-compose.yml", "restart"]
+        );
+    }
+
+    #[test]
+    fn test_build_args_for_build_context_services() {
+        let compose: crate::compose::ComposeConfig = serde_yaml::from_str(
+            r#"
+services:
+  pds:
+    image: ghcr.io/bluesky-social/pds:latest
+  labeler:
+    build:
+      context: ./labeler
+      dockerfile: Dockerfile
+  feed:
+    build: ./feed
+"#,
+        )
+        .unwrap();
+
+        let services = compose.build_services(None);
+        assert_eq!(services, vec!["feed", "labeler"]);
+        assert_eq!(
+            compose.build_services(Some(&["pds".to_string(), "feed".to_string()])),
+            vec!["feed"]
+        );
+
+        let docker = DockerService::new("docker-compose.yml");
+        assert_eq!(
+            docker.build_args(&services, true),
+            vec![
+                "-f",
+                "docker-compose.yml",
+                "build",
+                "--no-cache",
+                "feed",
+                "labeler"
+            ]
+        );
+        assert!(!docker
+            .build_args(&services, false)
+            .contains(&"--no-cache".to_string()));
+    }
+
+    #[test]
+    fn test_update_args() {
+        let docker = DockerService::new("docker-compose.yml");
+        let services = ["pds".to_string()];
+        assert_eq!(
+            docker.pull_args(Some(&services)),
+            vec!["-f", "docker-compose.yml", "pull", "pds"]
+        );
+        assert_eq!(
+            docker.recreate_args(Some(&services)),
+            vec!["-f", "docker-compose.yml", "up", "-d", "--no-deps", "pds"]
+        );
+    }
+
+    #[test]
+    fn test_prune_only_after_successful_update() {
+        assert_eq!(
+            prune_args(true, true),
+            Some(vec![
+                "image".to_string(),
+                "prune".to_string(),
+                "-f".to_string(),
+                "--filter".to_string(),
+                "dangling=true".to_string()
+            ])
+        );
+        assert_eq!(prune_args(true, false), None);
+        assert_eq!(prune_args(false, true), None);
+    }
+
+    #[test]
+    fn test_logs_args() {
+        let docker = DockerService::new("docker-compose.yml");
+        assert_eq!(
+            docker.logs_args(Some("pds"), true, Some(100)),
+            vec![
+                "-f",
+                "docker-compose.yml",
+                "logs",
+                "--no-color",
+                "--follow",
+                "--tail=100",
+                "pds"
+            ]
+        );
+
+        let docker = docker
+            .with_log_style(LogStyle {
+                prefix: false,
+                color: true,
+            })
+            .with_log_window(LogWindow {
+                since: Some("2024-05-01T12:00:00Z".to_string()),
+                until: None,
+            });
+        assert_eq!(
+            docker.logs_args(None, false, None),
+            vec![
+                "-f",
+                "docker-compose.yml",
+                "logs",
+                "--no-color",
+                "--no-log-prefix",
+                "--since=2024-05-01T12:00:00Z"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unique_services() {
+        let services: Vec<String> = ["caddy", "pds", "plc", "bgs", "pds", "appview"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(
+            unique_services(&services),
+            vec!["caddy", "pds", "plc", "bgs", "appview"]
+        );
+        assert!(unique_services(&[]).is_empty());
+    }
+
     #[tokio::test]
     async fn test_mock_docker_service() {
         let docker = mock::MockDockerService::new();
@@ -236,6 +2027,7 @@ mod tests {
                     running: true,
                     state: "running".to_string(),
                     ports: vec!["8080:80".to_string()],
+                    health: None,
                 },
             )
             .await;
@@ -247,4 +2039,226 @@ mod tests {
         assert_eq!(test_service.state, "running");
         assert_eq!(test_service.ports[0], "8080:80");
     }
+
+    #[tokio::test]
+    async fn test_verify_env_reports_missing_secret() -> Result<()> {
+        let docker = mock::MockDockerService::new();
+        docker
+            .set_env_output(
+                "pds",
+                "PATH=/usr/bin\nPDS_ADMIN_PASSWORD=hunter2\nPDS_PLC_ROTATION_KEY_K256=abc=\n",
+            )
+            .await;
+
+        let keys: Vec<String> = ["PDS_JWT_SECRET", "PDS_ADMIN_PASSWORD"]
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let missing = docker.verify_env("pds", &keys).await?;
+        assert_eq!(missing, vec!["PDS_JWT_SECRET".to_string()]);
+
+        assert!(matches!(
+            docker.verify_env("bgs", &keys).await,
+            Err(Error::Docker(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tool_version() -> Result<()> {
+        let compose = parse_tool_version("Docker Compose version v2.23.0-desktop.1\n")?;
+        assert_eq!(compose, Version::new(2, 23, 0));
+        assert!(ensure_min_version("Docker Compose", compose, Version::new(2, 1, 0)).is_ok());
+
+        let legacy = parse_tool_version("docker-compose version 1.29.2, build 5becea4c")?;
+        assert_eq!(legacy, Version::new(1, 29, 2));
+        let required = VersionRequirements::default().compose;
+        match ensure_min_version("Docker Compose", legacy, required) {
+            Err(Error::Docker(msg)) => {
+                assert!(msg.contains("1.29.2"));
+                assert!(msg.contains("2.0.0"));
+            }
+            other => panic!("expected version error, got {:?}", other),
+        }
+
+        let docker = parse_tool_version("Docker version 24.0.7, build afdd53b")?;
+        assert_eq!(docker, Version::new(24, 0, 7));
+        assert!(parse_tool_version("command not found").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_limited_pull_error() {
+        let output = "Error response from daemon: toomanyrequests: You have reached your \
+            pull rate limit. You may increase the limit by authenticating and upgrading: \
+            https://www.docker.com/increase-rate-limit (Retry-After: 42)";
+        assert_eq!(
+            PullFailure::classify(output),
+            PullFailure::RateLimited {
+                retry_after: Some(42)
+            }
+        );
+
+        let report = PullReport {
+            succeeded: vec!["caddy".into()],
+            failed: vec![
+                ("bgs".into(), "dial tcp: i/o timeout".into()),
+                ("pds".into(), output.into()),
+            ],
+        };
+        match report.error() {
+            Some(Error::Docker(msg)) => {
+                assert!(msg.contains("rate limit"));
+                assert!(msg.contains("retry after 42s"));
+                assert!(msg.contains("bgs, pds"));
+            }
+            other => panic!("expected rate limit error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_classify_pull_failures() {
+        assert_eq!(
+            PullFailure::classify("429 Too Many Requests"),
+            PullFailure::RateLimited { retry_after: None }
+        );
+        assert_eq!(
+            PullFailure::classify("unauthorized: authentication required"),
+            PullFailure::Unauthorized
+        );
+        assert_eq!(
+            PullFailure::classify("dial tcp: lookup ghcr.io: no such host"),
+            PullFailure::Network
+        );
+        assert_eq!(
+            PullFailure::classify("manifest unknown"),
+            PullFailure::Other
+        );
+        assert_eq!(
+            PullFailure::classify("manifest for pds@sha256:4291ab03 not found"),
+            PullFailure::Other
+        );
+        assert!(PullReport::default().error().is_none());
+    }
+
+    #[test]
+    fn test_remove_orphans_forwarded() {
+        let services = vec!["pds".to_string()];
+
+        let docker = DockerService::new("docker-compose.yml");
+        assert!(!docker
+            .up_args(None)
+            .contains(&"--remove-orphans".to_string()));
+        assert!(!docker
+            .down_args(false)
+            .contains(&"--remove-orphans".to_string()));
+
+        let docker = docker.with_remove_orphans(true);
+        assert_eq!(
+            docker.up_args(Some(&services)),
+            [
+                "-f",
+                "docker-compose.yml",
+                "up",
+                "-d",
+                "--remove-orphans",
+                "pds"
+            ]
+        );
+        assert_eq!(
+            docker.down_args(true),
+            ["-f", "docker-compose.yml", "down", "-v", "--remove-orphans"]
+        );
+    }
+
+    #[test]
+    fn test_force_recreate_targets_service() {
+        let services = vec!["feed-generator".to_string()];
+
+        let docker = DockerService::new("docker-compose.yml");
+        assert!(!docker
+            .up_args(Some(&services))
+            .contains(&"--force-recreate".to_string()));
+
+        let docker = docker.with_force_recreate(true);
+        assert_eq!(
+            docker.up_args(Some(&services)),
+            [
+                "-f",
+                "docker-compose.yml",
+                "up",
+                "-d",
+                "--force-recreate",
+                "feed-generator"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_compose_containers_array_form() {
+        let containers = parse_compose_containers(concat!(
+            r#"[{"Name":"pds","Service":"pds","State":"running"},"#,
+            r#"{"Name":"atc-bgs-1","Service":"bgs","State":"running"}]"#,
+            "\n",
+        ));
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers["pds"], "pds");
+        assert_eq!(containers["atc-bgs-1"], "bgs");
+    }
+
+    #[test]
+    fn test_parse_stats() -> Result<()> {
+        let containers = parse_compose_containers(concat!(
+            r#"{"Name":"pds","Service":"pds","State":"running"}"#,
+            "\n",
+            r#"{"Name":"atc-bgs-1","Service":"bgs","State":"running"}"#,
+            "\n",
+        ));
+
+        let output = concat!(
+            r#"{"BlockIO":"0B / 0B","CPUPerc":"12.50%","Container":"a1","ID":"a1","MemPerc":"3.21%","MemUsage":"251.3MiB / 7.66GiB","Name":"pds","NetIO":"1kB / 2kB","PIDs":"12"}"#,
+            "\n",
+            r#"{"BlockIO":"0B / 0B","CPUPerc":"0.00%","Container":"b2","ID":"b2","MemPerc":"0.50%","MemUsage":"40MiB / 7.66GiB","Name":"atc-bgs-1","NetIO":"0B / 0B","PIDs":"3"}"#,
+            "\n",
+            r#"{"BlockIO":"0B / 0B","CPUPerc":"99.00%","Container":"c3","ID":"c3","MemPerc":"1.00%","MemUsage":"1GiB / 7.66GiB","Name":"unrelated","NetIO":"0B / 0B","PIDs":"1"}"#,
+            "\n",
+        );
+
+        let stats = parse_stats(output, &containers)?;
+        assert_eq!(
+            stats,
+            vec![
+                ContainerStats {
+                    service: "bgs".to_string(),
+                    container: "atc-bgs-1".to_string(),
+                    cpu_percent: 0.0,
+                    memory_usage: "40MiB".to_string(),
+                    memory_limit: "7.66GiB".to_string(),
+                    memory_percent: 0.5,
+                },
+                ContainerStats {
+                    service: "pds".to_string(),
+                    container: "pds".to_string(),
+                    cpu_percent: 12.5,
+                    memory_usage: "251.3MiB".to_string(),
+                    memory_limit: "7.66GiB".to_string(),
+                    memory_percent: 3.21,
+                },
+            ]
+        );
+
+        assert!(parse_stats("", &containers)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let containers = vec!["pds".to_string(), "bgs".to_string(), "bgs".to_string()];
+        let defined = vec!["pds".to_string(), "caddy".to_string()];
+        assert_eq!(find_orphans(&containers, &defined), vec!["bgs"]);
+        assert_eq!(
+            default_project_name(Path::new("/srv/My Bsky.v2")),
+            "mybskyv2"
+        );
+    }
 }
@@ -28,8 +28,39 @@ pub enum Error {
     #[error("{}", format_error("API error", .0))]
     Api(String),
 
+    #[error("{}", format_error("Update error", .0))]
+    Update(String),
+
     #[error("{}", format_error("JSON error", .0.to_string()))]
     Json(#[from] serde_json::Error),
+
+    #[error("{}", format_error("Unhealthy", .0))]
+    Unhealthy(String),
+
+    #[error("{}", format_error("Degraded", .0))]
+    Degraded(String),
+
+    #[error("{}", format_error("Disconnected", .0))]
+    Disconnected(String),
+}
+
+/// Exit status for failures that may succeed if retried, such as a
+/// subscription giving up after losing its connection (`EX_TEMPFAIL`).
+pub const EXIT_RETRYABLE: i32 = 75;
+
+/// Exit status when services respond but report degraded health.
+pub const EXIT_DEGRADED: i32 = 2;
+
+impl Error {
+    /// Process exit status, so supervisors can tell a dropped subscription
+    /// worth restarting apart from everything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Disconnected(_) => EXIT_RETRYABLE,
+            Error::Degraded(_) => EXIT_DEGRADED,
+            _ => 1,
+        }
+    }
 }
 
 fn format_error(error_type: &str, message: impl AsRef<str>) -> String {
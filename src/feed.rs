@@ -1,4 +1,6 @@
-use crate::error::{Error, Result};
+use crate::config::NetworkConfig;
+use crate::error::Result;
+use crate::http::{read_json, send_traced};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument};
@@ -26,55 +28,40 @@ pub struct FeedGenerator {
 }
 
 impl FeedGenerator {
-    pub fn new(domain: &str, did: &str) -> Self {
+    /// A client for the feed generator at `network`'s domain, honoring its
+    /// TLS and port settings.
+    pub fn new(network: &NetworkConfig, did: &str) -> Self {
         Self {
             client: Client::builder()
                 .danger_accept_invalid_certs(true)
                 .build()
                 .expect("Failed to create HTTP client"),
-            base_url: format!("https://feed-generator.{}", domain),
+            base_url: network.service_url("feed-generator"),
             did: did.to_string(),
         }
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self), fields(request_id))]
     pub async fn publish_feed(&self) -> Result<PublishFeedResponse> {
         let url = format!("{}/scripts/publishFeedGen.ts", self.base_url);
         debug!("Publishing feed at: {}", url);
 
         let request = PublishFeedRequest {
             feed_did: self.did.clone(),
-            feed_url: format!("https://feed-generator.{}/", self.base_url),
+            feed_url: format!("{}/", self.base_url),
             name: "test-feed".to_string(),
             display_name: "Test Feed".to_string(),
             description: "A test feed generator".to_string(),
             avatar: None,
         };
 
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&request)?)
-            .send()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to publish feed: {}", e)))?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(Error::Api(format!(
-                "Failed to publish feed: {}",
-                error_text
-            )));
-        }
-
-        let feed = response
-            .json::<PublishFeedResponse>()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to parse response: {}", e)))?;
+            .body(serde_json::to_string(&request)?);
+        let response = send_traced(request, "publish feed").await?;
+        let feed: PublishFeedResponse = read_json(response).await?;
 
         debug!("Feed published successfully: {}", feed.uri);
         Ok(feed)
@@ -1,6 +1,9 @@
+use crate::config::{HealthEndpoint, NetworkConfig};
 use crate::error::Result;
+use crate::retry::Backoff;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 use tracing::{debug, instrument, warn};
 
@@ -19,21 +22,101 @@ pub enum HealthState {
     Unhealthy,
 }
 
+#[derive(Clone)]
 pub struct HealthChecker {
     client: Client,
-    base_url: String,
+    domain: String,
+    scheme: &'static str,
+    port: Option<u16>,
+    base_url: Option<String>,
+    endpoints: HashMap<String, HealthEndpoint>,
+    retries: u32,
 }
 
+/// Per-request timeout used by `HealthChecker::for_network`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl HealthChecker {
-    pub fn new(base_url: &str) -> Self {
+    /// Probes each service on its subdomain of `network`'s domain, honoring
+    /// its TLS and port settings, once, with a 5 second timeout.
+    pub fn for_network(network: &NetworkConfig) -> Self {
+        Self::for_network_with_options(network, DEFAULT_TIMEOUT, 0)
+    }
+
+    /// Like `for_network`, with the timeout and retries of `with_options`.
+    pub fn for_network_with_options(
+        network: &NetworkConfig,
+        timeout: Duration,
+        retries: u32,
+    ) -> Self {
+        Self::with_options(&network.domain, timeout, retries).with_origin(network.origin())
+    }
+
+    /// Probes each service at `https://<service>.<domain>`, once, with a
+    /// 5 second timeout.
+    #[cfg(test)]
+    pub fn new(domain: &str) -> Self {
+        Self::with_options(domain, DEFAULT_TIMEOUT, 0)
+    }
+
+    /// Like `new`, but each request times out after `timeout` and a probe
+    /// that isn't healthy is retried up to `retries` times.
+    pub fn with_options(domain: &str, timeout: Duration, retries: u32) -> Self {
         Self {
             client: Client::builder()
                 .danger_accept_invalid_certs(true) // For development with self-signed certs
-                .timeout(Duration::from_secs(5))
+                .timeout(timeout)
                 .build()
                 .expect("Failed to create HTTP client"),
-            base_url: base_url.trim_end_matches('/').to_string(),
+            domain: domain.trim_end_matches('/').to_string(),
+            scheme: "https",
+            port: None,
+            base_url: None,
+            endpoints: HashMap::new(),
+            retries,
+        }
+    }
+
+    /// Probes every service at `base_url` instead of its own subdomain.
+    #[cfg(test)]
+    pub fn with_base_url(base_url: &str) -> Self {
+        let mut checker = Self::new("");
+        checker.base_url = Some(base_url.trim_end_matches('/').to_string());
+        checker
+    }
+
+    /// Probes over `scheme` on `port` instead of HTTPS on the default port,
+    /// e.g. with `NetworkConfig::origin`.
+    pub fn with_origin(mut self, (scheme, port): (&'static str, Option<u16>)) -> Self {
+        self.scheme = scheme;
+        self.port = port;
+        self
+    }
+
+    /// Overrides the built-in health path and expected status per service.
+    pub fn with_endpoints(mut self, endpoints: HashMap<String, HealthEndpoint>) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// Probes all `services` concurrently, returning statuses in input order.
+    pub async fn check_all(&self, services: &[String]) -> Result<Vec<HealthStatus>> {
+        let mut set = tokio::task::JoinSet::new();
+        for (index, service) in services.iter().enumerate() {
+            let checker = self.clone();
+            let service = service.clone();
+            set.spawn(async move { (index, checker.check_service(&service).await) });
+        }
+
+        let mut results = Vec::with_capacity(services.len());
+        while let Some(joined) = set.join_next().await {
+            let (index, status) = joined
+                .map_err(|e| crate::error::Error::Network(format!("Health probe failed: {}", e)))?;
+            results.push((index, status?));
         }
+
+        results.sort_by_key(|(index, _)| *index);
+        Ok(results.into_iter().map(|(_, status)| status).collect())
     }
 
     #[instrument(skip(self))]
@@ -41,7 +124,23 @@ impl HealthChecker {
         debug!("Checking health for service: {}", service);
 
         let start = std::time::Instant::now();
-        let status = match service {
+        let probe = match self.endpoints.get(service) {
+            Some(endpoint) => self.check_endpoint(service, endpoint).await?,
+            None => self.check_default(service).await?,
+        };
+
+        let latency = start.elapsed().as_millis() as u64;
+
+        Ok(HealthStatus {
+            service: service.to_string(),
+            status: probe.state,
+            latency_ms: latency,
+            details: probe.details,
+        })
+    }
+
+    async fn check_default(&self, service: &str) -> Result<Probe> {
+        let probe = match service {
             "pds" => self.check_pds().await?,
             "plc" => self.check_plc().await?,
             "appview" => self.check_appview().await?,
@@ -52,102 +151,180 @@ impl HealthChecker {
             "jetstream" => self.check_jetstream().await?,
             _ => {
                 warn!("Unknown service: {}", service);
-                HealthState::Unhealthy
+                Probe {
+                    state: HealthState::Unhealthy,
+                    details: Some(format!("Unknown service: {}", service)),
+                }
             }
         };
 
-        let latency = start.elapsed().as_millis() as u64;
+        Ok(probe)
+    }
 
-        Ok(HealthStatus {
-            service: service.to_string(),
-            status,
-            latency_ms: latency,
-            details: None,
-        })
+    async fn check_endpoint(&self, service: &str, endpoint: &HealthEndpoint) -> Result<Probe> {
+        let url = self.service_url(service, &endpoint.path);
+        debug!("Checking configured health endpoint: {}", url);
+
+        Ok(self
+            .probe(&url, |code| match code {
+                code if code == endpoint.expected_status => HealthState::Healthy,
+                500..=599 => HealthState::Degraded,
+                _ => HealthState::Unhealthy,
+            })
+            .await)
     }
 
-    #[instrument(skip(self))]
-    async fn check_pds(&self) -> Result<HealthState> {
-        let url = format!("{}/xrpc/_health", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) => match response.status().as_u16() {
-                200 => Ok(HealthState::Healthy),
-                // 5xx status codes indicate degraded service
-                500..=599 => Ok(HealthState::Degraded),
-                // Any other code is considered unhealthy
-                _ => Ok(HealthState::Unhealthy),
-            },
-            Err(_) => Ok(HealthState::Unhealthy),
+    fn service_url(&self, service: &str, path: &str) -> String {
+        let path = if path.is_empty() || path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+
+        let port = self
+            .port
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+        match &self.base_url {
+            Some(base_url) => format!("{}{}", base_url, path),
+            None => format!(
+                "{}://{}.{}{}{}",
+                self.scheme, service, self.domain, port, path
+            ),
         }
     }
 
-    async fn check_plc(&self) -> Result<HealthState> {
-        let url = format!("https://plc.{}/health", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) => match response.status().as_u16() {
-                200 => Ok(HealthState::Healthy),
-                500..=599 => Ok(HealthState::Degraded),
-                _ => Ok(HealthState::Unhealthy),
-            },
-            Err(_) => Ok(HealthState::Unhealthy),
+    /// GETs `url` and maps the status code to a state with `classify`,
+    /// retrying with a short backoff while the result isn't healthy. The
+    /// details carry the status code, plus the start of the body when it
+    /// isn't 200, or the connection error if there was no response.
+    async fn probe(&self, url: &str, classify: impl Fn(u16) -> HealthState) -> Probe {
+        let mut backoff = Backoff::new(Duration::from_millis(200), Duration::from_secs(2));
+        let mut probe = self.probe_once(url, &classify).await;
+        for attempt in 1..=self.retries {
+            if probe.state == HealthState::Healthy {
+                break;
+            }
+            let delay = backoff.next_delay();
+            debug!("{} not healthy, retry {} in {:?}", url, attempt, delay);
+            tokio::time::sleep(delay).await;
+            probe = self.probe_once(url, &classify).await;
         }
+        probe
     }
 
-    async fn check_appview(&self) -> Result<HealthState> {
-        let url = format!("https://appview.{}/xrpc/_health", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) => match response.status().as_u16() {
-                200 => Ok(HealthState::Healthy),
-                500..=599 => Ok(HealthState::Degraded),
-                _ => Ok(HealthState::Unhealthy),
+    async fn probe_once(&self, url: &str, classify: impl Fn(u16) -> HealthState) -> Probe {
+        match self.client.get(url).send().await {
+            Ok(response) => {
+                let code = response.status().as_u16();
+                let state = classify(code);
+                let body = if code == 200 {
+                    String::new()
+                } else {
+                    response.text().await.unwrap_or_default()
+                };
+                let details = match body_snippet(&body) {
+                    snippet if snippet.is_empty() => format!("HTTP {}", code),
+                    snippet => format!("HTTP {}: {}", code, snippet),
+                };
+                Probe {
+                    state,
+                    details: Some(details),
+                }
+            }
+            Err(e) => Probe {
+                state: HealthState::Unhealthy,
+                details: Some(e.to_string()),
             },
-            Err(_) => Ok(HealthState::Unhealthy),
         }
     }
 
-    async fn check_bgs(&self) -> Result<HealthState> {
-        let url = format!("https://bgs.{}/health", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) if response.status().is_success() => Ok(HealthState::Healthy),
-            Ok(_) => Ok(HealthState::Degraded),
-            Err(_) => Ok(HealthState::Unhealthy),
-        }
+    #[instrument(skip(self))]
+    async fn check_pds(&self) -> Result<Probe> {
+        let url = self.service_url("pds", "/xrpc/_health");
+        Ok(self.probe(&url, strict_state).await)
     }
 
-    async fn check_social_app(&self) -> Result<HealthState> {
-        let url = format!("https://social-app.{}", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) if response.status().is_success() => Ok(HealthState::Healthy),
-            Ok(_) => Ok(HealthState::Degraded),
-            Err(_) => Ok(HealthState::Unhealthy),
-        }
+    async fn check_plc(&self) -> Result<Probe> {
+        let url = self.service_url("plc", "/health");
+        Ok(self.probe(&url, strict_state).await)
     }
 
-    async fn check_ozone(&self) -> Result<HealthState> {
-        let url = format!("https://ozone.{}/health", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) if response.status().is_success() => Ok(HealthState::Healthy),
-            Ok(_) => Ok(HealthState::Degraded),
-            Err(_) => Ok(HealthState::Unhealthy),
-        }
+    async fn check_appview(&self) -> Result<Probe> {
+        let url = self.service_url("appview", "/xrpc/_health");
+        Ok(self.probe(&url, strict_state).await)
     }
 
-    async fn check_feed_generator(&self) -> Result<HealthState> {
-        let url = format!("https://feed-generator.{}/health", self.base_url);
-        match self.client.get(&url).send().await {
-            Ok(response) if response.status().is_success() => Ok(HealthState::Healthy),
-            Ok(_) => Ok(HealthState::Degraded),
-            Err(_) => Ok(HealthState::Unhealthy),
-        }
+    async fn check_bgs(&self) -> Result<Probe> {
+        let url = self.service_url("bgs", "/health");
+        Ok(self.probe(&url, lenient_state).await)
+    }
+
+    async fn check_social_app(&self) -> Result<Probe> {
+        let url = self.service_url("social-app", "");
+        Ok(self.probe(&url, lenient_state).await)
+    }
+
+    async fn check_ozone(&self) -> Result<Probe> {
+        let url = self.service_url("ozone", "/health");
+        Ok(self.probe(&url, lenient_state).await)
     }
 
-    async fn check_jetstream(&self) -> Result<HealthState> {
-        let _url = format!("wss://jetstream.{}/health", self.base_url);
+    async fn check_feed_generator(&self) -> Result<Probe> {
+        let url = self.service_url("feed-generator", "/health");
+        Ok(self.probe(&url, lenient_state).await)
+    }
+
+    async fn check_jetstream(&self) -> Result<Probe> {
+        let _url = format!("wss://jetstream.{}/health", self.domain);
         // For now just check if the endpoint exists
-        Ok(HealthState::Healthy)
+        Ok(Probe {
+            state: HealthState::Healthy,
+            details: None,
+        })
     }
 }
 
+/// What a single health probe observed.
+#[derive(Debug, Clone, PartialEq)]
+struct Probe {
+    state: HealthState,
+    details: Option<String>,
+}
+
+/// Longest response body excerpt kept in `HealthStatus::details`.
+const BODY_SNIPPET_CHARS: usize = 120;
+
+/// 200 is healthy, 5xx degraded and anything else unhealthy.
+fn strict_state(code: u16) -> HealthState {
+    match code {
+        200 => HealthState::Healthy,
+        // 5xx status codes indicate degraded service
+        500..=599 => HealthState::Degraded,
+        // Any other code is considered unhealthy
+        _ => HealthState::Unhealthy,
+    }
+}
+
+/// Any 2xx is healthy; a response at all means at least degraded.
+fn lenient_state(code: u16) -> HealthState {
+    match code {
+        200..=299 => HealthState::Healthy,
+        _ => HealthState::Degraded,
+    }
+}
+
+/// The body on one line, cut to `BODY_SNIPPET_CHARS`.
+fn body_snippet(body: &str) -> String {
+    let line = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() <= BODY_SNIPPET_CHARS {
+        return line;
+    }
+    let mut snippet: String = line.chars().take(BODY_SNIPPET_CHARS).collect();
+    snippet.push('…');
+    snippet
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,7 +334,7 @@ mod tests {
     #[tokio::test]
     async fn test_health_checker() {
         let mock_server = MockServer::start().await;
-        let checker = HealthChecker::new(&mock_server.uri());
+        let checker = HealthChecker::with_base_url(&mock_server.uri());
 
         // Mock health endpoint
         Mock::given(method("GET"))
@@ -167,13 +344,13 @@ mod tests {
             .await;
 
         let status = checker.check_pds().await.unwrap();
-        assert_eq!(status, HealthState::Healthy);
+        assert_eq!(status.state, HealthState::Healthy);
     }
 
     #[tokio::test]
     async fn test_degraded_service() {
         let mock_server = MockServer::start().await;
-        let checker = HealthChecker::new(&mock_server.uri());
+        let checker = HealthChecker::with_base_url(&mock_server.uri());
 
         Mock::given(method("GET"))
             .and(path("/xrpc/_health"))
@@ -182,7 +359,141 @@ mod tests {
             .await;
 
         let status = checker.check_pds().await.unwrap();
-        assert_eq!(status, HealthState::Degraded);
+        assert_eq!(status.state, HealthState::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_details_capture_status_and_body() {
+        let mock_server = MockServer::start().await;
+        let checker = HealthChecker::with_base_url(&mock_server.uri());
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("upstream connect error\n"))
+            .mount(&mock_server)
+            .await;
+
+        let status = checker.check_service("pds").await.unwrap();
+        assert_eq!(status.status, HealthState::Degraded);
+        assert_eq!(
+            status.details.as_deref(),
+            Some("HTTP 503: upstream connect error")
+        );
+
+        assert_eq!(
+            body_snippet(&"x".repeat(500)).chars().count(),
+            BODY_SNIPPET_CHARS + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        let checker = HealthChecker {
+            retries: 2,
+            ..HealthChecker::with_base_url(&mock_server.uri())
+        };
+        let status = checker.check_service("pds").await.unwrap();
+        assert_eq!(status.status, HealthState::Healthy);
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_details_record_connection_errors() {
+        // Nothing listens on a port once its listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let checker = HealthChecker::with_base_url(&format!("http://127.0.0.1:{}", port));
+
+        let status = checker.check_service("pds").await.unwrap();
+        assert_eq!(status.status, HealthState::Unhealthy);
+        assert!(status.details.is_some_and(|details| !details.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_configured_endpoint() {
+        let mock_server = MockServer::start().await;
+        let endpoints = HashMap::from([(
+            "pds".to_string(),
+            HealthEndpoint {
+                path: "/custom/ready".to_string(),
+                expected_status: 204,
+            },
+        )]);
+        let checker = HealthChecker::with_base_url(&mock_server.uri()).with_endpoints(endpoints);
+
+        Mock::given(method("GET"))
+            .and(path("/custom/ready"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let status = checker.check_service("pds").await.unwrap();
+        assert_eq!(status.status, HealthState::Healthy);
+    }
+
+    #[test]
+    fn test_service_urls() {
+        let checker = HealthChecker::new("example.com");
+        assert_eq!(
+            checker.service_url("pds", "/xrpc/_health"),
+            "https://pds.example.com/xrpc/_health"
+        );
+        assert_eq!(
+            checker.service_url("plc", "/health"),
+            "https://plc.example.com/health"
+        );
+        assert_eq!(
+            checker.service_url("appview", "xrpc/_health"),
+            "https://appview.example.com/xrpc/_health"
+        );
+        assert_eq!(
+            checker.service_url("social-app", ""),
+            "https://social-app.example.com"
+        );
+
+        let checker = HealthChecker::new("example.com").with_origin(("http", Some(8080)));
+        assert_eq!(
+            checker.service_url("pds", "/xrpc/_health"),
+            "http://pds.example.com:8080/xrpc/_health"
+        );
+
+        let network = NetworkConfig {
+            domain: "example.com".into(),
+            use_tls: false,
+            ports: crate::config::Ports {
+                http: 8080,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            HealthChecker::for_network(&network).service_url("plc", "/health"),
+            "http://plc.example.com:8080/health"
+        );
+
+        let checker = HealthChecker::with_base_url("http://127.0.0.1:3000/");
+        assert_eq!(
+            checker.service_url("plc", "/health"),
+            "http://127.0.0.1:3000/health"
+        );
     }
 
     #[tokio::test]
@@ -0,0 +1,143 @@
+use crate::error::{Error, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use tracing::Span;
+
+/// Header used to correlate an outgoing call with our logs and errors.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The id a response's request was sent with, kept in its extensions.
+#[derive(Debug, Clone)]
+struct RequestId(String);
+
+pub fn new_request_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect()
+}
+
+/// Sends `request` under a fresh request id, set as the `X-Request-Id`
+/// header and recorded on the current span's `request_id` field. Transport
+/// errors and non-success statuses become `Error::Api`, as
+/// `Failed to <action> [request <id>]: ...`.
+pub async fn send_traced(request: RequestBuilder, action: &str) -> Result<Response> {
+    let response = send_traced_unchecked(request, action).await?;
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let request_id = request_id(&response).to_string();
+    let status = response.status();
+    let error_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(Error::Api(format!(
+        "Failed to {} [request {}]: HTTP {}: {}",
+        action, request_id, status, error_text
+    )))
+}
+
+/// Like `send_traced`, but returns the response whatever its status.
+pub async fn send_traced_unchecked(request: RequestBuilder, action: &str) -> Result<Response> {
+    let request_id = new_request_id();
+    Span::current().record("request_id", &request_id);
+
+    let mut response = request
+        .header(REQUEST_ID_HEADER, &request_id)
+        .send()
+        .await
+        .map_err(|e| {
+            Error::Api(format!(
+                "Failed to {} [request {}]: {}",
+                action, request_id, e
+            ))
+        })?;
+    response.extensions_mut().insert(RequestId(request_id));
+    Ok(response)
+}
+
+/// The id `response`'s request was sent with by `send_traced`.
+pub fn request_id(response: &Response) -> &str {
+    response
+        .extensions()
+        .get::<RequestId>()
+        .map_or("unknown", |id| id.0.as_str())
+}
+
+/// Parses the JSON body of a `send_traced` response.
+pub async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let request_id = request_id(&response).to_string();
+    response.json::<T>().await.map_err(|e| {
+        Error::Api(format!(
+            "Failed to parse response [request {}]: {}",
+            request_id, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use wiremock::matchers::{header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_request_ids_are_unique() {
+        let a = new_request_id();
+        let b = new_request_id();
+        assert_eq!(a.len(), 16);
+        assert!(a.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_send_traced() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/ok"))
+            .and(header_exists(REQUEST_ID_HEADER))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[1, 2]"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("no such thing"))
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        let response =
+            send_traced(client.get(format!("{}/ok", mock_server.uri())), "get ok").await?;
+        let sent = mock_server.received_requests().await.unwrap()[0].headers[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(request_id(&response), sent);
+        assert_eq!(read_json::<Vec<u32>>(response).await?, vec![1, 2]);
+
+        let err = send_traced(
+            client.get(format!("{}/missing", mock_server.uri())),
+            "get missing",
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Api(msg) if msg.starts_with("Failed to get missing [request ")
+                && msg.ends_with("HTTP 404 Not Found: no such thing")
+        ));
+
+        let response = send_traced_unchecked(
+            client.get(format!("{}/missing", mock_server.uri())),
+            "get missing",
+        )
+        .await?;
+        assert_eq!(response.status(), 404);
+        Ok(())
+    }
+}
@@ -1,46 +1,428 @@
-use crate::error::Result;
-use reqwest::Client;
+use crate::error::{Error, Result};
+use crate::nsid::Nsid;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, instrument};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, instrument, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Where `subscribe --save` records the subscription for `--resume`.
+pub const JETSTREAM_CONFIG_PATH: &str = "config/jetstream.toml";
+
+/// Where the `time_us` of the last received event is kept between runs.
+pub const JETSTREAM_CURSOR_PATH: &str = ".jetstream-cursor";
+
+/// Consecutive failed reconnects after which a subscription gives up.
+pub const MAX_RECONNECT_FAILURES: u32 = 10;
+
+/// Why a subscription stopped without error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamEnd {
+    /// `max_events` events were received.
+    MaxEvents(u64),
+    /// Shutdown was requested, e.g. by Ctrl-C.
+    Interrupted(u64),
+}
+
+impl StreamEnd {
+    /// Number of events written before stopping.
+    pub fn events(self) -> u64 {
+        match self {
+            StreamEnd::MaxEvents(count) | StreamEnd::Interrupted(count) => count,
+        }
+    }
+}
+
+/// Parameters of a Jetstream subscription.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JetstreamConfig {
-    pub collections: Vec<String>,
+    pub collections: Vec<Nsid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wanted_dids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<u64>,
     pub subscription_endpoint: String,
     pub reconnect_delay: u32,
 }
 
+impl JetstreamConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::Config(format!(
+                "No saved subscription at {} ({}); run subscribe with --save first",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let config: Self = toml::from_str(&content)
+            .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| Error::Config(format!("Failed to serialize subscription: {}", e)))?;
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(did) = self.wanted_dids.iter().find(|did| !did.starts_with("did:")) {
+            return Err(Error::Config(format!(
+                "Invalid DID {:?} in subscription (expected e.g. did:plc:...)",
+                did
+            )));
+        }
+        if !self.subscription_endpoint.starts_with("ws://")
+            && !self.subscription_endpoint.starts_with("wss://")
+        {
+            return Err(Error::Config(format!(
+                "Subscription endpoint must be a ws:// or wss:// URL, got {:?}",
+                self.subscription_endpoint
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that a saved subscription is resumed against the endpoint it
+    /// was saved for, since its cursor means nothing to another stream.
+    pub fn check_endpoint(&self, endpoint: &str) -> Result<()> {
+        if self.subscription_endpoint != endpoint {
+            return Err(Error::Config(format!(
+                "Saved subscription is for {}, not {}; subscribe without --resume to start over",
+                self.subscription_endpoint, endpoint
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the cursor persisted by a previous subscription, if any.
+pub fn load_cursor(path: impl AsRef<Path>) -> Result<Option<u64>> {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(content) => content
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| Error::Config(format!("Invalid cursor in {}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save_cursor(path: impl AsRef<Path>, cursor: u64) -> Result<()> {
+    fs::write(path, format!("{}\n", cursor))?;
+    Ok(())
+}
+
+/// The `time_us` of an event, which doubles as its replay cursor.
+fn event_time_us(event: &str) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(event)
+        .ok()?
+        .get("time_us")?
+        .as_u64()
+}
+
+/// Where decoded events are written.
+pub enum EventSink {
+    Stdout,
+    /// One NDJSON file, truncated when the sink is created
+    File(BufWriter<File>),
+    Split(CollectionSplitter),
+}
+
+impl EventSink {
+    /// Splits into `split_dir` when given, else writes to `output`, else
+    /// to stdout.
+    pub fn new(output: Option<&Path>, split_dir: Option<&Path>) -> Result<Self> {
+        match (split_dir, output) {
+            (Some(dir), _) => Ok(Self::Split(CollectionSplitter::new(dir)?)),
+            (None, Some(path)) => Ok(Self::File(BufWriter::new(File::create(path)?))),
+            (None, None) => Ok(Self::Stdout),
+        }
+    }
+
+    pub fn write_event(&mut self, event: &str) -> Result<()> {
+        match self {
+            Self::Stdout => {
+                println!("{}", event);
+                Ok(())
+            }
+            Self::File(writer) => {
+                writeln!(writer, "{}", event.trim_end())?;
+                Ok(())
+            }
+            Self::Split(splitter) => splitter.write_event(event),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Stdout => Ok(()),
+            Self::File(writer) => Ok(writer.flush()?),
+            Self::Split(splitter) => splitter.flush(),
+        }
+    }
+}
+
+/// Routes events into `<dir>/<collection>.ndjson`, opening files on first use.
+pub struct CollectionSplitter {
+    dir: PathBuf,
+    writers: HashMap<String, BufWriter<File>>,
+}
+
+impl CollectionSplitter {
+    const FALLBACK: &'static str = "other";
+
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            writers: HashMap::new(),
+        })
+    }
+
+    pub fn write_event(&mut self, event: &str) -> Result<()> {
+        let collection = Self::collection_of(event);
+
+        let writer = match self.writers.entry(collection) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let path = self.dir.join(format!("{}.ndjson", entry.key()));
+                debug!("Opening split output: {:?}", path);
+                let file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                entry.insert(BufWriter::new(file))
+            }
+        };
+
+        writeln!(writer, "{}", event.trim_end())?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        for writer in self.writers.values_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn collection_of(event: &str) -> String {
+        let collection = serde_json::from_str::<serde_json::Value>(event)
+            .ok()
+            .and_then(|value| {
+                value
+                    .pointer("/commit/collection")
+                    .and_then(|c| c.as_str())
+                    .map(String::from)
+            });
+
+        match collection {
+            // Only accept names that are safe to use as a file name
+            Some(c)
+                if !c.is_empty()
+                    && !c.starts_with('.')
+                    && c.chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '.' || ch == '-') =>
+            {
+                c
+            }
+            _ => {
+                warn!(
+                    "Event without a usable collection, routing to {}",
+                    Self::FALLBACK
+                );
+                Self::FALLBACK.to_string()
+            }
+        }
+    }
+}
+
+impl Drop for CollectionSplitter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
 pub struct JetstreamClient {
-    client: Client,
     base_url: String,
 }
 
 impl JetstreamClient {
     pub fn new(domain: &str) -> Self {
         Self {
-            client: Client::builder()
-                .danger_accept_invalid_certs(true)
-                .build()
-                .expect("Failed to create HTTP client"),
-            base_url: format!("https://jetstream.{}", domain),
+            base_url: format!("wss://jetstream.{}", domain),
         }
     }
 
+    /// The subscribe endpoint this client connects to.
+    pub fn endpoint(&self) -> String {
+        format!("{}/subscribe", self.base_url)
+    }
+
+    fn subscribe_url(&self, subscription: &JetstreamConfig) -> String {
+        let query = subscription
+            .collections
+            .iter()
+            .map(|c| format!("wantedCollections={}", c))
+            .chain(
+                subscription
+                    .wanted_dids
+                    .iter()
+                    .map(|did| format!("wantedDids={}", did)),
+            )
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{}?{}", self.endpoint(), query)
+    }
+
+    /// Streams events into stdout, `output` or `split_dir` until Ctrl-C,
+    /// or until `max_events` when given, reconnecting after the
+    /// subscription's `reconnect_delay` if the connection drops. Replay
+    /// starts from the subscription's cursor, and the last event's
+    /// `time_us` is persisted to the cursor file. Gives up with
+    /// `Error::Disconnected` after `MAX_RECONNECT_FAILURES` consecutive
+    /// failed reconnects.
     #[instrument(skip(self))]
-    pub async fn subscribe(&self, collections: &[String]) -> Result<()> {
-        let url = format!(
-            "wss://jetstream.{}/subscribe?{}",
-            self.base_url,
-            collections
-                .iter()
-                .map(|c| format!("wantedCollections={}", c))
-                .collect::<Vec<_>>()
-                .join("&")
-        );
+    pub async fn subscribe(
+        &self,
+        subscription: &JetstreamConfig,
+        output: Option<&Path>,
+        split_dir: Option<&Path>,
+        max_events: Option<u64>,
+    ) -> Result<StreamEnd> {
+        let url = self.subscribe_url(subscription);
         debug!("Subscribing to collections at: {}", url);
 
-        // TODO! Implement WebSocket connection
-        Ok(())
+        let mut sink = EventSink::new(output, split_dir)?;
+        let mut cursor = subscription.cursor;
+        let end = stream_events(
+            &url,
+            &mut cursor,
+            &mut sink,
+            max_events,
+            Duration::from_millis(subscription.reconnect_delay.into()),
+            MAX_RECONNECT_FAILURES,
+            tokio::signal::ctrl_c(),
+        )
+        .await;
+        sink.flush()?;
+
+        if let Some(cursor) = cursor.filter(|c| Some(*c) != subscription.cursor) {
+            save_cursor(JETSTREAM_CURSOR_PATH, cursor)?;
+            debug!("Saved cursor {} to {}", cursor, JETSTREAM_CURSOR_PATH);
+        }
+        end
+    }
+}
+
+/// Reads text frames into `sink` until `max_events` or `shutdown` resolves,
+/// keeping `cursor` at the last event seen so reconnects pick up from there.
+///
+/// Failing to connect the first time is an error; after that, dropped
+/// connections are retried every `reconnect_delay` until more than
+/// `max_reconnect_failures` attempts in a row fail.
+async fn stream_events<T>(
+    url: &str,
+    cursor: &mut Option<u64>,
+    sink: &mut EventSink,
+    max_events: Option<u64>,
+    reconnect_delay: Duration,
+    max_reconnect_failures: u32,
+    shutdown: impl Future<Output = T>,
+) -> Result<StreamEnd> {
+    tokio::pin!(shutdown);
+    let mut count = 0;
+    let mut connected_before = false;
+    let mut failures = 0;
+
+    loop {
+        let url = match cursor {
+            Some(cursor) if url.ends_with('?') => format!("{}cursor={}", url, cursor),
+            Some(cursor) => format!("{}&cursor={}", url, cursor),
+            None => url.to_string(),
+        };
+        match connect_async(&url).await {
+            Ok((mut socket, _)) => {
+                connected_before = true;
+                failures = 0;
+                loop {
+                    if max_events.is_some_and(|max| count >= max) {
+                        info!("Reached {} events, disconnecting", count);
+                        let _ = socket.close(None).await;
+                        return Ok(StreamEnd::MaxEvents(count));
+                    }
+
+                    let message = tokio::select! {
+                        _ = &mut shutdown => {
+                            info!("Interrupted, disconnecting");
+                            let _ = socket.close(None).await;
+                            return Ok(StreamEnd::Interrupted(count));
+                        }
+                        message = socket.next() => message,
+                    };
+
+                    match message {
+                        Some(Ok(Message::Text(text))) => {
+                            sink.write_event(&text)?;
+                            count += 1;
+                            if let Some(time_us) = event_time_us(&text) {
+                                *cursor = Some(time_us);
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | None => break,
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            warn!("WebSocket error: {}", e);
+                            break;
+                        }
+                    }
+                }
+                warn!("Disconnected, reconnecting in {:?}", reconnect_delay);
+            }
+            Err(e) if !connected_before => {
+                return Err(Error::Network(format!(
+                    "Failed to connect to {}: {}",
+                    url, e
+                )));
+            }
+            Err(e) if failures >= max_reconnect_failures => {
+                return Err(Error::Disconnected(format!(
+                    "Giving up after {} failed reconnects to {}: {}",
+                    failures + 1,
+                    url,
+                    e
+                )));
+            }
+            Err(e) => {
+                failures += 1;
+                warn!(
+                    "Reconnect failed ({}), retrying in {:?}",
+                    e, reconnect_delay
+                )
+            }
+        }
+
+        sink.flush()?;
+        tokio::select! {
+            _ = &mut shutdown => return Ok(StreamEnd::Interrupted(count)),
+            _ = tokio::time::sleep(reconnect_delay) => {}
+        }
     }
 }
 
@@ -56,19 +438,362 @@ pub const STANDARD_COLLECTIONS: &[&str] = &[
     "app.bsky.graph.unmuteActor",
 ];
 
+/// `STANDARD_COLLECTIONS` as NSIDs, subscribed to when none are given.
+pub fn standard_collections() -> Vec<Nsid> {
+    STANDARD_COLLECTIONS
+        .iter()
+        .map(|collection| {
+            collection
+                .parse()
+                .expect("standard collections are valid NSIDs")
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use wiremock::matchers::{method, path};
-    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use futures_util::SinkExt;
+
+    #[test]
+    fn test_jetstream_client() -> Result<()> {
+        let client = JetstreamClient::new("test.com");
+        assert_eq!(client.endpoint(), "wss://jetstream.test.com/subscribe");
+
+        let subscription = JetstreamConfig {
+            collections: vec!["app.bsky.feed.post".parse()?],
+            wanted_dids: vec!["did:plc:alice".to_string()],
+            cursor: None,
+            subscription_endpoint: client.endpoint(),
+            reconnect_delay: 200,
+        };
+        assert_eq!(
+            client.subscribe_url(&subscription),
+            "wss://jetstream.test.com/subscribe\
+             ?wantedCollections=app.bsky.feed.post&wantedDids=did:plc:alice"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_saved_subscription_resumes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config/jetstream.toml");
+
+        let saved = JetstreamConfig {
+            collections: vec![
+                "app.bsky.feed.post".parse()?,
+                "app.bsky.graph.follow".parse()?,
+            ],
+            wanted_dids: vec!["did:plc:alice".to_string()],
+            cursor: Some(1_725_911_162_329_308),
+            subscription_endpoint: "wss://jetstream.test.com/subscribe".to_string(),
+            reconnect_delay: 200,
+        };
+        saved.save(&path)?;
+
+        let resumed = JetstreamConfig::load(&path)?;
+        assert_eq!(resumed, saved);
+        resumed.check_endpoint("wss://jetstream.test.com/subscribe")?;
+        assert!(matches!(
+            resumed.check_endpoint("wss://jetstream.other.com/subscribe"),
+            Err(Error::Config(msg)) if msg.contains("jetstream.other.com")
+        ));
+
+        fs::write(
+            &path,
+            "collections = [\"app.bsky.feed.post\"]\n\
+             wanted_dids = [\"alice\"]\n\
+             subscription_endpoint = \"wss://jetstream.test.com/subscribe\"\n\
+             reconnect_delay = 200\n",
+        )?;
+        assert!(matches!(
+            JetstreamConfig::load(&path),
+            Err(Error::Config(msg)) if msg.contains("alice")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cursor_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(JETSTREAM_CURSOR_PATH);
+
+        assert_eq!(load_cursor(&path)?, None);
+        save_cursor(&path, 1_725_911_162_329_308)?;
+        assert_eq!(load_cursor(&path)?, Some(1_725_911_162_329_308));
+
+        fs::write(&path, "yesterday")?;
+        assert!(matches!(load_cursor(&path), Err(Error::Config(_))));
+
+        assert_eq!(
+            event_time_us(r#"{"did":"did:plc:a","time_us":42}"#),
+            Some(42)
+        );
+        assert_eq!(event_time_us("not json"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collection_splitter() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut splitter = CollectionSplitter::new(dir.path())?;
+
+        let post =
+            r#"{"did":"did:plc:a","kind":"commit","commit":{"collection":"app.bsky.feed.post"}}"#;
+        let like =
+            r#"{"did":"did:plc:b","kind":"commit","commit":{"collection":"app.bsky.feed.like"}}"#;
+        let identity = r#"{"did":"did:plc:c","kind":"identity"}"#;
+
+        splitter.write_event(post)?;
+        splitter.write_event(like)?;
+        splitter.write_event(post)?;
+        splitter.write_event(identity)?;
+        splitter.write_event("not json")?;
+        splitter.flush()?;
+
+        let posts = fs::read_to_string(dir.path().join("app.bsky.feed.post.ndjson"))?;
+        assert_eq!(posts.lines().collect::<Vec<_>>(), vec![post, post]);
+
+        let likes = fs::read_to_string(dir.path().join("app.bsky.feed.like.ndjson"))?;
+        assert_eq!(likes.lines().collect::<Vec<_>>(), vec![like]);
+
+        let other = fs::read_to_string(dir.path().join("other.ndjson"))?;
+        assert_eq!(
+            other.lines().collect::<Vec<_>>(),
+            vec![identity, "not json"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_file_sink() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("sample.ndjson");
+        fs::write(&path, "stale\n")?;
+
+        let mut sink = EventSink::new(Some(&path), None)?;
+        sink.write_event(r#"{"time_us":1}"#)?;
+        sink.write_event("{\"time_us\":2}\n")?;
+        sink.flush()?;
+
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "{\"time_us\":1}\n{\"time_us\":2}\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_stops_at_max_events() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for i in 0..10 {
+                let event = format!(
+                    r#"{{"time_us":{},"commit":{{"collection":"app.bsky.feed.post"}}}}"#,
+                    i
+                );
+                if socket.send(Message::text(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let dir = tempfile::tempdir()?;
+        let mut sink = EventSink::new(None, Some(dir.path()))?;
+        let mut cursor = None;
+        let count = stream_events(
+            &format!("ws://{}/subscribe?", addr),
+            &mut cursor,
+            &mut sink,
+            Some(3),
+            Duration::from_millis(10),
+            MAX_RECONNECT_FAILURES,
+            std::future::pending::<()>(),
+        )
+        .await?;
+        sink.flush()?;
+        server.await.unwrap();
+
+        assert_eq!(count, StreamEnd::MaxEvents(3));
+        let written = fs::read_to_string(dir.path().join("app.bsky.feed.post.ndjson"))?;
+        assert_eq!(written.lines().count(), 3);
+        assert!(written.lines().last().unwrap().contains(r#""time_us":2"#));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_split_by_collection() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for (i, collection) in ["app.bsky.feed.post", "app.bsky.feed.like"]
+                .iter()
+                .cycle()
+                .take(4)
+                .enumerate()
+            {
+                let event = format!(
+                    r#"{{"time_us":{},"commit":{{"collection":"{}"}}}}"#,
+                    i, collection
+                );
+                socket.send(Message::text(event)).await.unwrap();
+            }
+        });
+
+        let dir = tempfile::tempdir()?;
+        let mut sink = EventSink::new(None, Some(dir.path()))?;
+        let mut cursor = None;
+        stream_events(
+            &format!("ws://{}/subscribe?", addr),
+            &mut cursor,
+            &mut sink,
+            Some(4),
+            Duration::from_millis(10),
+            MAX_RECONNECT_FAILURES,
+            std::future::pending::<()>(),
+        )
+        .await?;
+        sink.flush()?;
+        server.await.unwrap();
+
+        let posts = fs::read_to_string(dir.path().join("app.bsky.feed.post.ndjson"))?;
+        let likes = fs::read_to_string(dir.path().join("app.bsky.feed.like.ndjson"))?;
+        assert_eq!(posts.lines().count(), 2);
+        assert_eq!(likes.lines().count(), 2);
+        assert!(likes
+            .lines()
+            .all(|line| line.contains("app.bsky.feed.like")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_reconnects() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        // Each connection delivers two events and then drops
+        let server = tokio::spawn(async move {
+            let mut uris = Vec::new();
+            for connection in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut head = [0u8; 256];
+                let n = stream.peek(&mut head).await.unwrap();
+                let request = String::from_utf8_lossy(&head[..n]).into_owned();
+                uris.push(request.split_whitespace().nth(1).unwrap().to_string());
+
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                for i in 0..2 {
+                    let event = format!(r#"{{"time_us":{}}}"#, connection * 2 + i);
+                    socket.send(Message::text(event)).await.unwrap();
+                }
+                let _ = socket.close(None).await;
+            }
+            uris
+        });
+
+        let dir = tempfile::tempdir()?;
+        let mut sink = EventSink::new(None, Some(dir.path()))?;
+        let mut cursor = None;
+        let count = stream_events(
+            &format!("ws://{}/subscribe?", addr),
+            &mut cursor,
+            &mut sink,
+            Some(4),
+            Duration::from_millis(10),
+            MAX_RECONNECT_FAILURES,
+            std::future::pending::<()>(),
+        )
+        .await?;
+        sink.flush()?;
+        let uris = server.await.unwrap();
+
+        assert_eq!(count.events(), 4);
+        assert_eq!(uris, vec!["/subscribe?", "/subscribe?cursor=1"]);
+        assert_eq!(cursor, Some(3));
+        let written = fs::read_to_string(dir.path().join("other.ndjson"))?;
+        assert_eq!(written.lines().count(), 4);
+        Ok(())
+    }
 
     #[tokio::test]
-    async fn test_jetstream_client() {
-        let mock_server = MockServer::start().await;
-        let mock_server_uri = mock_server.uri();
-        let domain = mock_server_uri.trim_start_matches("http://");
+    async fn test_stream_events_stops_on_shutdown() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            // Stay connected without sending until the client leaves
+            while let Some(Ok(message)) = socket.next().await {
+                if message.is_close() {
+                    break;
+                }
+            }
+        });
 
-        let client = JetstreamClient::new(domain);
-        assert!(client.base_url.contains(domain));
+        let mut sink = EventSink::new(None, Some(tempfile::tempdir()?.path()))?;
+        let mut cursor = Some(7);
+        let count = stream_events(
+            &format!("ws://{}/subscribe?", addr),
+            &mut cursor,
+            &mut sink,
+            None,
+            Duration::from_millis(10),
+            MAX_RECONNECT_FAILURES,
+            tokio::time::sleep(Duration::from_millis(50)),
+        )
+        .await?;
+        server.await.unwrap();
+
+        assert_eq!(count, StreamEnd::Interrupted(0));
+        assert_eq!(cursor, Some(7));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_gives_up_after_reconnect_failures() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        // Serve one connection, then stop listening so reconnects fail
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            socket
+                .send(Message::text(r#"{"time_us":1}"#))
+                .await
+                .unwrap();
+            let _ = socket.close(None).await;
+        });
+
+        let dir = tempfile::tempdir()?;
+        let mut sink = EventSink::new(None, Some(dir.path()))?;
+        let mut cursor = None;
+        let result = stream_events(
+            &format!("ws://{}/subscribe?", addr),
+            &mut cursor,
+            &mut sink,
+            None,
+            Duration::from_millis(10),
+            2,
+            std::future::pending::<()>(),
+        )
+        .await;
+        server.await.unwrap();
+
+        let error = result.unwrap_err();
+        assert!(matches!(&error, Error::Disconnected(msg) if msg.contains("3 failed reconnects")));
+        assert_eq!(error.exit_code(), crate::error::EXIT_RETRYABLE);
+        assert_eq!(cursor, Some(1));
+        Ok(())
     }
 }
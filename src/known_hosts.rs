@@ -0,0 +1,335 @@
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+pub const KNOWN_HOSTS_PATH: &str = "config/known_hosts.toml";
+
+/// TLS certificate fingerprints recorded the first time each host was seen,
+/// or when a changed certificate was explicitly accepted.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KnownHosts {
+    #[serde(default)]
+    hosts: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FingerprintCheck {
+    FirstSeen,
+    Match,
+    Changed {
+        expected: String,
+        actual: String,
+        /// The new certificate chains to a trusted CA, as after an ACME
+        /// renewal. Still reported: a publicly trusted cert for the wrong
+        /// server looks the same.
+        trusted_chain: bool,
+    },
+    /// Changed, and the new fingerprint was recorded because the caller
+    /// asked to accept it
+    Accepted,
+}
+
+impl KnownHosts {
+    /// Loads the store, treating a missing file as empty.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| Error::Config(format!("Failed to serialize known hosts: {}", e)))?;
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Compares `cert` with the stored fingerprint, recording it if `host`
+    /// has not been seen before. A changed fingerprint is never overwritten.
+    pub fn check(&mut self, host: &str, cert: &PeerCertificate) -> FingerprintCheck {
+        match self.hosts.get(host) {
+            None => {
+                self.hosts
+                    .insert(host.to_string(), cert.fingerprint.clone());
+                FingerprintCheck::FirstSeen
+            }
+            Some(expected) if *expected == cert.fingerprint => FingerprintCheck::Match,
+            Some(expected) => FingerprintCheck::Changed {
+                expected: expected.clone(),
+                actual: cert.fingerprint.clone(),
+                trusted_chain: cert.trusted,
+            },
+        }
+    }
+
+    /// Replaces the recorded fingerprint for `host`.
+    pub fn repin(&mut self, host: &str, fingerprint: &str) {
+        self.hosts.insert(host.to_string(), fingerprint.to_string());
+    }
+}
+
+/// SHA-256 of a DER certificate as colon-separated hex.
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// The leaf certificate a host presented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerCertificate {
+    pub fingerprint: String,
+    /// Whether the chain validated against the system roots on the same
+    /// connection the fingerprint was read from
+    pub trusted: bool,
+}
+
+#[async_trait]
+pub trait CertificateSource: Sync {
+    /// The certificate presented by `url`, or `None` if it presented none.
+    async fn peer_certificate(&self, url: &str) -> Result<Option<PeerCertificate>>;
+}
+
+/// Reads certificates over real TLS connections.
+pub struct TlsCertificateSource;
+
+#[async_trait]
+impl CertificateSource for TlsCertificateSource {
+    async fn peer_certificate(&self, url: &str) -> Result<Option<PeerCertificate>> {
+        // A validating request reads the fingerprint from the connection whose
+        // chain was checked; only if that fails is the cert read unverified
+        if let Ok(fingerprint) = fetch_fingerprint(url, true).await {
+            return Ok(fingerprint.map(|fingerprint| PeerCertificate {
+                fingerprint,
+                trusted: true,
+            }));
+        }
+        Ok(fetch_fingerprint(url, false)
+            .await?
+            .map(|fingerprint| PeerCertificate {
+                fingerprint,
+                trusted: false,
+            }))
+    }
+}
+
+/// Fetches the fingerprint of the certificate presented by `url`, if any.
+/// With `validate`, the request fails unless the chain is trusted.
+async fn fetch_fingerprint(url: &str, validate: bool) -> Result<Option<String>> {
+    let client = Client::builder()
+        .danger_accept_invalid_certs(!validate)
+        .tls_info(true)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))?;
+
+    let response = client
+        .head(url)
+        .send()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to connect to {}: {}", url, e)))?;
+
+    Ok(response
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .map(fingerprint))
+}
+
+/// Trust-on-first-use check of `host`'s certificate against `path`.
+///
+/// A changed certificate is a warning, or an error when `strict` is set,
+/// even if it chains to a trusted CA. It is only re-pinned when
+/// `accept_new` is set. Under `strict`, failing to read the certificate is
+/// an error too.
+pub async fn verify_host(
+    source: &impl CertificateSource,
+    host: &str,
+    path: impl AsRef<Path>,
+    strict: bool,
+    accept_new: bool,
+) -> Result<()> {
+    let url = format!("https://{}", host);
+    let cert = match source.peer_certificate(&url).await {
+        Ok(Some(cert)) => cert,
+        Ok(None) if strict => {
+            return Err(Error::Cert(format!("{} presented no certificate", host)))
+        }
+        Err(e) if strict => {
+            return Err(Error::Cert(format!(
+                "Could not read the certificate of {}: {}",
+                host, e
+            )))
+        }
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            debug!("Skipping certificate check: {}", e);
+            return Ok(());
+        }
+    };
+
+    let mut known = KnownHosts::load(&path)?;
+    let check = match known.check(host, &cert) {
+        FingerprintCheck::Changed { .. } if accept_new => {
+            known.repin(host, &cert.fingerprint);
+            FingerprintCheck::Accepted
+        }
+        check => check,
+    };
+    report(host, check, strict)?;
+    known.save(&path)
+}
+
+fn report(host: &str, check: FingerprintCheck, strict: bool) -> Result<()> {
+    match check {
+        FingerprintCheck::FirstSeen => {
+            info!("Recorded certificate fingerprint for {}", host);
+            Ok(())
+        }
+        FingerprintCheck::Match => Ok(()),
+        FingerprintCheck::Accepted => {
+            info!("Accepted the new certificate for {}; re-pinned", host);
+            Ok(())
+        }
+        FingerprintCheck::Changed {
+            expected,
+            actual,
+            trusted_chain,
+        } => {
+            let note = if trusted_chain {
+                "; the new certificate chains to a trusted CA, as after a renewal"
+            } else {
+                ""
+            };
+            let message = format!(
+                "Certificate for {} changed (expected {}, got {}){}. If this is expected, \
+                 pass --accept-new-cert or remove its entry from {}",
+                host, expected, actual, note, KNOWN_HOSTS_PATH
+            );
+            if strict {
+                return Err(Error::Cert(message));
+            }
+            warn!("{}", message);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presents a fixed certificate, or fails to connect when `None`.
+    struct StaticSource(Option<PeerCertificate>);
+
+    #[async_trait]
+    impl CertificateSource for StaticSource {
+        async fn peer_certificate(&self, url: &str) -> Result<Option<PeerCertificate>> {
+            match &self.0 {
+                Some(cert) => Ok(Some(cert.clone())),
+                None => Err(Error::Network(format!("Failed to connect to {}", url))),
+            }
+        }
+    }
+
+    fn cert(der: &[u8], trusted: bool) -> PeerCertificate {
+        PeerCertificate {
+            fingerprint: fingerprint(der),
+            trusted,
+        }
+    }
+
+    #[test]
+    fn test_changed_fingerprint_is_reported() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("known_hosts.toml");
+
+        let first = cert(b"first certificate", false);
+        let second = cert(b"second certificate", false);
+
+        let mut known = KnownHosts::load(&path)?;
+        assert_eq!(
+            known.check("pds.test.com", &first),
+            FingerprintCheck::FirstSeen
+        );
+        known.save(&path)?;
+
+        let mut known = KnownHosts::load(&path)?;
+        assert_eq!(known.check("pds.test.com", &first), FingerprintCheck::Match);
+
+        let changed = known.check("pds.test.com", &second);
+        assert_eq!(
+            changed,
+            FingerprintCheck::Changed {
+                expected: first.fingerprint.clone(),
+                actual: second.fingerprint.clone(),
+                trusted_chain: false,
+            }
+        );
+
+        assert!(report("pds.test.com", changed, false).is_ok());
+        let changed = known.check("pds.test.com", &second);
+        assert!(matches!(
+            report("pds.test.com", changed, true),
+            Err(Error::Cert(msg))
+                if msg.contains(&first.fingerprint) && msg.contains(&second.fingerprint)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_host_strict_rejects_trusted_change() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("known_hosts.toml");
+        let first = cert(b"first certificate", true);
+        let renewed = cert(b"renewed certificate", true);
+
+        verify_host(
+            &StaticSource(Some(first.clone())),
+            "pds.test.com",
+            &path,
+            true,
+            false,
+        )
+        .await?;
+
+        // Chaining to a trusted CA doesn't make a changed cert acceptable
+        let source = StaticSource(Some(renewed.clone()));
+        assert!(matches!(
+            verify_host(&source, "pds.test.com", &path, true, false).await,
+            Err(Error::Cert(msg)) if msg.contains("trusted CA")
+        ));
+        assert!(verify_host(&source, "pds.test.com", &path, false, false)
+            .await
+            .is_ok());
+        assert_eq!(
+            KnownHosts::load(&path)?.check("pds.test.com", &first),
+            FingerprintCheck::Match
+        );
+
+        // Until it is accepted explicitly
+        verify_host(&source, "pds.test.com", &path, true, true).await?;
+        verify_host(&source, "pds.test.com", &path, true, false).await?;
+
+        // Strict mode won't pass a host whose certificate can't be read
+        assert!(matches!(
+            verify_host(&StaticSource(None), "pds.test.com", &path, true, false).await,
+            Err(Error::Cert(_))
+        ));
+        verify_host(&StaticSource(None), "pds.test.com", &path, false, false).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::error::{Error, Result};
+use owo_colors::{AnsiColors, OwoColorize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// Lines shown per service when neither `--tail` nor a time window is given.
+pub const DEFAULT_TAIL: usize = 200;
+
+/// Largest `--tail` passed on to compose.
+pub const MAX_TAIL: usize = 10_000;
+
+/// Colors handed out to services, picked by a stable hash of the name.
+const PALETTE: &[AnsiColors] = &[
+    AnsiColors::Cyan,
+    AnsiColors::Green,
+    AnsiColors::Yellow,
+    AnsiColors::Blue,
+    AnsiColors::Magenta,
+    AnsiColors::BrightCyan,
+    AnsiColors::BrightGreen,
+    AnsiColors::BrightMagenta,
+];
+
+/// How `docker-compose logs` output is presented.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogStyle {
+    /// Keep the `service-1 |` prefix on each line.
+    pub prefix: bool,
+    /// Color the prefix by service.
+    pub color: bool,
+}
+
+impl Default for LogStyle {
+    fn default() -> Self {
+        Self {
+            prefix: true,
+            color: true,
+        }
+    }
+}
+
+impl LogStyle {
+    /// Whether lines need rewriting, as opposed to passing compose's output
+    /// straight through.
+    pub fn recolors(&self) -> bool {
+        self.prefix && self.color
+    }
+
+    /// Re-colors the prefix of a compose log line such as `pds-1  | started`.
+    /// Lines without a prefix are returned unchanged.
+    pub fn format_line(&self, line: &str) -> String {
+        match line.split_once(" | ") {
+            Some((prefix, message)) if self.recolors() => {
+                let color = service_color(service_of(prefix));
+                format!("{} | {}", prefix.color(color), message)
+            }
+            _ => line.to_string(),
+        }
+    }
+}
+
+/// Bounds passed to `docker-compose logs` as `--since`/`--until`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogWindow {
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+impl LogWindow {
+    /// Validates each bound as an RFC3339 timestamp or a relative duration
+    /// such as `1h30m`, and checks that an absolute window is not reversed.
+    pub fn parse(since: Option<&str>, until: Option<&str>) -> Result<Self> {
+        let since = since.map(|s| parse_log_time("--since", s)).transpose()?;
+        let until = until.map(|s| parse_log_time("--until", s)).transpose()?;
+
+        if let (Some(Some(start)), Some(Some(end))) = (
+            since.as_ref().map(|(_, at)| *at),
+            until.as_ref().map(|(_, at)| *at),
+        ) {
+            if end < start {
+                return Err(Error::Config(format!(
+                    "--until ({}) is before --since ({})",
+                    end, start
+                )));
+            }
+        }
+
+        Ok(Self {
+            since: since.map(|(value, _)| value),
+            until: until.map(|(value, _)| value),
+        })
+    }
+}
+
+/// The `--tail` to pass to compose. Without `--follow` or a time window the
+/// output is bounded by `DEFAULT_TAIL`, and explicit values are capped at
+/// `MAX_TAIL`.
+pub fn effective_tail(tail: Option<usize>, follow: bool, window: &LogWindow) -> Option<usize> {
+    match tail {
+        Some(tail) if tail > MAX_TAIL => {
+            warn!(
+                "--tail {} exceeds the maximum; showing {} lines",
+                tail, MAX_TAIL
+            );
+            Some(MAX_TAIL)
+        }
+        Some(tail) => Some(tail),
+        None if follow || window.since.is_some() || window.until.is_some() => None,
+        None => Some(DEFAULT_TAIL),
+    }
+}
+
+/// Returns the value to forward and, for timestamps, the parsed time.
+fn parse_log_time(flag: &str, input: &str) -> Result<(String, Option<OffsetDateTime>)> {
+    let input = input.trim();
+    if let Ok(at) = OffsetDateTime::parse(input, &Rfc3339) {
+        return Ok((input.to_string(), Some(at)));
+    }
+    if is_duration(input) {
+        return Ok((input.to_string(), None));
+    }
+    Err(Error::Config(format!(
+        "Invalid {} value {:?} (expected an RFC3339 timestamp like \
+         2024-05-01T12:00:00Z or a duration like 30m)",
+        flag, input
+    )))
+}
+
+/// Go-style durations as compose accepts them, e.g. `42m` or `1h30m`.
+fn is_duration(input: &str) -> bool {
+    let mut rest = input;
+    if rest.is_empty() {
+        return false;
+    }
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        let Some(unit) = ["ms", "us", "ns", "h", "m", "s"]
+            .into_iter()
+            .find(|unit| rest.starts_with(unit))
+        else {
+            return false;
+        };
+        rest = &rest[unit.len()..];
+    }
+    true
+}
+
+/// The color used for `service`'s prefix.
+pub fn service_color(service: &str) -> AnsiColors {
+    let hash = service.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    PALETTE[hash % PALETTE.len()]
+}
+
+/// Strips the replica suffix from a prefix, e.g. `pds-1` or `pds_1`.
+fn service_of(prefix: &str) -> &str {
+    prefix
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_end_matches(['-', '_'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_tail() {
+        let unbounded = LogWindow::default();
+        assert_eq!(effective_tail(None, false, &unbounded), Some(DEFAULT_TAIL));
+        assert_eq!(effective_tail(None, true, &unbounded), None);
+        assert_eq!(effective_tail(Some(50), false, &unbounded), Some(50));
+        assert_eq!(
+            effective_tail(Some(MAX_TAIL + 1), true, &unbounded),
+            Some(MAX_TAIL)
+        );
+
+        let window = LogWindow::parse(Some("30m"), None).unwrap();
+        assert_eq!(effective_tail(None, false, &window), None);
+    }
+
+    #[test]
+    fn test_log_window_parsing() {
+        let window = LogWindow::parse(Some("2024-05-01T12:00:00Z"), Some("1h30m")).unwrap();
+        assert_eq!(window.since.as_deref(), Some("2024-05-01T12:00:00Z"));
+        assert_eq!(window.until.as_deref(), Some("1h30m"));
+
+        for bad in ["yesterday", "2024-05-01 12:00", "10", "5x"] {
+            assert!(
+                matches!(LogWindow::parse(Some(bad), None), Err(Error::Config(_))),
+                "{}",
+                bad
+            );
+        }
+        assert!(matches!(
+            LogWindow::parse(Some("2024-05-02T00:00:00Z"), Some("2024-05-01T00:00:00Z")),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_pds_line_gets_pds_color() {
+        let style = LogStyle::default();
+        let line = style.format_line("pds-1  | Listening on 3000");
+        assert_eq!(
+            line,
+            format!(
+                "{} | Listening on 3000",
+                "pds-1 ".color(service_color("pds"))
+            )
+        );
+
+        assert_eq!(service_of("pds-1 "), "pds");
+        assert_eq!(service_of("feed-generator-2"), "feed-generator");
+        assert_eq!(
+            style.format_line("Attaching to pds-1"),
+            "Attaching to pds-1"
+        );
+
+        let plain = LogStyle {
+            prefix: true,
+            color: false,
+        };
+        assert_eq!(
+            plain.format_line("pds-1  | Listening on 3000"),
+            "pds-1  | Listening on 3000"
+        );
+    }
+}
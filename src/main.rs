@@ -1,19 +1,33 @@
+mod accounts;
 mod api;
+mod backup;
 mod caddy;
 mod certs;
 mod cli;
 mod commands;
 mod compose;
 mod config;
+mod configure;
+mod diff;
 mod dns;
 mod docker;
 mod error;
 mod feed;
 mod health;
+mod http;
 mod jetstream;
+mod known_hosts;
+mod logs;
+mod monitor;
+mod notify;
+mod nsid;
 mod ozone;
+mod retry;
 mod secrets;
+mod self_update;
+mod serve;
 mod status;
+mod version;
 
 use clap::Parser;
 use cli::Cli;
@@ -28,10 +42,15 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    if let Err(e) = commands::handle_command(cli.command, &cli.config).await {
+    let result = match commands::enter_project_dir(cli.dir.as_deref()) {
+        Ok(()) => commands::handle_command(cli.command, &cli.config).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
         error!("{}", e);
         eprintln!("{}", format!("Error: {}", e).red().bold());
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
     }
 
     Ok(())
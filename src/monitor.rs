@@ -0,0 +1,262 @@
+use crate::error::{Error, Result};
+use crate::health::{HealthChecker, HealthState, HealthStatus};
+use crate::notify::Notifier;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::{debug, instrument, warn};
+
+/// Tracks how long each service has been continuously unhealthy.
+#[derive(Debug, Default)]
+pub struct UnhealthyTracker {
+    since: HashMap<String, Instant>,
+}
+
+impl UnhealthyTracker {
+    /// Records a probe result, returning how long the service has been
+    /// unhealthy once that reaches `threshold`.
+    pub fn observe(
+        &mut self,
+        status: &HealthStatus,
+        now: Instant,
+        threshold: Duration,
+    ) -> Option<Duration> {
+        if status.status != HealthState::Unhealthy {
+            self.since.remove(&status.service);
+            return None;
+        }
+
+        let since = *self.since.entry(status.service.clone()).or_insert(now);
+        let elapsed = now.duration_since(since);
+        (elapsed >= threshold).then_some(elapsed)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AlertPayload<'a> {
+    service: &'a str,
+    status: &'a HealthState,
+    unhealthy_for_secs: u64,
+    latency_ms: u64,
+}
+
+/// Polls service health and alerts when one stays unhealthy too long.
+pub struct Monitor {
+    checker: HealthChecker,
+    client: Client,
+    services: Vec<String>,
+    interval: Duration,
+    threshold: Duration,
+    webhook: Option<String>,
+    notifier: Option<Notifier>,
+}
+
+impl Monitor {
+    pub fn new(checker: HealthChecker, services: Vec<String>) -> Self {
+        Self {
+            checker,
+            client: Client::new(),
+            services,
+            interval: Duration::from_secs(10),
+            threshold: Duration::from_secs(60),
+            webhook: None,
+            notifier: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_webhook(mut self, webhook: Option<String>) -> Self {
+        self.webhook = webhook;
+        self
+    }
+
+    /// Reports every health state change, not just threshold breaches.
+    pub fn with_notifier(mut self, notifier: Option<Notifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Runs until a service breaches the threshold, then fires the webhook
+    /// (if any) and returns an error naming the service.
+    #[instrument(skip(self, on_status))]
+    pub async fn run(&mut self, mut on_status: impl FnMut(&HealthStatus)) -> Result<()> {
+        let mut tracker = UnhealthyTracker::default();
+
+        loop {
+            let now = Instant::now();
+            for status in self.checker.check_all(&self.services).await? {
+                on_status(&status);
+                if let Some(notifier) = &mut self.notifier {
+                    notifier.notify(&status, now).await;
+                }
+
+                if let Some(elapsed) = tracker.observe(&status, now, self.threshold) {
+                    self.alert(&status, elapsed).await;
+                    return Err(Error::Network(format!(
+                        "{} has been unhealthy for {}s",
+                        status.service,
+                        elapsed.as_secs()
+                    )));
+                }
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// Probes every interval until `shutdown` resolves, only reporting
+    /// state changes through the notifier; the threshold never stops it.
+    #[instrument(skip(self, on_status, shutdown))]
+    pub async fn watch<T>(
+        &mut self,
+        mut on_status: impl FnMut(&HealthStatus),
+        shutdown: impl Future<Output = T>,
+    ) -> Result<()> {
+        tokio::pin!(shutdown);
+
+        loop {
+            let now = Instant::now();
+            for status in self.checker.check_all(&self.services).await? {
+                on_status(&status);
+                if let Some(notifier) = &mut self.notifier {
+                    notifier.notify(&status, now).await;
+                }
+            }
+
+            tokio::select! {
+                _ = &mut shutdown => return Ok(()),
+                _ = tokio::time::sleep(self.interval) => {}
+            }
+        }
+    }
+
+    async fn alert(&self, status: &HealthStatus, elapsed: Duration) {
+        let Some(url) = &self.webhook else {
+            return;
+        };
+
+        let payload = AlertPayload {
+            service: &status.service,
+            status: &status.status,
+            unhealthy_for_secs: elapsed.as_secs(),
+            latency_ms: status.latency_ms,
+        };
+
+        debug!("Posting alert for {} to {}", status.service, url);
+        match self.client.post(url).json(&payload).send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!("Alert webhook returned HTTP {}", response.status()),
+            Err(e) => warn!("Failed to post alert webhook: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn status(service: &str, state: HealthState) -> HealthStatus {
+        HealthStatus {
+            service: service.to_string(),
+            status: state,
+            latency_ms: 1,
+            details: None,
+        }
+    }
+
+    #[test]
+    fn test_tracker_threshold() {
+        let mut tracker = UnhealthyTracker::default();
+        let threshold = Duration::from_secs(30);
+        let start = Instant::now();
+
+        let down = status("pds", HealthState::Unhealthy);
+        assert_eq!(tracker.observe(&down, start, threshold), None);
+        assert_eq!(
+            tracker.observe(&down, start + Duration::from_secs(31), threshold),
+            Some(Duration::from_secs(31))
+        );
+
+        // Recovering resets the clock
+        let up = status("pds", HealthState::Healthy);
+        assert_eq!(
+            tracker.observe(&up, start + Duration::from_secs(32), threshold),
+            None
+        );
+        assert_eq!(
+            tracker.observe(&down, start + Duration::from_secs(40), threshold),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_monitor_fires_webhook() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/alert"))
+            .and(body_partial_json(serde_json::json!({ "service": "pds" })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let checker = HealthChecker::with_base_url(&mock_server.uri());
+        let mut monitor = Monitor::new(checker, vec!["pds".to_string()])
+            .with_interval(Duration::from_millis(10))
+            .with_threshold(Duration::from_millis(20))
+            .with_webhook(Some(format!("{}/alert", mock_server.uri())));
+
+        let mut probes = 0;
+        let result = monitor.run(|_| probes += 1).await;
+
+        assert!(matches!(result, Err(Error::Network(msg)) if msg.contains("pds")));
+        assert!(probes >= 2);
+    }
+
+    #[tokio::test]
+    async fn test_watch_runs_until_shutdown() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/_health"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&mock_server)
+            .await;
+
+        let checker = HealthChecker::with_base_url(&mock_server.uri());
+        let mut monitor = Monitor::new(checker, vec!["pds".to_string()])
+            .with_interval(Duration::from_millis(10))
+            .with_threshold(Duration::from_millis(1));
+
+        let mut probes = 0;
+        let result = monitor
+            .watch(
+                |_| probes += 1,
+                tokio::time::sleep(Duration::from_millis(100)),
+            )
+            .await;
+
+        assert!(result.is_ok());
+        assert!(probes >= 2);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::config::NotificationConfig;
+use crate::health::{HealthState, HealthStatus};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// A reported change in a service's health.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Transition {
+    pub service: String,
+    pub from: HealthState,
+    pub to: HealthState,
+}
+
+#[derive(Debug)]
+struct ServiceState {
+    reported: HealthState,
+    pending: Option<(HealthState, Instant)>,
+}
+
+/// Posts a JSON payload to a webhook when a service changes health state.
+///
+/// A new state must hold for the debounce window before it is reported, so
+/// rapid flaps don't produce a message per probe.
+pub struct Notifier {
+    client: Client,
+    webhook_url: String,
+    debounce: Duration,
+    states: HashMap<String, ServiceState>,
+}
+
+impl Notifier {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            webhook_url: webhook_url.to_string(),
+            debounce: Duration::ZERO,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Builds a notifier from config, or `None` when no webhook is configured.
+    pub fn from_config(config: &NotificationConfig) -> Option<Self> {
+        let url = config.webhook_url.as_deref()?;
+        Some(Self::new(url).with_debounce(Duration::from_secs(config.debounce_secs)))
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Records a probe result and returns a transition once the new state
+    /// has been stable for the debounce window. The first probe of a
+    /// service only sets its baseline.
+    pub fn observe(&mut self, status: &HealthStatus, now: Instant) -> Option<Transition> {
+        let Some(state) = self.states.get_mut(&status.service) else {
+            self.states.insert(
+                status.service.clone(),
+                ServiceState {
+                    reported: status.status.clone(),
+                    pending: None,
+                },
+            );
+            return None;
+        };
+
+        if status.status == state.reported {
+            state.pending = None;
+            return None;
+        }
+
+        let since = match &state.pending {
+            Some((pending, since)) if *pending == status.status => *since,
+            _ => {
+                state.pending = Some((status.status.clone(), now));
+                now
+            }
+        };
+
+        if now.duration_since(since) < self.debounce {
+            return None;
+        }
+
+        let from = std::mem::replace(&mut state.reported, status.status.clone());
+        state.pending = None;
+        Some(Transition {
+            service: status.service.clone(),
+            from,
+            to: status.status.clone(),
+        })
+    }
+
+    /// Observes `status` and posts the webhook if it completes a transition.
+    pub async fn notify(&mut self, status: &HealthStatus, now: Instant) {
+        let Some(transition) = self.observe(status, now) else {
+            return;
+        };
+
+        debug!(
+            "Notifying {} transition {:?} -> {:?}",
+            transition.service, transition.from, transition.to
+        );
+        match self
+            .client
+            .post(&self.webhook_url)
+            .json(&transition)
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!("Notification webhook returned HTTP {}", response.status()),
+            Err(e) => warn!("Failed to post notification: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn status(state: HealthState) -> HealthStatus {
+        HealthStatus {
+            service: "pds".to_string(),
+            status: state,
+            latency_ms: 1,
+            details: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transition_posts_once() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_partial_json(serde_json::json!({
+                "service": "pds",
+                "from": "Healthy",
+                "to": "Unhealthy",
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut notifier = Notifier::new(&format!("{}/hook", mock_server.uri()));
+        let now = Instant::now();
+
+        notifier.notify(&status(HealthState::Healthy), now).await;
+        notifier.notify(&status(HealthState::Unhealthy), now).await;
+        notifier.notify(&status(HealthState::Unhealthy), now).await;
+    }
+
+    #[test]
+    fn test_flaps_are_debounced() {
+        let mut notifier = Notifier::new("http://unused").with_debounce(Duration::from_secs(30));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(notifier.observe(&status(HealthState::Healthy), at(0)), None);
+        assert_eq!(
+            notifier.observe(&status(HealthState::Unhealthy), at(10)),
+            None
+        );
+        assert_eq!(
+            notifier.observe(&status(HealthState::Healthy), at(20)),
+            None
+        );
+        assert_eq!(
+            notifier.observe(&status(HealthState::Unhealthy), at(30)),
+            None
+        );
+
+        let transition = notifier
+            .observe(&status(HealthState::Unhealthy), at(60))
+            .unwrap();
+        assert_eq!(transition.from, HealthState::Healthy);
+        assert_eq!(transition.to, HealthState::Unhealthy);
+    }
+}
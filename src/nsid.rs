@@ -0,0 +1,155 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A Namespaced Identifier such as `app.bsky.feed.post`: a reverse-DNS
+/// authority followed by a name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Nsid(String);
+
+impl Nsid {
+    const MAX_LENGTH: usize = 317;
+    const MAX_SEGMENT_LENGTH: usize = 63;
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The final segment, e.g. `post` for `app.bsky.feed.post`.
+    pub fn name(&self) -> &str {
+        self.0.rsplit('.').next().unwrap_or_default()
+    }
+
+    fn check(s: &str) -> std::result::Result<(), String> {
+        if s.len() > Self::MAX_LENGTH {
+            return Err(format!("longer than {} characters", Self::MAX_LENGTH));
+        }
+
+        let segments: Vec<&str> = s.split('.').collect();
+        if segments.len() < 3 {
+            return Err("expected at least three dot-separated segments".into());
+        }
+
+        let (name, authority) = segments.split_last().unwrap();
+        for (index, segment) in authority.iter().enumerate() {
+            if segment.is_empty() || segment.len() > Self::MAX_SEGMENT_LENGTH {
+                return Err(format!(
+                    "segment {:?} must be 1-{} characters",
+                    segment,
+                    Self::MAX_SEGMENT_LENGTH
+                ));
+            }
+            if !segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+            {
+                return Err(format!(
+                    "segment {:?} may only contain letters, digits and hyphens",
+                    segment
+                ));
+            }
+            if segment.starts_with('-') || segment.ends_with('-') {
+                return Err(format!(
+                    "segment {:?} cannot start or end with a hyphen",
+                    segment
+                ));
+            }
+            if index == 0 && segment.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(format!("segment {:?} cannot start with a digit", segment));
+            }
+        }
+
+        if name.is_empty() || name.len() > Self::MAX_SEGMENT_LENGTH {
+            return Err(format!(
+                "name {:?} must be 1-{} characters",
+                name,
+                Self::MAX_SEGMENT_LENGTH
+            ));
+        }
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(format!(
+                "name {:?} must start with a letter and contain only letters and digits",
+                name
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Nsid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::check(s)
+            .map(|()| Self(s.to_string()))
+            .map_err(|reason| {
+                Error::Config(format!(
+                    "Invalid NSID {:?}: {} (expected e.g. app.bsky.feed.post)",
+                    s, reason
+                ))
+            })
+    }
+}
+
+impl TryFrom<String> for Nsid {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Nsid> for String {
+    fn from(nsid: Nsid) -> Self {
+        nsid.0
+    }
+}
+
+impl fmt::Display for Nsid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jetstream::{standard_collections, STANDARD_COLLECTIONS};
+    use test_case::test_case;
+
+    #[test_case("app.bsky.feed.post" ; "feed post")]
+    #[test_case("com.atproto.repo.createRecord" ; "camel case name")]
+    #[test_case("net.example-host.v2.thing" ; "hyphenated authority")]
+    fn test_valid_nsid(input: &str) {
+        let nsid: Nsid = input.parse().unwrap();
+        assert_eq!(nsid.to_string(), input);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("app.bsky" ; "too few segments")]
+    #[test_case("app..feed.post" ; "empty segment")]
+    #[test_case("1app.bsky.post" ; "leading digit")]
+    #[test_case("app.-bsky.post" ; "leading hyphen")]
+    #[test_case("app.bsky.feed.post-v2" ; "hyphen in name")]
+    #[test_case("app.bsky.feed.*" ; "wildcard")]
+    fn test_invalid_nsid(input: &str) {
+        assert!(matches!(input.parse::<Nsid>(), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_standard_collections_are_valid() {
+        for collection in STANDARD_COLLECTIONS {
+            assert!(collection.parse::<Nsid>().is_ok(), "{}", collection);
+        }
+        assert_eq!(standard_collections().len(), STANDARD_COLLECTIONS.len());
+        assert_eq!(
+            "app.bsky.graph.follow".parse::<Nsid>().unwrap().name(),
+            "follow"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::{Error, Result};
+use crate::http::{read_json, request_id, send_traced};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, instrument};
@@ -16,6 +17,20 @@ pub struct UpdateDidDocResponse {
     pub updated: bool,
 }
 
+/// An admin to configure: their handle and the PLC sign token they were sent.
+#[derive(Debug, Clone)]
+pub struct OzoneAdmin {
+    pub handle: String,
+    pub plc_sign_token: String,
+}
+
+/// Outcome of configuring a single admin.
+#[derive(Debug)]
+pub struct AdminResult {
+    pub handle: String,
+    pub outcome: std::result::Result<UpdateDidDocResponse, String>,
+}
+
 pub struct OzoneClient {
     client: Client,
     base_url: String,
@@ -32,42 +47,29 @@ impl OzoneClient {
         }
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self), fields(request_id))]
     pub async fn request_plc_sign(&self, handle: &str) -> Result<String> {
         let url = format!("{}/api/ozone/reqPlcSign", self.base_url);
         debug!("Requesting PLC sign for handle: {}", handle);
 
-        let response = self
+        let request = self
             .client
             .post(&url)
             .header("Content-Type", "application/json")
             .body(serde_json::to_string(&serde_json::json!({
                 "handle": handle
-            }))?)
-            .send()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to request PLC sign: {}", e)))?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(Error::Api(format!(
-                "Failed to request PLC sign: {}",
-                error_text
-            )));
-        }
-
-        let token = response
-            .text()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to read response: {}", e)))?;
-
-        Ok(token)
+            }))?);
+        let response = send_traced(request, "request PLC sign").await?;
+        let request_id = request_id(&response).to_string();
+        response.text().await.map_err(|e| {
+            Error::Api(format!(
+                "Failed to read response [request {}]: {}",
+                request_id, e
+            ))
+        })
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self, plc_sign_token), fields(request_id))]
     pub async fn update_did_doc(
         &self,
         plc_sign_token: &str,
@@ -77,33 +79,38 @@ impl OzoneClient {
         let url = format!("{}/api/ozone/updateDidDoc", self.base_url);
         debug!("Updating DID doc at {}", url);
 
-        let response = self
-            .client
-            .post(&url)
-            .json(&serde_json::json!({
-                "plc_sign_token": plc_sign_token,
-                "handle": handle,
-                "ozone_url": ozone_url,
-            }))
-            .send()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to update DID doc: {}", e)))?;
+        let request = self.client.post(&url).json(&UpdateDidDocRequest {
+            plc_sign_token: plc_sign_token.to_string(),
+            handle: handle.to_string(),
+            ozone_url: ozone_url.to_string(),
+        });
+        let response = send_traced(request, "update DID doc").await?;
+        read_json(response).await
+    }
 
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
+    /// Updates the DID doc of each admin in turn, continuing past failures.
+    /// `on_result` is called as each admin finishes.
+    pub async fn configure_admins(
+        &self,
+        admins: &[OzoneAdmin],
+        ozone_url: &str,
+        mut on_result: impl FnMut(&AdminResult),
+    ) -> Vec<AdminResult> {
+        let mut results = Vec::with_capacity(admins.len());
+        for admin in admins {
+            let outcome = self
+                .update_did_doc(&admin.plc_sign_token, &admin.handle, ozone_url)
                 .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(Error::Api(format!(
-                "Failed to update DID doc: {}",
-                error_text
-            )));
+                .map_err(|e| e.to_string());
+
+            let result = AdminResult {
+                handle: admin.handle.clone(),
+                outcome,
+            };
+            on_result(&result);
+            results.push(result);
         }
-
-        response
-            .json::<UpdateDidDocResponse>()
-            .await
-            .map_err(|e| Error::Api(format!("Failed to parse response: {}", e)))
+        results
     }
 }
 
@@ -111,7 +118,7 @@ impl OzoneClient {
 mod tests {
     use super::*;
     use serde_json::json;
-    use wiremock::matchers::{header, method, path};
+    use wiremock::matchers::{body_partial_json, header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -162,4 +169,55 @@ mod tests {
         assert_eq!(response.did, "did:plc:test123");
         assert!(response.updated);
     }
+
+    #[tokio::test]
+    async fn test_configure_admins_continues_past_failures() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/ozone/updateDidDoc"))
+            .and(body_partial_json(json!({ "handle": "alice.test.com" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "did": "did:plc:alice",
+                "updated": true
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/ozone/updateDidDoc"))
+            .and(body_partial_json(json!({ "handle": "bob.test.com" })))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Invalid token"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = OzoneClient {
+            client: Client::new(),
+            base_url: mock_server.uri(),
+        };
+        let admins = [
+            OzoneAdmin {
+                handle: "bob.test.com".to_string(),
+                plc_sign_token: "bad_token".to_string(),
+            },
+            OzoneAdmin {
+                handle: "alice.test.com".to_string(),
+                plc_sign_token: "good_token".to_string(),
+            },
+        ];
+
+        let mut reported = 0;
+        let results = client
+            .configure_admins(&admins, "https://ozone.test.com", |_| reported += 1)
+            .await;
+
+        assert_eq!(reported, 2);
+        assert!(matches!(
+            &results[0].outcome,
+            Err(msg) if msg.contains("Invalid token")
+        ));
+        assert_eq!(results[1].outcome.as_ref().unwrap().did, "did:plc:alice");
+    }
 }
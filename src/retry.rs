@@ -0,0 +1,120 @@
+use crate::error::Result;
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Exponential backoff with "equal jitter": each delay is picked uniformly
+/// between half and all of the current exponential step, capped at `max`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// Un-jittered delay for the given attempt, never exceeding the cap.
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let base = self.base_delay(self.attempt);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = base / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(250), Duration::from_secs(10))
+    }
+}
+
+/// Polls `check` until it reports ready or `timeout` elapses.
+///
+/// Returns `Ok(false)` on timeout; errors from `check` are propagated.
+pub async fn poll_until<F, Fut>(
+    timeout: Duration,
+    mut backoff: Backoff,
+    mut check: F,
+) -> Result<bool>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if check().await? {
+            return Ok(true);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(false);
+        }
+
+        let delay = backoff.next_delay().min(deadline - now);
+        debug!("Not ready yet, retrying in {:?}", delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_within_cap() {
+        let cap = Duration::from_secs(2);
+        let mut backoff = Backoff::new(Duration::from_millis(100), cap);
+
+        let bases: Vec<_> = (0..8).map(|attempt| backoff.base_delay(attempt)).collect();
+        assert!(bases.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(bases[0], Duration::from_millis(100));
+        assert_eq!(bases[3], Duration::from_millis(800));
+        assert_eq!(*bases.last().unwrap(), cap);
+
+        for attempt in 0..20 {
+            let base = backoff.base_delay(attempt);
+            let delay = backoff.next_delay();
+            assert!(delay >= base / 2 && delay <= base);
+            assert!(delay <= cap);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_until() -> Result<()> {
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(5));
+
+        let mut calls = 0;
+        let ready = poll_until(Duration::from_secs(5), backoff.clone(), || {
+            calls += 1;
+            let done = calls >= 3;
+            async move { Ok(done) }
+        })
+        .await?;
+        assert!(ready);
+        assert_eq!(calls, 3);
+
+        let ready = poll_until(Duration::from_millis(20), backoff, || async { Ok(false) }).await?;
+        assert!(!ready);
+
+        Ok(())
+    }
+}
@@ -1,14 +1,29 @@
 use crate::error::{Error, Result};
 use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
-use tracing::instrument;
+use std::future::Future;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, instrument, warn};
+
+/// The Postgres password compose used before it became a generated secret.
+/// Postgres only reads its password when the data volume is first created,
+/// so installs from then keep needing it.
+pub const LEGACY_POSTGRES_PASSWORD: &str = "postgres";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Secrets {
     pub pds_jwt_secret: String,
     pub pds_admin_password: String,
     pub pds_plc_rotation_key: String,
+    pub postgres_password: String,
+    /// DNS provider credentials for Caddy's DNS-01 challenge, keyed by
+    /// environment variable
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dns_credentials: BTreeMap<String, String>,
 }
 
 impl Secrets {
@@ -20,27 +35,100 @@ impl Secrets {
             pds_jwt_secret: generate_secure_string(&mut rng, 32),
             pds_admin_password: generate_secure_string(&mut rng, 16),
             pds_plc_rotation_key: generate_base32_key(&mut rng),
+            postgres_password: generate_secure_string(&mut rng, 32),
+            dns_credentials: BTreeMap::new(),
         }
     }
 
+    pub fn generate_admin_password() -> String {
+        generate_secure_string(&mut rand::thread_rng(), 16)
+    }
+
+    /// Copies the secrets file to `<path>.bak`, replacing any older backup.
+    #[instrument(skip(path))]
+    pub fn backup(path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+
+        fs::copy(path, &backup)?;
+        Ok(backup)
+    }
+
     #[instrument(skip(path))]
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
         let content = toml::to_string_pretty(self)
             .map_err(|e| Error::Config(format!("Failed to serialize secrets: {}", e)))?;
+        write_private(path, &content)
+    }
 
-        if let Some(parent) = path.as_ref().parent() {
-            fs::create_dir_all(parent)?;
+    /// Loads the secrets file without modifying it. Secrets added since the
+    /// file was written get their migration value for this run only;
+    /// `upgrade` persists them.
+    #[instrument(skip(path))]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let (secrets, added) = Self::read(path.as_ref())?;
+        if !added.is_empty() {
+            debug!("Secrets file lacks {}", added.join(", "));
         }
+        Ok(secrets)
+    }
 
-        fs::write(path, content)?;
-        Ok(())
+    /// Loads the secrets file and, if secrets were added since it was
+    /// written, backs it up and saves them. An existing `.env` at `env_path`
+    /// is regenerated too so `docker compose` run by hand sees them.
+    #[instrument(skip(path, env_path))]
+    pub fn upgrade(
+        path: impl AsRef<Path>,
+        env_path: impl AsRef<Path>,
+        domain: &str,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let (secrets, added) = Self::read(path)?;
+        if added.is_empty() {
+            return Ok(secrets);
+        }
+
+        let backup = Self::backup(path)?;
+        secrets.save(path)?;
+        let env_path = env_path.as_ref();
+        if env_path.exists() {
+            secrets.save_env_file(env_path, domain)?;
+        }
+        warn!(
+            "Added missing secrets {} (previous file backed up to {:?})",
+            added.join(", "),
+            backup
+        );
+        Ok(secrets)
     }
 
-    #[instrument(skip(path))]
-    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+    /// Parses the file at `path`, returning the secrets with missing ones
+    /// filled in and the names of those that were missing.
+    fn read(path: &Path) -> Result<(Self, Vec<String>)> {
         let content = fs::read_to_string(path)?;
-        toml::from_str(&content)
-            .map_err(|e| Error::Config(format!("Failed to parse secrets: {}", e)))
+        let mut table: toml::Table = toml::from_str(&content)
+            .map_err(|e| Error::Config(format!("Failed to parse secrets: {}", e)))?;
+
+        let added = fill_missing(&mut table)?;
+        let secrets = table
+            .try_into()
+            .map_err(|e| Error::Config(format!("Failed to parse secrets: {}", e)))?;
+        Ok((secrets, added))
+    }
+
+    /// Writes every secret plus `DOMAIN` as `KEY=VALUE` lines, readable
+    /// only by the owner. Compose reads `.env` next to the compose file, so
+    /// running `docker-compose` by hand gets the same values as atc.
+    #[instrument(skip(self, path))]
+    pub fn save_env_file(&self, path: impl AsRef<Path>, domain: &str) -> Result<()> {
+        let mut content = String::from("# Generated by atc; keep private\n");
+        content.push_str(&format!("DOMAIN={}\n", domain));
+        for (key, value) in self.as_env_vars() {
+            content.push_str(&format!("{}={}\n", key, value));
+        }
+        write_private(path, &content)
     }
 
     pub fn as_env_vars(&self) -> Vec<(String, String)> {
@@ -51,10 +139,120 @@ impl Secrets {
                 "PDS_PLC_ROTATION_KEY_K256".into(),
                 self.pds_plc_rotation_key.clone(),
             ),
+            ("POSTGRES_PASSWORD".into(), self.postgres_password.clone()),
         ]
+        .into_iter()
+        .chain(self.dns_credentials.clone())
+        .collect()
     }
 }
 
+/// Generates a new admin password and hands it to `apply`, which must make
+/// the running PDS use it. Only once `apply` succeeds is the old file backed
+/// up and the new password persisted; on failure the file is left untouched.
+pub async fn rotate_admin_password<F, Fut>(path: impl AsRef<Path>, apply: F) -> Result<String>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let path = path.as_ref();
+    let mut secrets = Secrets::load(path)?;
+
+    let new_password = Secrets::generate_admin_password();
+    apply(new_password.clone()).await?;
+
+    let backup = Secrets::backup(path)?;
+    info!("Backed up previous secrets to {:?}", backup);
+
+    secrets.pds_admin_password = new_password.clone();
+    secrets.save(path)?;
+    Ok(new_password)
+}
+
+/// Replaces every secret in the file at `path` with a freshly generated one,
+/// backing up the old file first. The PLC rotation key is carried over
+/// unless `rotate_plc_key` is set, since the DID documents already published
+/// to the PLC directory still reference the old key. The Postgres password
+/// is always kept: Postgres only reads it when the data directory is created.
+#[instrument(skip(path))]
+pub fn rotate_secrets(path: impl AsRef<Path>, rotate_plc_key: bool) -> Result<Secrets> {
+    let path = path.as_ref();
+    let old = Secrets::load(path)?;
+
+    let mut secrets = Secrets::generate();
+    if !rotate_plc_key {
+        secrets.pds_plc_rotation_key = old.pds_plc_rotation_key;
+    }
+    secrets.postgres_password = old.postgres_password;
+    secrets.dns_credentials = old.dns_credentials;
+
+    let backup = Secrets::backup(path)?;
+    info!("Backed up previous secrets to {:?}", backup);
+
+    secrets.save(path)?;
+    Ok(secrets)
+}
+
+/// Adds the migration value of every secret missing from `table`,
+/// returning the names of the ones added.
+fn fill_missing(table: &mut toml::Table) -> Result<Vec<String>> {
+    let generated = toml::Table::try_from(Secrets::generate())
+        .map_err(|e| Error::Config(format!("Failed to serialize secrets: {}", e)))?;
+
+    let mut added = Vec::new();
+    for (key, value) in generated {
+        if !table.contains_key(&key) {
+            table.insert(key.clone(), migration_value(&key, value));
+            added.push(key);
+        }
+    }
+    Ok(added)
+}
+
+/// The value a secret missing from an existing file starts with. Secrets
+/// tied to state that already exists keep what older releases used; the
+/// rest take the freshly `generated` value.
+fn migration_value(key: &str, generated: toml::Value) -> toml::Value {
+    match key {
+        "postgres_password" => LEGACY_POSTGRES_PASSWORD.into(),
+        _ => generated,
+    }
+}
+
+/// Writes `content` to `path`, restricting it to the owner on Unix before
+/// anything is written.
+pub fn write_private(path: impl AsRef<Path>, content: &str) -> Result<()> {
+    use std::io::Write;
+
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    create_private(path)?.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Creates or truncates `path` for writing, restricted to the owner on Unix
+/// before anything is written to it.
+pub fn create_private(path: impl AsRef<Path>) -> Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path)?;
+
+    // `mode` only applies to new files, so tighten one that already existed
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(file)
+}
+
 fn generate_secure_string(rng: &mut impl Rng, len: usize) -> String {
     rng.sample_iter(&Alphanumeric)
         .take(len)
@@ -75,7 +273,10 @@ fn generate_base32_key(rng: &mut impl Rng) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::PdsClient;
     use assert_fs::prelude::*;
+    use wiremock::matchers::{header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn test_generate_secrets() {
@@ -101,6 +302,13 @@ mod tests {
             .pds_plc_rotation_key
             .chars()
             .all(|c| "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567".contains(c)));
+
+        // Check Postgres password
+        assert_eq!(secrets.postgres_password.len(), 32);
+        assert!(secrets
+            .postgres_password
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric()));
     }
 
     #[test]
@@ -116,10 +324,56 @@ mod tests {
         assert_eq!(secrets.pds_jwt_secret, loaded.pds_jwt_secret);
         assert_eq!(secrets.pds_admin_password, loaded.pds_admin_password);
         assert_eq!(secrets.pds_plc_rotation_key, loaded.pds_plc_rotation_key);
+        assert_eq!(secrets.postgres_password, loaded.postgres_password);
 
         Ok(())
     }
 
+    #[test]
+    fn test_load_fills_missing_secrets() -> Result<()> {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let secrets_path = temp.child("secrets.toml");
+        let original = "pds_jwt_secret = \"jwt\"\n\
+                        pds_admin_password = \"admin\"\n\
+                        pds_plc_rotation_key = \"KEY\"\n";
+        secrets_path.write_str(original).unwrap();
+
+        let secrets = Secrets::load(&secrets_path)?;
+        assert_eq!(secrets.pds_jwt_secret, "jwt");
+        assert_eq!(secrets.pds_plc_rotation_key, "KEY");
+        assert_eq!(secrets.postgres_password, LEGACY_POSTGRES_PASSWORD);
+        secrets_path.assert(original);
+
+        let env_path = temp.child(".env");
+        env_path.write_str("POSTGRES_PASSWORD=\n").unwrap();
+        Secrets::upgrade(&secrets_path, &env_path, "example.com")?;
+        temp.child("secrets.toml.bak").assert(original);
+        let saved = fs::read_to_string(&secrets_path)?;
+        assert!(saved.contains("postgres_password = \"postgres\""));
+        env_path.assert(predicates::str::contains("POSTGRES_PASSWORD=postgres\n"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secrets_file_is_private() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let secrets_path = temp.child("config/secrets.toml");
+        Secrets::generate().save(&secrets_path)?;
+
+        let mode = fs::metadata(&secrets_path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // An existing world-readable file is tightened too
+        fs::set_permissions(&secrets_path, fs::Permissions::from_mode(0o644))?;
+        Secrets::generate().save(&secrets_path)?;
+        let mode = fs::metadata(&secrets_path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        Ok(())
+    }
+
     #[test]
     fn test_env_vars() {
         let secrets = Secrets::generate();
@@ -130,9 +384,152 @@ mod tests {
         assert!(env_vars
             .iter()
             .any(|(k, _)| k == "PDS_PLC_ROTATION_KEY_K256"));
+        assert!(env_vars.iter().any(|(k, _)| k == "POSTGRES_PASSWORD"));
 
         for (_, v) in env_vars {
             assert!(!v.is_empty());
         }
     }
+
+    #[test]
+    fn test_compose_uses_generated_postgres_password() -> Result<()> {
+        let secrets = Secrets::generate();
+        assert!(!secrets.postgres_password.is_empty());
+
+        let mut compose = crate::compose::ComposeConfig::new();
+        compose.add_appview();
+        let env = secrets.as_env_vars().into_iter().collect();
+
+        let db = compose.resolve_environment("db", &env)?;
+        let password = db.iter().find(|v| v.key == "POSTGRES_PASSWORD").unwrap();
+        assert_eq!(password.references, vec!["POSTGRES_PASSWORD"]);
+        assert_eq!(password.value, secrets.postgres_password);
+
+        let appview = compose.resolve_environment("appview", &env)?;
+        let url = appview
+            .iter()
+            .find(|v| v.key == "APPVIEW_DATABASE_URL")
+            .unwrap();
+        assert!(url.value.contains(&secrets.postgres_password));
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_env_file() -> Result<()> {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let env_path = temp.child(".env");
+
+        let secrets = Secrets::generate();
+        secrets.save_env_file(&env_path, "test.com")?;
+
+        let content = fs::read_to_string(&env_path)?;
+        assert!(content.contains("DOMAIN=test.com\n"));
+        assert!(content.contains(&format!("PDS_JWT_SECRET={}\n", secrets.pds_jwt_secret)));
+        assert!(content.contains(&format!(
+            "PDS_ADMIN_PASSWORD={}\n",
+            secrets.pds_admin_password
+        )));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&env_path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_secrets_keeps_plc_key_by_default() -> Result<()> {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let secrets_path = temp.child("secrets.toml");
+        let original = Secrets::generate();
+        original.save(&secrets_path)?;
+
+        let rotated = rotate_secrets(&secrets_path, false)?;
+        assert_ne!(rotated.pds_jwt_secret, original.pds_jwt_secret);
+        assert_ne!(rotated.pds_admin_password, original.pds_admin_password);
+        assert_eq!(rotated.pds_plc_rotation_key, original.pds_plc_rotation_key);
+        assert_eq!(rotated.postgres_password, original.postgres_password);
+
+        let saved = Secrets::load(&secrets_path)?;
+        assert_eq!(saved.pds_jwt_secret, rotated.pds_jwt_secret);
+        let backup = Secrets::load(temp.child("secrets.toml.bak"))?;
+        assert_eq!(backup.pds_jwt_secret, original.pds_jwt_secret);
+
+        let rotated = rotate_secrets(&secrets_path, true)?;
+        assert_ne!(rotated.pds_plc_rotation_key, original.pds_plc_rotation_key);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_admin_password() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.admin.getInviteCodes"))
+            .and(header_exists("Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let secrets_path = temp.child("secrets.toml");
+        let original = Secrets::generate();
+        original.save(&secrets_path)?;
+
+        let new_password = rotate_admin_password(&secrets_path, |password| async move {
+            match client.verify_admin_password(&password).await? {
+                true => Ok(()),
+                false => Err(Error::Api("rejected".into())),
+            }
+        })
+        .await?;
+
+        let rotated = Secrets::load(&secrets_path)?;
+        assert_eq!(rotated.pds_admin_password, new_password);
+        assert_ne!(rotated.pds_admin_password, original.pds_admin_password);
+        assert_eq!(rotated.pds_jwt_secret, original.pds_jwt_secret);
+
+        let backup = Secrets::load(temp.child("secrets.toml.bak"))?;
+        assert_eq!(backup.pds_admin_password, original.pds_admin_password);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_admin_password_aborts_on_failure() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = PdsClient::with_base_url(&mock_server.uri());
+
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.admin.getInviteCodes"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let secrets_path = temp.child("secrets.toml");
+        let original = Secrets::generate();
+        original.save(&secrets_path)?;
+
+        let result = rotate_admin_password(&secrets_path, |password| async move {
+            match client.verify_admin_password(&password).await? {
+                true => Ok(()),
+                false => Err(Error::Api("rejected".into())),
+            }
+        })
+        .await;
+        assert!(result.is_err());
+
+        let unchanged = Secrets::load(&secrets_path)?;
+        assert_eq!(unchanged.pds_admin_password, original.pds_admin_password);
+        temp.child("secrets.toml.bak")
+            .assert(predicates::path::missing());
+
+        Ok(())
+    }
 }
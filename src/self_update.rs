@@ -0,0 +1,236 @@
+use crate::error::{Error, Result};
+use crate::version::Version;
+use reqwest::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tracing::{debug, instrument};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/mekaem/atc/releases/latest";
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+pub struct SelfUpdater {
+    client: Client,
+    releases_url: String,
+}
+
+impl SelfUpdater {
+    pub fn new() -> Result<Self> {
+        let client = Client::builder()
+            .user_agent(concat!("atc/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| Error::Update(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            releases_url: RELEASES_URL.to_string(),
+        })
+    }
+
+    pub fn current_version() -> Version {
+        env!("CARGO_PKG_VERSION")
+            .parse()
+            .expect("Crate version is valid")
+    }
+
+    /// Binary asset name for the platform we were built for.
+    pub fn asset_name() -> String {
+        format!("atc-{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn latest_release(&self) -> Result<Release> {
+        debug!("Fetching latest release from {}", self.releases_url);
+
+        let response = self
+            .client
+            .get(&self.releases_url)
+            .send()
+            .await
+            .map_err(|e| Error::Update(format!("Failed to fetch latest release: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Update(format!(
+                "Failed to fetch latest release: HTTP {}",
+                response.status()
+            )));
+        }
+
+        response
+            .json::<Release>()
+            .await
+            .map_err(|e| Error::Update(format!("Failed to parse release: {}", e)))
+    }
+
+    /// Returns the latest release if it is newer than the running binary.
+    pub async fn check(&self) -> Result<Option<(Version, Release)>> {
+        let release = self.latest_release().await?;
+        let latest: Version = release.tag_name.parse()?;
+
+        if latest > Self::current_version() {
+            Ok(Some((latest, release)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Downloads the platform binary from `release`, verifies it against the
+    /// published `.sha256` asset and replaces the executable at `target`.
+    ///
+    /// The checksum comes from the same release as the binary, so it catches
+    /// a corrupted download but does not prove who published the release.
+    #[instrument(skip(self, release))]
+    pub async fn install(&self, release: &Release, target: &Path) -> Result<()> {
+        let asset_name = Self::asset_name();
+        let checksum_name = format!("{}.sha256", asset_name);
+
+        let find = |name: &str| {
+            release
+                .assets
+                .iter()
+                .find(|a| a.name == name)
+                .ok_or_else(|| {
+                    Error::Update(format!(
+                        "Release {} has no asset named {}",
+                        release.tag_name, name
+                    ))
+                })
+        };
+        let binary_asset = find(&asset_name)?;
+        let checksum_asset = find(&checksum_name)?;
+
+        let binary = self.download(&binary_asset.browser_download_url).await?;
+        let checksum = self.download(&checksum_asset.browser_download_url).await?;
+        verify_checksum(&binary, &String::from_utf8_lossy(&checksum))?;
+
+        // Write next to the target so the final rename stays on one filesystem
+        let staged = target.with_extension("new");
+        tokio::fs::write(&staged, &binary).await?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)).await?;
+        }
+
+        tokio::fs::rename(&staged, target).await?;
+        Ok(())
+    }
+
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        debug!("Downloading {}", url);
+
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| Error::Update(format!("Failed to download {}: {}", url, e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Update(format!(
+                "Failed to download {}: HTTP {}",
+                url,
+                response.status()
+            )));
+        }
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Update(format!("Failed to download {}: {}", url, e)))?;
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Checks `data` against a `sha256sum`-style line (`<hex digest>  <file>`).
+fn verify_checksum(data: &[u8], checksum_file: &str) -> Result<()> {
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| Error::Update("Checksum file is empty".into()))?
+        .to_ascii_lowercase();
+
+    let actual: String = Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    if actual != expected {
+        return Err(Error::Update(format!(
+            "Checksum mismatch: expected {}, got {}",
+            expected, actual
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_updater(server: &MockServer) -> SelfUpdater {
+        SelfUpdater {
+            client: Client::new(),
+            releases_url: format!("{}/releases/latest", server.uri()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_newer_release() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/releases/latest"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "tag_name": "v99.0.0",
+                "assets": []
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let (version, release) = test_updater(&mock_server).check().await.unwrap().unwrap();
+        assert_eq!(version, Version::new(99, 0, 0));
+        assert_eq!(release.tag_name, "v99.0.0");
+    }
+
+    #[tokio::test]
+    async fn test_check_when_up_to_date() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/releases/latest"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "tag_name": format!("v{}", env!("CARGO_PKG_VERSION")),
+                "assets": []
+            })))
+            .mount(&mock_server)
+            .await;
+
+        assert!(test_updater(&mock_server).check().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        // sha256("hello")
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify_checksum(b"hello", &format!("{}  atc", digest)).is_ok());
+        assert!(verify_checksum(b"tampered", digest).is_err());
+        assert!(verify_checksum(b"hello", "").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::error::{Error, Result};
+use crate::health::{HealthChecker, HealthState, HealthStatus};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+/// Requests larger than this are rejected before parsing.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a client gets to send its request before it is dropped.
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves service health as Prometheus metrics on `/metrics`, plus a
+/// `/healthz` liveness route for atc itself.
+pub struct MetricsServer {
+    listener: TcpListener,
+    checker: Arc<HealthChecker>,
+    services: Arc<Vec<String>>,
+    read_timeout: Duration,
+}
+
+impl MetricsServer {
+    pub async fn bind(
+        addr: SocketAddr,
+        checker: HealthChecker,
+        services: Vec<String>,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| Error::Network(format!("Failed to listen on {}: {}", addr, e)))?;
+        Ok(Self {
+            listener,
+            checker: Arc::new(checker),
+            services: Arc::new(services),
+            read_timeout: DEFAULT_READ_TIMEOUT,
+        })
+    }
+
+    /// Drops connections that haven't sent a full request after `timeout`.
+    #[cfg(test)]
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts connections until the process is stopped.
+    pub async fn run(self) -> Result<()> {
+        info!("Serving metrics on http://{}", self.local_addr()?);
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let checker = Arc::clone(&self.checker);
+            let services = Arc::clone(&self.services);
+            let read_timeout = self.read_timeout;
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, &checker, &services, read_timeout).await {
+                    debug!("Request from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    checker: &HealthChecker,
+    services: &[String],
+    read_timeout: Duration,
+) -> Result<()> {
+    let buf = match tokio::time::timeout(read_timeout, read_request(&mut stream)).await {
+        Ok(Ok(Some(buf))) => buf,
+        Ok(Ok(None)) => {
+            return respond(&mut stream, "413 Payload Too Large", "text/plain", "").await
+        }
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return respond(&mut stream, "408 Request Timeout", "text/plain", "").await,
+    };
+
+    let request = String::from_utf8_lossy(&buf);
+    let mut parts = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+
+    match (method, path) {
+        (Some("GET"), Some("/healthz")) => {
+            respond(&mut stream, "200 OK", "text/plain", "ok\n").await
+        }
+        (Some("GET"), Some("/metrics")) => match checker.check_all(services).await {
+            Ok(statuses) => {
+                let body = render_metrics(&statuses);
+                respond(&mut stream, "200 OK", "text/plain; version=0.0.4", &body).await
+            }
+            Err(e) => {
+                warn!("Health probe failed: {}", e);
+                respond(&mut stream, "503 Service Unavailable", "text/plain", "").await
+            }
+        },
+        (Some("GET"), _) => respond(&mut stream, "404 Not Found", "text/plain", "").await,
+        _ => respond(&mut stream, "405 Method Not Allowed", "text/plain", "").await,
+    }
+}
+
+/// Reads up to the end of the request headers, or `None` if they exceed
+/// `MAX_REQUEST_BYTES`.
+async fn read_request(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+    }
+    Ok(Some(buf))
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Prometheus text exposition of the probed services.
+fn render_metrics(statuses: &[HealthStatus]) -> String {
+    let mut out = String::new();
+    out.push_str("# HELP atc_service_up Whether the service passed its health check.\n");
+    out.push_str("# TYPE atc_service_up gauge\n");
+    for status in statuses {
+        let up = u8::from(status.status == HealthState::Healthy);
+        let _ = writeln!(
+            out,
+            "atc_service_up{{service=\"{}\"}} {}",
+            status.service, up
+        );
+    }
+
+    out.push_str("# HELP atc_service_latency_ms Latency of the last health check.\n");
+    out.push_str("# TYPE atc_service_latency_ms gauge\n");
+    for status in statuses {
+        let _ = writeln!(
+            out,
+            "atc_service_latency_ms{{service=\"{}\"}} {}",
+            status.service, status.latency_ms
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_healthz() -> Result<()> {
+        let checker = HealthChecker::with_base_url("http://127.0.0.1:9");
+        let server =
+            MetricsServer::bind("127.0.0.1:0".parse().unwrap(), checker, Vec::new()).await?;
+        let addr = server.local_addr()?;
+        let handle = tokio::spawn(server.run());
+
+        let response = reqwest::get(format!("http://{}/healthz", addr))
+            .await
+            .map_err(|e| Error::Network(e.to_string()))?;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "ok\n");
+
+        let response = reqwest::get(format!("http://{}/nope", addr))
+            .await
+            .map_err(|e| Error::Network(e.to_string()))?;
+        assert_eq!(response.status(), 404);
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_connection_times_out() -> Result<()> {
+        let checker = HealthChecker::with_base_url("http://127.0.0.1:9");
+        let server = MetricsServer::bind("127.0.0.1:0".parse().unwrap(), checker, Vec::new())
+            .await?
+            .with_read_timeout(Duration::from_millis(100));
+        let addr = server.local_addr()?;
+        let handle = tokio::spawn(server.run());
+
+        // Connect but never send a request
+        let mut stream = TcpStream::connect(addr).await?;
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+            .await
+            .expect("server should close the idle connection")?;
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let statuses = vec![
+            HealthStatus {
+                service: "pds".to_string(),
+                status: HealthState::Healthy,
+                latency_ms: 12,
+                details: None,
+            },
+            HealthStatus {
+                service: "bgs".to_string(),
+                status: HealthState::Unhealthy,
+                latency_ms: 5000,
+                details: None,
+            },
+        ];
+
+        let metrics = render_metrics(&statuses);
+        assert!(metrics.contains("atc_service_up{service=\"pds\"} 1\n"));
+        assert!(metrics.contains("atc_service_up{service=\"bgs\"} 0\n"));
+        assert!(metrics.contains("atc_service_latency_ms{service=\"bgs\"} 5000\n"));
+    }
+}
@@ -4,7 +4,7 @@ use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use time::OffsetDateTime;
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceStatus {
@@ -23,6 +23,19 @@ pub struct SystemStatus {
     pub timestamp: OffsetDateTime,
 }
 
+impl SystemStatus {
+    /// Which of `expected` are known here but not running, sorted.
+    pub fn stopped_services(&self, expected: &[String]) -> Vec<String> {
+        let mut stopped: Vec<String> = expected
+            .iter()
+            .filter(|name| self.services.get(*name).is_some_and(|s| !s.running))
+            .cloned()
+            .collect();
+        stopped.sort();
+        stopped
+    }
+}
+
 pub struct StatusManager<T: DockerServiceTrait> {
     docker: T,
 }
@@ -62,7 +75,10 @@ impl<T: DockerServiceTrait> StatusManager<T> {
             let mut service_status = ServiceStatus {
                 name: service_name.to_string(),
                 running: docker_status.map(|s| s.running).unwrap_or(false),
-                healthy: false,
+                healthy: docker_status.is_some_and(|s| match &s.health {
+                    Some(health) => health == "healthy",
+                    None => s.running,
+                }),
                 endpoint: None,
                 version: None,
                 details: HashMap::new(),
@@ -119,17 +135,23 @@ impl<T: DockerServiceTrait> StatusManager<T> {
             }
         }
 
-        println!(
-            "\nLast Updated: {}",
-            status
-                .timestamp
-                .format(&time::format_description::well_known::Rfc3339)
-                .unwrap()
-        );
+        println!("\nLast Updated: {}", format_timestamp(status.timestamp));
         println!();
     }
 }
 
+/// Formats as RFC 3339, falling back to the raw Unix timestamp for values
+/// RFC 3339 can't represent (e.g. years outside 0..=9999).
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    match timestamp.format(&time::format_description::well_known::Rfc3339) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            warn!("Failed to format timestamp {:?}: {}", timestamp, e);
+            format!("{} (unix)", timestamp.unix_timestamp())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,6 +168,7 @@ mod tests {
                     running: true,
                     state: "running".to_string(),
                     ports: vec!["3000:3000".to_string()],
+                    health: None,
                 },
             )
             .await;
@@ -157,6 +180,7 @@ mod tests {
                     running: true,
                     state: "running".to_string(),
                     ports: vec!["2582:2582".to_string()],
+                    health: None,
                 },
             )
             .await;
@@ -168,6 +192,7 @@ mod tests {
                     running: false,
                     state: "exited".to_string(),
                     ports: vec![],
+                    health: None,
                 },
             )
             .await;
@@ -223,6 +248,27 @@ mod tests {
         assert!(system_status.timestamp <= after);
     }
 
+    #[tokio::test]
+    async fn test_json_output_and_stopped_services() {
+        let docker = setup_mock_docker().await;
+        let status_manager = StatusManager::new(docker);
+        let system_status = status_manager.get_status(false).await.unwrap();
+
+        let json = serde_json::to_value(&system_status).unwrap();
+        let timestamp = json["timestamp"].as_str().unwrap();
+        assert!(
+            OffsetDateTime::parse(timestamp, &time::format_description::well_known::Rfc3339)
+                .is_ok()
+        );
+        assert_eq!(json["services"]["pds"]["running"], true);
+
+        let expected: Vec<String> = ["caddy", "pds", "plc", "bgs"].map(String::from).to_vec();
+        assert_eq!(
+            system_status.stopped_services(&expected),
+            vec!["bgs".to_string()]
+        );
+    }
+
     #[tokio::test]
     async fn test_all_core_services_present() {
         let docker = setup_mock_docker().await;
@@ -274,18 +320,26 @@ mod tests {
     #[tokio::test]
     async fn test_service_health_tracking() {
         let docker = setup_mock_docker().await;
+        docker
+            .set_service_status(
+                "appview",
+                DockerServiceStatus {
+                    running: true,
+                    state: "running".to_string(),
+                    ports: vec![],
+                    health: Some("unhealthy".to_string()),
+                },
+            )
+            .await;
         let status_manager = StatusManager::new(docker);
 
         let system_status = status_manager.get_status(true).await.unwrap();
 
-        // Initially all services should be marked as not healthy
-        for (_, status) in system_status.services.iter() {
-            assert!(
-                !status.healthy,
-                "Service {} should initially be marked as not healthy",
-                status.name
-            );
-        }
+        // Without a healthcheck, a running service counts as healthy
+        assert!(system_status.services["pds"].healthy);
+        assert!(!system_status.services["bgs"].healthy);
+        assert!(!system_status.services["appview"].healthy);
+        assert!(!system_status.services["ozone"].healthy);
     }
 
     #[tokio::test]
@@ -301,4 +355,16 @@ mod tests {
             assert!(status.details.is_empty());
         }
     }
+
+    #[test]
+    fn test_format_timestamp_fallback() {
+        let epoch = OffsetDateTime::UNIX_EPOCH;
+        assert_eq!(format_timestamp(epoch), "1970-01-01T00:00:00Z");
+
+        let out_of_range = epoch.replace_year(-1).unwrap();
+        assert_eq!(
+            format_timestamp(out_of_range),
+            format!("{} (unix)", out_of_range.unix_timestamp())
+        );
+    }
 }
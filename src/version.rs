@@ -0,0 +1,92 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A `major.minor.patch` version, tolerant of a leading `v` and of
+/// pre-release/build suffixes (which are ignored for ordering).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim().trim_start_matches('v');
+        let core = trimmed.split(['-', '+']).next().unwrap_or_default();
+
+        let mut parts = core.split('.').map(|part| part.parse::<u64>());
+        let mut next = |required: bool| match parts.next() {
+            Some(Ok(n)) => Ok(n),
+            None if !required => Ok(0),
+            _ => Err(Error::Config(format!("Invalid version: {}", s))),
+        };
+
+        Ok(Self {
+            major: next(true)?,
+            minor: next(false)?,
+            patch: next(false)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Version> for String {
+    fn from(version: Version) -> Self {
+        version.to_string()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("1.2.3", Version::new(1, 2, 3) ; "plain")]
+    #[test_case("v0.4.0", Version::new(0, 4, 0) ; "leading v")]
+    #[test_case("2.29.1-desktop.1", Version::new(2, 29, 1) ; "pre-release suffix")]
+    #[test_case("28.2", Version::new(28, 2, 0) ; "missing patch")]
+    fn test_parse_version(input: &str, expected: Version) {
+        assert_eq!(input.parse::<Version>().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_invalid_version() {
+        assert!("".parse::<Version>().is_err());
+        assert!("latest".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(Version::new(0, 10, 0) > Version::new(0, 9, 9));
+        assert!(Version::new(1, 0, 0) > Version::new(0, 99, 99));
+    }
+}